@@ -1 +1,2 @@
+pub mod id_generator;
 pub mod utils;
\ No newline at end of file
@@ -0,0 +1,158 @@
+use std::collections::BTreeMap;
+use std::fmt::{Debug, Formatter};
+use std::sync::{Arc, Mutex};
+
+use chrono::NaiveDate;
+
+///The kinds of ids the engine generates. Each kind is drawn from its own sequence
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum IdType {
+    Order,
+    Execution,
+    Trade,
+}
+
+impl IdType {
+    ///Returns the character that starts the sequence number of an id of this kind
+    pub fn char_value(&self) -> char {
+        match self {
+            IdType::Order => 'O',
+            IdType::Execution => 'E',
+            IdType::Trade => 'T',
+        }
+    }
+}
+
+/// The IdGenerator trait is implemented by the generators of the order, execution and trade ids. Every
+/// id it returns for a kind must be unique for the session
+pub trait IdGenerator {
+    ///Returns the next id of the kind
+    fn next_id(&mut self, id_type: IdType) -> String;
+}
+
+///Generates ids from a monotonic sequence per kind of id, in the format
+/// `[<prefix>-][<YYYYMMDD>-]<O|E|T><sequence>`, such as `SIM-20240105-E17`. The sequences start after the
+/// seed, so that a generator seeded the same way always produces the same ids
+/// # Example
+///```rust
+/// use chrono::NaiveDate;
+/// use matching_engine::common::id_generator::{IdGenerator, IdType, SequentialIdGenerator};
+/// let mut generator = SequentialIdGenerator::new(Some("SIM".to_string()), NaiveDate::from_ymd_opt(2024, 1, 5));
+/// assert_eq!(generator.next_id(IdType::Execution), "SIM-20240105-E1");
+/// assert_eq!(generator.next_id(IdType::Execution), "SIM-20240105-E2");
+/// assert_eq!(generator.next_id(IdType::Order), "SIM-20240105-O1");
+///```
+#[derive(Debug, Clone, Default)]
+pub struct SequentialIdGenerator {
+    prefix: Option<String>,
+    session_date: Option<NaiveDate>,
+    seed: u64,
+    sequences: BTreeMap<IdType, u64>,
+}
+
+impl SequentialIdGenerator {
+    pub fn new(prefix: Option<String>, session_date: Option<NaiveDate>) -> Self {
+        Self { prefix, session_date, ..Self::default() }
+    }
+
+    ///Returns the generator with every sequence starting after `seed`
+    pub fn seeded(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self.sequences.clear();
+        self
+    }
+
+    pub fn prefix(&self) -> Option<&str> {
+        self.prefix.as_deref()
+    }
+
+    pub fn session_date(&self) -> Option<NaiveDate> {
+        self.session_date
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+}
+
+impl IdGenerator for SequentialIdGenerator {
+    fn next_id(&mut self, id_type: IdType) -> String {
+        let sequence = self.sequences.entry(id_type).or_insert(self.seed);
+        *sequence += 1;
+        let mut id = String::new();
+        if let Some(prefix) = &self.prefix {
+            id.push_str(prefix);
+            id.push('-');
+        }
+        if let Some(session_date) = self.session_date {
+            id.push_str(&session_date.format("%Y%m%d-").to_string());
+        }
+        id.push(id_type.char_value());
+        id.push_str(&sequence.to_string());
+        id
+    }
+}
+
+///A handle on the id generator of an order book, which the matchers draw the ids of the reports they
+/// create from. Clones of the handle share the generator. Until another generator is given, ids are drawn
+/// from an unseeded [`SequentialIdGenerator`] without a prefix or a session date
+#[derive(Clone)]
+pub struct SharedIdGenerator(Arc<Mutex<Box<dyn IdGenerator + Send>>>);
+
+impl SharedIdGenerator {
+    pub fn new(generator: Box<dyn IdGenerator + Send>) -> Self {
+        Self(Arc::new(Mutex::new(generator)))
+    }
+
+    ///Returns the next id of the kind from the shared generator
+    pub fn next_id(&self, id_type: IdType) -> String {
+        self.0.lock().unwrap_or_else(|e| e.into_inner()).next_id(id_type)
+    }
+}
+
+impl Default for SharedIdGenerator {
+    fn default() -> Self {
+        Self::new(Box::<SequentialIdGenerator>::default())
+    }
+}
+
+impl Debug for SharedIdGenerator {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str("SharedIdGenerator")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::common::id_generator::{IdGenerator, IdType, SequentialIdGenerator, SharedIdGenerator};
+
+    #[test]
+    fn test_sequences_are_monotonic_per_id_type() {
+        let mut generator = SequentialIdGenerator::default();
+        assert_eq!(generator.next_id(IdType::Order), "O1");
+        assert_eq!(generator.next_id(IdType::Execution), "E1");
+        assert_eq!(generator.next_id(IdType::Execution), "E2");
+        assert_eq!(generator.next_id(IdType::Trade), "T1");
+        assert_eq!(generator.next_id(IdType::Order), "O2");
+    }
+
+    #[test]
+    fn test_seeded_generators_repeat_their_ids() {
+        let ids = |generator: &mut SequentialIdGenerator| -> Vec<String> {
+            (0..3).map(|_| generator.next_id(IdType::Trade)).collect()
+        };
+        let mut first = SequentialIdGenerator::new(Some("SIM".to_string()), None).seeded(1000);
+        let mut second = SequentialIdGenerator::new(Some("SIM".to_string()), None).seeded(1000);
+        assert_eq!(ids(&mut first), vec!["SIM-T1001", "SIM-T1002", "SIM-T1003"]);
+        assert_eq!(ids(&mut first.clone().seeded(1000)), ids(&mut second));
+    }
+
+    #[test]
+    fn test_clones_of_a_shared_generator_draw_from_the_same_sequences() {
+        let generator = SharedIdGenerator::default();
+        let clone = generator.clone();
+        assert_eq!(generator.next_id(IdType::Execution), "E1");
+        assert_eq!(clone.next_id(IdType::Execution), "E2");
+        assert_eq!(SharedIdGenerator::default().next_id(IdType::Execution), "E1");
+    }
+}
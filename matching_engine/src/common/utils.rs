@@ -2,24 +2,26 @@ use std::fs::{File, OpenOptions};
 use std::io::{BufRead, BufReader, Write};
 use std::str::FromStr;
 use std::sync::Mutex;
-use std::time::{SystemTime, UNIX_EPOCH};
 
-use chrono::{DateTime, Local};
+use chrono::{DateTime, Local, NaiveDate, NaiveTime};
 use log::{error, trace};
-use rand::Rng;
 
-use crate::model::domain::{Fill, OrderBook, OrderSingle, OrderType, Side};
+use crate::model::cancel::{CancelRequest, ReplaceRequest};
+use crate::model::domain::{Fill, OrderBook, OrderSingle, OrderType, PegType, PostOnly, SelfTradePrevention, Side, TimeInForce};
+use crate::model::instrument::{Instrument, InstrumentRegistry, PriceBand};
+use crate::model::price::Price;
+use crate::model::session::{ScheduleEntry, SessionSchedule, TradingPhase};
 
 pub struct Aggregator;
 
 
 pub trait Sigma<T> {
     ///Aggregates quantities in a vec of orders or fills
-    fn sigma(items: &Vec<T>) -> u32;
+    fn sigma(items: &[T]) -> u32;
 }
 
 impl Sigma<OrderSingle> for Aggregator {
-    fn sigma(items: &Vec<OrderSingle>) -> u32 {
+    fn sigma(items: &[OrderSingle]) -> u32 {
         let mut sum = 0;
         for order in items {
             sum += order.qty();
@@ -30,7 +32,7 @@ impl Sigma<OrderSingle> for Aggregator {
 }
 
 impl Sigma<Fill> for Aggregator {
-    fn sigma(items: &Vec<Fill>) -> u32 {
+    fn sigma(items: &[Fill]) -> u32 {
         let mut sum = 0;
         for fill in items {
             sum += fill.qty();
@@ -60,15 +62,6 @@ pub fn read_input(file_path: &str) -> Vec<String> {
     lines
 }
 
-///Generates a unique id
-pub fn generate_id() -> String {
-    let num: u64 = rand::thread_rng().gen_range(1..=1000000);
-    let now = SystemTime::now();
-    let duration = now.duration_since(UNIX_EPOCH).expect("Time went backwards");
-    let id = duration.as_secs() * 1000 + duration.subsec_millis() as u64;
-    (num + id).to_string()
-}
-
 ///Creates an [`OrderBook`] with the orders in the input vector
 /// # Example:
 ///```rust
@@ -76,57 +69,274 @@ pub fn generate_id() -> String {
 /// let input = read_input("test_data/orders.txt");
 /// let mut order_book = create_order_book(input);
 pub fn create_order_book(input: Vec<String>) -> OrderBook {
-    let mut order_book = OrderBook::default();
+    create_order_book_with_instruments(input, InstrumentRegistry::default())
+}
+
+///Creates an [`OrderBook`] with the orders in the input vector that are valid for the instruments.
+/// Orders that violate the reference data of their symbol, or whose symbol is unknown, are rejected
+/// # Example:
+///```rust
+/// use matching_engine::common::utils::{create_instrument_registry, create_order_book_with_instruments, read_input};
+/// let instruments = create_instrument_registry(read_input("test_data/instruments.txt")).unwrap();
+/// let input = read_input("test_data/orders.txt");
+/// let mut order_book = create_order_book_with_instruments(input, instruments);
+pub fn create_order_book_with_instruments(input: Vec<String>, instruments: InstrumentRegistry) -> OrderBook {
+    let mut order_book = OrderBook::with_instruments(instruments);
     if input.len() > 1 {
         trace!("Creating order book");
         for line in input {
             let order = create_order_from_string(line);
-            if order.is_valid() {
-                order_book.add_order_to_order_book(order);
+            match order_book.validate_order(&order) {
+                Ok(()) => order_book.add_order_to_order_book(order),
+                Err(e) => error!("Order {} rejected: {}", order.cl_ord_id(), e),
             }
         }
     }
     order_book
 }
-///Creates an Order from the string
+
+///Creates an [`InstrumentRegistry`] from the input vector, one instrument per line in the format
+/// `<symbol> <tick_size> <lot_size> <min_qty> <price_precision>` optionally followed by `band=<width>`, the
+/// static price band, `dynband=<width>`, the dynamic price band, where a width is a percentage such as
+/// `5%` or an absolute amount, and `group=<name>`, the instrument group. Blank lines and lines starting with
+/// `#` are ignored
+/// # Example
+///```rust
+/// use matching_engine::common::utils::{create_instrument_registry, read_input};
+/// let instruments = create_instrument_registry(read_input("test_data/instruments.txt")).unwrap();
+/// assert!(instruments.get("IBM").is_some());
+///```
+pub fn create_instrument_registry(input: Vec<String>) -> Result<InstrumentRegistry, String> {
+    let mut registry = InstrumentRegistry::default();
+    for line in input {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        registry.add(create_instrument_from_string(line)?);
+    }
+    Ok(registry)
+}
+
+///Creates an [`Instrument`] from a line in the format `<symbol> <tick_size> <lot_size> <min_qty> <price_precision>`
+/// followed by the optional `band=<width>`, `dynband=<width>` and `group=<name>` attributes
+pub fn create_instrument_from_string(line: &str) -> Result<Instrument, String> {
+    let tokens: Vec<&str> = line.split_whitespace().collect();
+    if tokens.len() < 5 {
+        return Err(format!("Instrument should contain 5 fields <symbol tick_size lot_size min_qty price_precision>: {}", line));
+    }
+    let tick_size = Price::from_str(tokens[1])?;
+    let lot_size = u32::from_str(tokens[2]).map_err(|e| format!("invalid lot size {} {}", tokens[2], e))?;
+    let min_qty = u32::from_str(tokens[3]).map_err(|e| format!("invalid minimum quantity {} {}", tokens[3], e))?;
+    let price_precision = u32::from_str(tokens[4]).map_err(|e| format!("invalid price precision {} {}", tokens[4], e))?;
+    let mut instrument = Instrument::new(tokens[0].to_string(), tick_size, lot_size, min_qty, price_precision);
+    for token in &tokens[5..] {
+        match token.split_once('=') {
+            Some(("band", value)) => instrument.set_price_band(Some(PriceBand::from_str(value)?)),
+            Some(("dynband", value)) => instrument.set_dynamic_band(Some(PriceBand::from_str(value)?)),
+            Some(("group", value)) if !value.is_empty() => instrument.set_group(Some(value.to_string())),
+            _ => return Err(format!("invalid instrument attribute {}", token)),
+        }
+    }
+    Ok(instrument)
+}
+
+///Creates a [`SessionSchedule`] from the input vector, one phase change per line in the format
+/// `<HH:MM[:SS]> <symbol> <phase>`. Blank lines and lines starting with `#` are ignored
+/// # Example
+///```rust
+/// use matching_engine::common::utils::{create_session_schedule, read_input};
+/// let schedule = create_session_schedule(read_input("test_data/schedule.txt")).unwrap();
+/// assert!(!schedule.entries().is_empty());
+///```
+pub fn create_session_schedule(input: Vec<String>) -> Result<SessionSchedule, String> {
+    let mut entries = vec![];
+    for line in input {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        if tokens.len() != 3 {
+            return Err(format!("Schedule entry should contain 3 fields <time symbol phase>: {}", line));
+        }
+        let time = NaiveTime::parse_from_str(tokens[0], "%H:%M:%S")
+            .or_else(|_| NaiveTime::parse_from_str(tokens[0], "%H:%M"))
+            .map_err(|e| format!("invalid schedule time {} {}", tokens[0], e))?;
+        entries.push(ScheduleEntry::new(time, tokens[1].to_string(), TradingPhase::from_str(tokens[2])?));
+    }
+    Ok(SessionSchedule::new(entries))
+}
+
+///Creates an Order from the string `<cl_ord_id> <symbol> <qty> <price> <side>` followed by optional
+/// `key=value` attributes. A price of `MKT` creates a market order and a price of `PEG` a pegged order,
+/// whose price is set by the book. The supported attributes are `tif=<Day|GTC|IOC|FOK|GTD>`,
+/// `expire=<YYYY-MM-DD>` for GTD orders, `display=<qty>` for iceberg orders, `stop=<stop price>`, which
+/// makes a market order a stop order and a limit order a stop limit order, `peg=<MID|PRIMARY|MARKET>` and
+/// `offset=<price>`, the signed offset added to the reference price of a pegged order, and
+/// `postonly=<REJECT|REPRICE>`, which keeps the order from crossing the contra side on arrival,
+/// `participant=<id>`, `stp=<CN|CO|CB|DC>`, the self-trade prevention mode of the order, `minqty=<qty>`,
+/// the smallest quantity the order trades in one go, `aon=<Y|N>` for all-or-none orders and `lmm=<Y|N>`
+/// for the orders of a lead market maker
 /// # Example
 ///```rust
 /// let order_string = "test1 IBM 100 150 Buy";
 /// use matching_engine::common::utils::create_order_from_string;
+/// use matching_engine::model::domain::TimeInForce;
 /// let order = create_order_from_string(order_string.to_string());
+/// let market_order = create_order_from_string("test2 IBM 100 MKT Sell".to_string());
+/// assert!(market_order.is_market());
+/// let ioc_order = create_order_from_string("test3 IBM 100 150 Buy tif=IOC".to_string());
+/// assert_eq!(ioc_order.time_in_force(), TimeInForce::IOC);
+///```
 pub fn create_order_from_string(line: String) -> OrderSingle {
-    let tokens: Vec<&str> = line.split(" ").collect();
-    if tokens.len() != 5 {
-        error!("\nInput should contain 5 fields <cl_ord_id symbol qty px side>. Sending order book for matching");
+    let tokens: Vec<&str> = line.split_whitespace().collect();
+    if tokens.len() < 5 {
+        error!("\nInput should contain 5 fields <cl_ord_id symbol qty px side> and optional key=value attributes. Sending order book for matching");
         OrderSingle::default()
     } else {
-        let _order = OrderSingle::default();
         trace!("received vector {:?}",tokens);
         let cl_ord_id = String::from(tokens[0]);
         let symbol = String::from(tokens[1]);
         let qty = u32::from_str(tokens[2]).unwrap();
-        let price = f64::from_str(tokens[3]).unwrap();
+        let order_type = if tokens[3].eq_ignore_ascii_case("MKT") { OrderType::Market } else { OrderType::Limit };
+        let price = match Price::from_str(tokens[3]) {
+            Ok(price) => price,
+            Err(_) if order_type == OrderType::Market || tokens[3].eq_ignore_ascii_case("PEG") => Price::ZERO,
+            Err(e) => {
+                error!("\n{}. Sending order book for matching", e);
+                return OrderSingle::default();
+            }
+        };
         let order_side = tokens[4];
         trace!("order side = {order_side}");
         let side = if order_side == "Buy" { Side::Buy } else { Side::Sell };
         trace!("side is {}",side.string_value());
-        OrderSingle::new(qty, symbol, price, side, OrderType::Limit, cl_ord_id)
+        let mut order = OrderSingle::new(qty, symbol, price, side, order_type, cl_ord_id);
+        if let Err(e) = set_order_attributes(&mut order, &tokens[5..]) {
+            error!("\n{}. Sending order book for matching", e);
+            return OrderSingle::default();
+        }
+        order
     }
+}
 
-    // order
+///Applies the optional `key=value` attributes that follow the mandatory fields of an order
+fn set_order_attributes(order: &mut OrderSingle, attributes: &[&str]) -> Result<(), String> {
+    for attribute in attributes {
+        let (key, value) = attribute.split_once('=')
+            .ok_or_else(|| format!("invalid order attribute {}", attribute))?;
+        match key {
+            "tif" => order.set_time_in_force(TimeInForce::from_str(value)?),
+            "expire" => {
+                let date = NaiveDate::parse_from_str(value, "%Y-%m-%d")
+                    .map_err(|e| format!("invalid expire date {} {}", value, e))?;
+                order.set_expire_date(Some(date));
+            }
+            "stop" => {
+                order.set_stop_price(Some(Price::from_str(value)?));
+                order.set_order_type(if order.is_market() { OrderType::Stop } else { OrderType::StopLimit });
+            }
+            "display" => {
+                let display_qty = u32::from_str(value).map_err(|e| format!("invalid display quantity {} {}", value, e))?;
+                order.set_display_qty(Some(display_qty));
+            }
+            "peg" => order.set_peg(Some(PegType::from_str(value)?)),
+            "offset" => order.set_peg_offset(Price::from_str(value)?),
+            "postonly" => order.set_post_only(Some(PostOnly::from_str(value)?)),
+            "participant" => order.set_participant(Some(value.to_string())),
+            "stp" => order.set_self_trade_prevention(SelfTradePrevention::from_str(value)?),
+            "minqty" => {
+                let min_qty = u32::from_str(value).map_err(|e| format!("invalid minimum quantity {} {}", value, e))?;
+                order.set_min_qty(Some(min_qty));
+            }
+            "aon" => match value {
+                "Y" => order.set_all_or_none(true),
+                "N" => order.set_all_or_none(false),
+                _ => return Err(format!("invalid all or none flag {}", value)),
+            },
+            "lmm" => match value {
+                "Y" => order.set_lead_market_maker(true),
+                "N" => order.set_lead_market_maker(false),
+                _ => return Err(format!("invalid lead market maker flag {}", value)),
+            },
+            _ => return Err(format!("unknown order attribute {}", key)),
+        }
+    }
+    Ok(())
 }
+
+///Creates a [`CancelRequest`] from a command in the format `cancel <cl_ord_id> [symbol=<symbol>] [side=<Buy|Sell>]`
+/// # Example
+///```rust
+/// use matching_engine::common::utils::create_cancel_request_from_string;
+/// let request = create_cancel_request_from_string("cancel id1 symbol=IBM").unwrap();
+/// assert_eq!(request.cl_ord_id(), "id1");
+/// assert_eq!(request.symbol(), Some("IBM"));
+///```
+pub fn create_cancel_request_from_string(line: &str) -> Result<CancelRequest, String> {
+    let tokens: Vec<&str> = line.split_whitespace().collect();
+    if tokens.len() < 2 || tokens[0] != "cancel" {
+        return Err(format!("Cancel should be in the format <cancel cl_ord_id [symbol=<symbol>] [side=<Buy|Sell>]>: {}", line));
+    }
+    let mut symbol = None;
+    let mut side = None;
+    for attribute in &tokens[2..] {
+        match attribute.split_once('=') {
+            Some(("symbol", value)) => symbol = Some(value.to_string()),
+            Some(("side", "Buy")) => side = Some(Side::Buy),
+            Some(("side", "Sell")) => side = Some(Side::Sell),
+            _ => return Err(format!("invalid cancel attribute {}", attribute)),
+        }
+    }
+    Ok(CancelRequest::new(tokens[1].to_string(), symbol, side))
+}
+
+///Creates a [`ReplaceRequest`] from a command in the format
+/// `replace <cl_ord_id> [qty=<qty>] [price=<price>] [symbol=<symbol>] [side=<Buy|Sell>]`
+/// # Example
+///```rust
+/// use matching_engine::common::utils::create_replace_request_from_string;
+/// let request = create_replace_request_from_string("replace id1 qty=50 price=601.5").unwrap();
+/// assert_eq!(request.qty(), Some(50));
+/// assert_eq!(request.price().unwrap().to_string(), "601.5");
+///```
+pub fn create_replace_request_from_string(line: &str) -> Result<ReplaceRequest, String> {
+    let tokens: Vec<&str> = line.split_whitespace().collect();
+    if tokens.len() < 3 || tokens[0] != "replace" {
+        return Err(format!("Replace should be in the format <replace cl_ord_id [qty=<qty>] [price=<price>] [symbol=<symbol>] [side=<Buy|Sell>]>: {}", line));
+    }
+    let mut qty = None;
+    let mut price = None;
+    let mut symbol = None;
+    let mut side = None;
+    for attribute in &tokens[2..] {
+        match attribute.split_once('=') {
+            Some(("qty", value)) => qty = Some(u32::from_str(value).map_err(|e| format!("invalid quantity {} {}", value, e))?),
+            Some(("price", value)) => price = Some(Price::from_str(value)?),
+            Some(("symbol", value)) => symbol = Some(value.to_string()),
+            Some(("side", "Buy")) => side = Some(Side::Buy),
+            Some(("side", "Sell")) => side = Some(Side::Sell),
+            _ => return Err(format!("invalid replace attribute {}", attribute)),
+        }
+    }
+    let mut request = ReplaceRequest::new(tokens[1].to_string(), qty, price);
+    request.set_symbol(symbol);
+    request.set_side(side);
+    Ok(request)
+}
+
 /// logs to a file. Use appropriate logger back end to log messages to a file
 #[deprecated]
 pub fn log(message: &String, log_file: &str) {
-    let mut file = OpenOptions::new()
-        .write(true)
+    let file = OpenOptions::new()
         .append(true)
         .open(log_file).unwrap();
     let utc: DateTime<Local> = Local::now();
 
     let formatted_timestamp = utc.format("%Y/%m/%d/%H:%M:%S:%3f").to_string();
 
-    let mut log_message = format!("\n{}-{}", formatted_timestamp, message);
+    let log_message = format!("\n{}-{}", formatted_timestamp, message);
     let file_lock = Mutex::new(file);
     {
         let mut file_guard = file_lock.lock().unwrap();
@@ -139,9 +349,14 @@ pub fn log(message: &String, log_file: &str) {
 #[cfg(test)]
 mod tests {
 
-    use crate::common::utils::{create_order_from_string};
-    use crate::model::domain::{OrderBook, OrderBookKey};
-    use crate::model::domain::Side::Buy;
+    use crate::common::utils::{create_cancel_request_from_string, create_instrument_from_string, create_instrument_registry, create_order_book_with_instruments, create_order_from_string, create_replace_request_from_string, create_session_schedule, read_input};
+    use chrono::{NaiveDate, NaiveTime};
+
+    use crate::model::domain::{OrderBook, OrderBookKey, OrderType, PegType, PostOnly, TimeInForce};
+    use crate::model::domain::Side::{Buy, Sell};
+    use crate::model::instrument::PriceBand;
+    use crate::model::price::Price;
+    use crate::model::session::TradingPhase;
 
 
 
@@ -156,9 +371,9 @@ mod tests {
         order_book.add_order_to_order_book(create_order_from_string(order2.to_string()));
         order_book.add_order_to_order_book(create_order_from_string(order3.to_string()));
         order_book.add_order_to_order_book(create_order_from_string(order4.to_string()));
-        let key1 = OrderBookKey::new(602.5, "IBM".to_string());
-        let key2 = OrderBookKey::new(601.1, "IBM".to_string());
-        let key3 = OrderBookKey::new(601.9, "IBM".to_string());
+        let key1 = OrderBookKey::new(Price::new(6025, 1), "IBM".to_string());
+        let key2 = OrderBookKey::new(Price::new(6011, 1), "IBM".to_string());
+        let key3 = OrderBookKey::new(Price::new(6019, 1), "IBM".to_string());
         let buy = order_book.get_orders_for(Buy);
         let orders = buy.get(&key1).unwrap();
         assert_eq!(buy.len(),3);
@@ -172,4 +387,115 @@ mod tests {
         assert_eq!(orders.iter().len(), 1);
 
     }
+
+    #[test]
+    fn test_create_order_book_rejects_orders_violating_instruments() {
+        let instruments = create_instrument_registry(read_input("test_data/instruments.txt")).unwrap();
+        let input = vec![
+            "id1 IBM 300 602.5 Buy".to_string(),
+            "id2 IBM 300 602.55 Sell".to_string(),
+            "id3 INFY 100 1500 Buy".to_string(),
+            "id4 TATA 25 602.5 Buy".to_string(),
+            "id5 TATA 20 602.5 Sell".to_string(),
+        ];
+        let order_book = create_order_book_with_instruments(input, instruments);
+
+        assert_eq!(order_book.get_orders_for(Buy).len(), 1);
+        assert_eq!(order_book.get_orders_for(Sell).len(), 1);
+        assert_eq!(order_book.get_excl_keys(), vec!["IBM", "TATA"]);
+    }
+
+    #[test]
+    fn test_create_order_with_attributes() {
+        let order = create_order_from_string("id1 IBM 100 602.5 Buy tif=GTD expire=2026-10-19".to_string());
+        assert_eq!(order.time_in_force(), TimeInForce::GTD);
+        assert_eq!(order.expire_date(), NaiveDate::from_ymd_opt(2026, 10, 19));
+        assert!(order.is_valid());
+
+        assert_eq!(create_order_from_string("id2 IBM 100 602.5 Buy".to_string()).time_in_force(), TimeInForce::GTC);
+        assert!(!create_order_from_string("id3 IBM 100 602.5 Buy tif=GTD".to_string()).is_valid());
+        assert!(!create_order_from_string("id4 IBM 100 602.5 Buy tif=XYZ".to_string()).is_valid());
+        assert!(!create_order_from_string("id5 IBM 100 602.5 Buy colour=red".to_string()).is_valid());
+
+        let stop_limit = create_order_from_string("id6 IBM 100 602.5 Buy stop=602".to_string());
+        assert_eq!(stop_limit.order_type(), OrderType::StopLimit);
+        assert_eq!(stop_limit.stop_price(), Some(Price::new(602, 0)));
+        assert_eq!(create_order_from_string("id7 IBM 100 MKT Sell stop=600".to_string()).order_type(), OrderType::Stop);
+
+        let pegged = create_order_from_string("id8 IBM 100 PEG Buy peg=PRIMARY offset=-0.1".to_string());
+        assert_eq!(pegged.peg(), Some(PegType::Primary));
+        assert_eq!(pegged.peg_offset(), Price::new(-1, 1));
+        assert!(pegged.is_valid());
+        assert!(!create_order_from_string("id9 IBM 100 PEG Buy".to_string()).is_valid());
+        assert!(!create_order_from_string("id10 IBM 100 PEG Buy peg=MID tif=IOC".to_string()).is_valid());
+
+        let post_only = create_order_from_string("id11 IBM 100 602.5 Buy postonly=reprice".to_string());
+        assert_eq!(post_only.post_only(), Some(PostOnly::Reprice));
+        assert!(post_only.is_valid());
+        assert!(!create_order_from_string("id12 IBM 100 MKT Buy postonly=reject".to_string()).is_valid());
+
+        let block = create_order_from_string("id13 IBM 500 602.5 Buy minqty=200".to_string());
+        assert_eq!(block.min_qty(), Some(200));
+        assert_eq!(block.min_fill_qty(), 200);
+        assert!(create_order_from_string("id14 IBM 500 602.5 Buy aon=Y".to_string()).all_or_none());
+        assert!(!create_order_from_string("id15 IBM 500 602.5 Buy aon=Y display=100".to_string()).is_valid());
+        assert!(create_order_from_string("id16 IBM 500 602.5 Sell lmm=Y".to_string()).is_lead_market_maker());
+    }
+
+    #[test]
+    fn test_create_cancel_request() {
+        let request = create_cancel_request_from_string("cancel id1 symbol=IBM side=Sell").unwrap();
+        assert_eq!(request.cl_ord_id(), "id1");
+        assert_eq!(request.symbol(), Some("IBM"));
+        assert_eq!(request.side(), Some(Sell));
+        assert_eq!(create_cancel_request_from_string("cancel id2").unwrap().side(), None);
+        assert!(create_cancel_request_from_string("cancel").is_err());
+        assert!(create_cancel_request_from_string("cancel id3 side=Up").is_err());
+    }
+
+    #[test]
+    fn test_create_replace_request() {
+        let request = create_replace_request_from_string("replace id1 qty=50 price=601.5 side=Buy").unwrap();
+        assert_eq!(request.cl_ord_id(), "id1");
+        assert_eq!(request.qty(), Some(50));
+        assert_eq!(request.price(), Some(Price::new(6015, 1)));
+        assert_eq!(request.side(), Some(Buy));
+        assert_eq!(create_replace_request_from_string("replace id2 qty=20").unwrap().price(), None);
+        assert!(create_replace_request_from_string("replace id3").is_err());
+        assert!(create_replace_request_from_string("replace id4 qty=ten").is_err());
+    }
+
+    #[test]
+    fn test_create_instrument_registry() {
+        let instruments = create_instrument_registry(read_input("test_data/instruments.txt")).unwrap();
+        let tata = instruments.get("TATA").unwrap();
+
+        assert_eq!(instruments.len(), 2);
+        assert_eq!(tata.tick_size(), Price::new(5, 2));
+        assert_eq!(tata.lot_size(), 10);
+        assert_eq!(tata.min_qty(), 10);
+        assert_eq!(tata.price_precision(), 2);
+        assert!(create_instrument_registry(vec!["IBM 0.1 1".to_string()]).is_err());
+        assert!(create_instrument_registry(vec!["IBM x 1 1 2".to_string()]).is_err());
+        let banded = create_instrument_from_string("IBM 0.1 1 1 2 band=10% dynband=5").unwrap();
+        assert_eq!(banded.price_band(), Some(PriceBand::Percent(Price::new(10, 0))));
+        assert_eq!(banded.dynamic_band(), Some(PriceBand::Absolute(Price::new(5, 0))));
+        assert!(create_instrument_from_string("IBM 0.1 1 1 2 band=x").is_err());
+        assert!(create_instrument_from_string("IBM 0.1 1 1 2 limit=5%").is_err());
+        assert_eq!(create_instrument_from_string("ES 0.25 1 1 2 group=FUT").unwrap().group(), Some("FUT"));
+        assert!(create_instrument_from_string("ES 0.25 1 1 2 group=").is_err());
+    }
+
+    #[test]
+    fn test_create_session_schedule() {
+        let schedule = create_session_schedule(read_input("test_data/schedule.txt")).unwrap();
+        let first = &schedule.entries()[0];
+
+        assert_eq!(schedule.entries().len(), 5);
+        assert_eq!(first.time(), NaiveTime::from_hms_opt(8, 0, 0).unwrap());
+        assert_eq!(first.phase(), TradingPhase::PreOpen);
+        assert!(create_session_schedule(vec!["09:30 IBM".to_string()]).is_err());
+        assert!(create_session_schedule(vec!["09:30 IBM Lunch".to_string()]).is_err());
+        assert!(create_session_schedule(vec!["9h30 IBM Continuous".to_string()]).is_err());
+    }
 }
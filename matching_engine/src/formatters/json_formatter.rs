@@ -10,10 +10,7 @@ pub struct JsonFormatter {}
 
 impl JsonFormatter {
     pub fn is_valid_json(val: &str) -> bool {
-        match from_str::<Value>(val) {
-            Ok(_) => true,
-            Err(_) => false,
-        }
+        from_str::<Value>(val).is_ok()
     }
 }
 
@@ -40,9 +37,10 @@ impl Formatter<OrderSingle> for JsonFormatter {
 mod tests {
     use serde_json::to_string;
 
+    use crate::common::id_generator::SharedIdGenerator;
     use crate::formatters::formatter::Formatter;
     use crate::formatters::json_formatter::JsonFormatter;
-    use crate::model::domain::Fill;
+    use crate::model::domain::{Fill, OrderSingle};
     use crate::utils::create_order_from_string;
 
     #[test]
@@ -51,9 +49,30 @@ mod tests {
         let string_val = to_string(&order).unwrap();
         println!("{string_val}");
         assert!(JsonFormatter::is_valid_json(&string_val));
-        let fill = Fill::from(&order);
+        let fill = Fill::from(&order, &SharedIdGenerator::default());
         let string_val = JsonFormatter::format_to(fill);
         println!("{string_val}");
         assert!(JsonFormatter::is_valid_json(&string_val))
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_order_price_round_trips_without_loss() {
+        let order = create_order_from_string("id1 IBM 20 602.123456789 Buy".to_string());
+        let json = <JsonFormatter as Formatter<OrderSingle>>::format_to(order.clone());
+        let parsed: OrderSingle = JsonFormatter::format_from(json);
+        assert_eq!(parsed.price(), order.price());
+        assert_eq!(parsed.price().to_string(), "602.123456789");
+    }
+
+    #[test]
+    fn test_market_order_without_price() {
+        let json = r#"{"qty":100,"symbol":"IBM","side":"Sell","order_type":"Market","cl_ord_id":"id2"}"#;
+        let order: OrderSingle = JsonFormatter::format_from(json.to_string());
+        assert!(order.is_market());
+        assert!(order.is_valid());
+
+        let market = create_order_from_string("id3 IBM 100 MKT Buy".to_string());
+        let parsed: OrderSingle = JsonFormatter::format_from(<JsonFormatter as Formatter<OrderSingle>>::format_to(market));
+        assert!(parsed.is_market());
+    }
+}
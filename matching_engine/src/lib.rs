@@ -1,19 +1,20 @@
 extern crate log;
 
-use std::{env, io, process};
-use std::io::BufRead;
+use std::{env, io, mem, process};
+use std::str::FromStr;
 use std::sync::mpsc::{Receiver, Sender};
-use std::thread::sleep;
-use std::time::Duration;
 
+use chrono::{Local, NaiveDate};
 use colored::Colorize;
-use log::{error, info};
+use log::{error, info, trace};
 
 use crate::common::utils;
-use crate::matchers::fifo_matcher::FIFOMatcher;
+use crate::matchers::auction::{AuctionAllocation, CallAuction};
 use crate::matchers::matcher::Matcher;
-use crate::matchers::prorata_matcher::ProrataMatcher;
-use crate::model::domain::OrderBook;
+use crate::matchers::matcher_registry::MatcherRegistry;
+use crate::model::command::Command;
+use crate::model::domain::{Fill, OrderBook};
+use crate::model::session::{SessionSchedule, TradingPhase};
 
 pub mod model;
 pub mod matchers;
@@ -26,6 +27,8 @@ pub mod common;
 pub struct CmdArgs {
     pub file_path: String,
     pub algo: String,
+    pub instruments_path: String,
+    pub schedule_path: String,
 }
 
 fn print_help() {
@@ -33,61 +36,183 @@ fn print_help() {
     println!("If using cargo:");
     println!("cargo -- -h for help");
     println!("cargo run <Enter> for starting without an orderbook or algo param");
-    println!("cargo run -- <order_file_path> <algo(FIFO|PRO>");
+    println!("cargo run -- <order_file_path> <algo(FIFO|PRO|PIPELINE)[,<symbol|group>=<FIFO|PRO|PIPELINE>...]> [instrument_file_path] [schedule_file_path]");
     println!();
     println!("If using executable:");
     println!("exchange_simulator <Enter> for starting without an orderbook or algo param");
     println!("exchange_simulator.exe -h for help");
-    println!("exchange_simulator.exe <order_file+path> <algo(FIFO|PRO>");
+    println!("exchange_simulator.exe <order_file+path> <algo(FIFO|PRO|PIPELINE)[,<symbol|group>=<FIFO|PRO|PIPELINE>...]> [instrument_file_path] [schedule_file_path]");
+    println!();
+    println!("The instrument file holds one instrument per line: <symbol> <tick_size> <lot_size> <min_qty> <price_precision> [band=<width>] [dynband=<width>] [group=<name>]");
+    println!("When it is given, orders for unknown symbols or that violate the instrument are rejected");
+    println!("The algo is the default matching algorithm, optionally followed by the algorithm of symbols or instrument groups, e.g. FIFO,FUT=PRO");
+    println!("The schedule file holds one trading phase change per line: <HH:MM[:SS]> <symbol> <phase>");
     process::exit(0);
 }
 ///This function sits in a loop accepting user input for matching until the user quits
-/// User inputs are added to the order book and sent to the matcher for matching
-pub fn start_user(tx: &Sender<OrderBook>, rx: &Receiver<OrderBook>, file_path: String) {
+/// The order book is loaded and sent to the matcher thread together with the schedule. Each input is then
+/// parsed into a command and sent to the matcher thread, which applies it to the order book it owns. The
+/// next input is read once the matcher has acknowledged the command
+pub fn start_user(tx: &Sender<Command>, rx: &Receiver<()>, file_path: String, instruments_path: String, schedule_path: String) {
     if file_path == "-h" {
         print_help();
     }
+    let instruments = match utils::create_instrument_registry(utils::read_input(&instruments_path)) {
+        Ok(instruments) => instruments,
+        Err(e) => {
+            error!("Error reading instruments {}", e);
+            process::exit(1);
+        }
+    };
+    let schedule = match utils::create_session_schedule(utils::read_input(&schedule_path)) {
+        Ok(schedule) => schedule,
+        Err(e) => {
+            error!("Error reading schedule {}", e);
+            process::exit(1);
+        }
+    };
     let input = utils::read_input(&file_path);
-    let mut ob = utils::create_order_book(input);
-    if let Err(e) = tx.send(ob) {
-        error!("Error sending file {}",e);
-    }
-    sleep(Duration::from_secs(1));
+    let ob = utils::create_order_book_with_instruments(input, instruments);
+    send_command(tx, rx, Command::Load(ob, schedule));
     loop {
-        let mut order_book = match rx.recv() {
-            Ok(ob) => ob,
-            Err(e) => {
-                error!("Error receiving order book {}",e);
-                OrderBook::default()
-            }
-        };
-
-        order_book.pretty_print_self();
-        println!("Enter an order({} ) to match  or {} to quit","<id> <symbol> <qty> <price> <side(Buy|Sell)>".bold().reversed(), "q".bold().red());
-        println!("");
+        println!("Enter an order({} ) to match, {} to cancel an order, {} to replace an order, {} to change the trading phase of a symbol, {} to end the session or {} to quit",
+                 "<id> <symbol> <qty> <price|MKT> <side(Buy|Sell)> [tif=<Day|GTC|IOC|FOK|GTD>] [expire=<YYYY-MM-DD>]".bold().reversed(),
+                 "cancel <id> [symbol=<symbol>] [side=<Buy|Sell>]".bold(),
+                 "replace <id> [qty=<qty>] [price=<price>] [symbol=<symbol>] [side=<Buy|Sell>]".bold(),
+                 "phase <symbol> <PreOpen|OpeningAuction|Continuous|Halted|ClosingAuction|Closed> [alloc=<FIFO|PRO>]".bold(),
+                 "eod [YYYY-MM-DD]".bold(), "q".bold().red());
+        println!();
         let mut line = String::new();
         io::stdin().read_line(&mut line).unwrap();
-        if line.chars().nth(0) == Some('q') {
+        if line.starts_with('q') {
             process::exit(0);
         }
-        let order = utils::create_order_from_string(line.trim_end().to_string());
-        order_book.add_order_to_order_book(order);
-        if let Err(e) = tx.send(order_book) {
-            error!("Error sending order book {}",e);
-        };
-        sleep(Duration::from_secs(1));
+        match parse_command(line.trim_end()) {
+            Ok(command) => send_command(tx, rx, command),
+            Err(e) => println!("{} {}", "Invalid command:".red().bold(), e),
+        }
     }
 }
 
-/// creates the matcher based on the algo and starts the matcher which spins in a loop matching the order book as and when available
-/// C
-pub fn start_matcher(tx: &Sender<OrderBook>, rx: &Receiver<OrderBook>, algo: String) {
-    if algo == "PRO" {
-        let mut matcher = ProrataMatcher;
-        matcher.start(&tx, &rx);
+///Sends the command to the matcher thread and waits until the matcher has applied it
+fn send_command(tx: &Sender<Command>, rx: &Receiver<()>, command: Command) {
+    if let Err(e) = tx.send(command) {
+        error!("Error sending command {}", e);
+        return;
+    }
+    if let Err(e) = rx.recv() {
+        error!("Error receiving acknowledgement {}", e);
+    }
+}
+
+///Parses a line of user input into a command: `eod`, `cancel`, `replace` and `phase` commands, or else a
+/// new order
+fn parse_command(line: &str) -> Result<Command, String> {
+    if line.starts_with("eod") {
+        parse_session_date(line).map(Command::EndSession)
+    } else if line.starts_with("cancel") {
+        utils::create_cancel_request_from_string(line).map(Command::Cancel)
+    } else if line.starts_with("replace") {
+        utils::create_replace_request_from_string(line).map(Command::Replace)
+    } else if line.starts_with("phase") {
+        parse_phase_change(line)
     } else {
-        let mut matcher = FIFOMatcher;
-        matcher.start(&tx, &rx);
+        Ok(Command::Submit(utils::create_order_from_string(line.to_string())))
+    }
+}
+
+///Parses the date of the `eod [YYYY-MM-DD]` command, today if no date is given
+fn parse_session_date(command: &str) -> Result<NaiveDate, String> {
+    match command.split_whitespace().nth(1) {
+        Some(date) => NaiveDate::parse_from_str(date, "%Y-%m-%d").map_err(|e| format!("invalid session date {} {}", date, e)),
+        None => Ok(Local::now().date_naive()),
+    }
+}
+
+///Parses the `phase <symbol> <phase> [alloc=<FIFO|PRO>]` command
+fn parse_phase_change(command: &str) -> Result<Command, String> {
+    let tokens: Vec<&str> = command.split_whitespace().collect();
+    let allocation = match tokens.get(3).map(|token| token.strip_prefix("alloc=")) {
+        None => AuctionAllocation::default(),
+        Some(Some(value)) => AuctionAllocation::from_str(value)?,
+        Some(None) => return Err(format!("invalid phase attribute {}", tokens[3])),
+    };
+    let phase = match tokens.get(2) {
+        Some(phase) => TradingPhase::from_str(phase)?,
+        None => return Err(format!("missing trading phase {}", command)),
+    };
+    match tokens.get(1) {
+        Some(symbol) => Ok(Command::Phase(symbol.to_string(), phase, allocation)),
+        None => Err(format!("missing symbol {}", command)),
+    }
+}
+
+///Applies the command to the order book with the matchers of the registry and prints the execution
+/// reports, or the reason the command was rejected
+fn apply_command(order_book: &mut OrderBook, registry: &mut MatcherRegistry, command: Command) {
+    match command {
+        Command::Load(..) => {
+            let fills = registry.match_order_book(order_book);
+            Fill::pretty_print(&fills);
+        }
+        Command::Submit(order) => {
+            let reports = registry.submit(order_book, order);
+            Fill::pretty_print(&reports);
+        }
+        Command::Cancel(request) => match order_book.cancel_order(&request) {
+            Ok(report) => {
+                Fill::pretty_print(&[report]);
+            }
+            Err(reject) => println!("{} {}", "Cancel rejected:".red().bold(), reject),
+        },
+        Command::Replace(request) => match registry.replace_order(order_book, &request) {
+            Ok(reports) => {
+                Fill::pretty_print(&reports);
+            }
+            Err(reject) => println!("{} {}", "Replace rejected:".red().bold(), reject),
+        },
+        Command::Phase(symbol, phase, allocation) => match CallAuction::new(allocation).change_phase(order_book, &symbol, phase, registry) {
+            Ok(fills) => {
+                Fill::pretty_print(&fills);
+            }
+            Err(e) => println!("{} {}", "Phase change rejected:".red().bold(), e),
+        },
+        Command::EndSession(session_date) => {
+            let expired = order_book.end_session(session_date);
+            Fill::pretty_print(&expired);
+        }
+    }
+}
+
+///Prints the status events published by the phase changes of the order book
+fn print_status_events(order_book: &mut OrderBook) {
+    for event in order_book.take_status_events() {
+        info!("trading phase changed {}", event);
+        println!("{} {}", "Trading phase:".yellow().bold(), event);
+    }
+}
+
+/// Runs the matcher thread, which owns the order book. It sits in a loop receiving the commands of the
+/// user, applies each one with the matchers of the registry and acknowledges it once the book has been
+/// printed. The phase changes of the schedule are applied as they become due, before each command
+pub fn start_matcher(tx: &Sender<()>, rx: &Receiver<Command>, mut registry: MatcherRegistry) {
+    info!("Starting matcher...");
+    let mut order_book = OrderBook::default();
+    let mut schedule = SessionSchedule::default();
+    while let Ok(mut command) = rx.recv() {
+        trace!("received command {:?}", command);
+        if let Command::Load(loaded, loaded_schedule) = &mut command {
+            order_book = mem::take(loaded);
+            schedule = mem::take(loaded_schedule);
+        }
+        let fills = CallAuction::default().run_schedule(&mut order_book, &mut schedule, Local::now().time(), &mut registry);
+        Fill::pretty_print(&fills);
+        apply_command(&mut order_book, &mut registry, command);
+        print_status_events(&mut order_book);
+        order_book.pretty_print_self();
+        if let Err(e) = tx.send(()) {
+            error!("Error sending acknowledgement {}", e);
+        }
     }
 }
 
@@ -104,9 +229,14 @@ pub fn get_cmd_args() -> Result<CmdArgs, &'static str> {
         None => String::from("FIFO")
     };
 
+    let instruments_path = args.next().unwrap_or_default();
+    let schedule_path = args.next().unwrap_or_default();
+
     let cmd_args = CmdArgs {
-        file_path: file_path,
-        algo: algo,
+        file_path,
+        algo,
+        instruments_path,
+        schedule_path,
     };
     Ok(cmd_args)
 }
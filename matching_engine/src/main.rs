@@ -1,11 +1,11 @@
-use std::sync::mpsc::{Receiver, Sender};
-use std::thread::JoinHandle;
-use log::warn;
+use std::process;
+use std::str::FromStr;
 
-use matching_engine::{CmdArgs, get_cmd_args, start_matcher, start_user};
+use log::{error, warn};
 
+use matching_engine::{CmdArgs, get_cmd_args, start_matcher, start_user};
+use matching_engine::matchers::matcher_registry::MatcherRegistry;
 
-#[allow(unused_imports)]
 
 ///The entry point for the sim module. The user input thread and matcher thread are started form here
 fn main() {
@@ -20,14 +20,24 @@ fn main() {
             CmdArgs {
                 file_path: String::new(),
                 algo: "FIFO".to_string(),
+                instruments_path: String::new(),
+                schedule_path: String::new(),
             }
         }
     };
+    let registry = match MatcherRegistry::from_str(&cmd_args.algo) {
+        Ok(registry) => registry,
+        Err(e) => {
+            error!("{}", e);
+            eprintln!("{}", e);
+            process::exit(1);
+        }
+    };
     let matcher = std::thread::spawn(move || {
-        start_matcher(&tx2, &rx1,cmd_args.algo);
+        start_matcher(&tx2, &rx1, registry);
     });
     let user = std::thread::spawn(move || {
-        start_user(&tx1, &rx2, cmd_args.file_path);
+        start_user(&tx1, &rx2, cmd_args.file_path, cmd_args.instruments_path, cmd_args.schedule_path);
     });
 
     user.join().expect("error");
@@ -1,3 +1,6 @@
+pub mod auction;
 pub mod fifo_matcher;
 pub mod prorata_matcher;
-pub mod matcher;
\ No newline at end of file
+pub mod matcher;
+pub mod matcher_registry;
+pub mod pipeline_matcher;
\ No newline at end of file
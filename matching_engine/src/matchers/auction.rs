@@ -0,0 +1,412 @@
+use std::collections::VecDeque;
+use std::str::FromStr;
+
+use chrono::NaiveTime;
+use log::{trace, warn};
+
+use crate::common::id_generator::{IdType, SharedIdGenerator};
+use crate::matchers::matcher::Matcher;
+use crate::model::domain::{Fill, OrderBook, OrderSingle, Side};
+use crate::model::domain::Side::{Buy, Sell};
+use crate::model::domain::Status::{Filled, PartialFill};
+use crate::model::domain::TimeInForce::FOK;
+use crate::model::price::Price;
+use crate::model::session::{SessionSchedule, TradingPhase};
+
+///How the volume of an auction is allocated among the orders of the last price level that trades,
+/// when that level cannot be filled completely
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum AuctionAllocation {
+    #[default]
+    TimePriority,
+    ProRata,
+}
+
+impl FromStr for AuctionAllocation {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_uppercase().as_str() {
+            "FIFO" | "TIME" => Ok(AuctionAllocation::TimePriority),
+            "PRO" | "PRORATA" => Ok(AuctionAllocation::ProRata),
+            _ => Err(format!("invalid auction allocation {}", s)),
+        }
+    }
+}
+
+///The single price an auction executes at, the volume executed and the quantity left over on the side
+/// with more interest at that price
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Uncrossing {
+    price: Price,
+    volume: u32,
+    imbalance: u32,
+}
+
+impl Uncrossing {
+    pub fn price(&self) -> Price {
+        self.price
+    }
+
+    pub fn volume(&self) -> u32 {
+        self.volume
+    }
+
+    pub fn imbalance(&self) -> u32 {
+        self.imbalance
+    }
+
+    ///Returns `true` if the uncrossing is preferred over `other`: more volume, then less imbalance and
+    /// then a price closer to the reference price
+    fn is_better_than(&self, other: &Uncrossing, reference_price: Option<Price>) -> bool {
+        if self.volume != other.volume {
+            return self.volume > other.volume;
+        }
+        if self.imbalance != other.imbalance {
+            return self.imbalance < other.imbalance;
+        }
+        match reference_price {
+            Some(reference) => {
+                let distance = |price: Price| if price > reference { price - reference } else { reference - price };
+                distance(self.price) < distance(other.price)
+            }
+            None => false,
+        }
+    }
+}
+
+///Runs the call auction of a symbol whose orders accumulated in the [`OrderBook`] without matching. All
+/// the orders that cross are executed at a single uncrossing price. Buy and sell orders are filled in
+/// price priority and the last price level that trades is allocated by time priority or pro-rata
+#[derive(Debug, Default)]
+pub struct CallAuction {
+    allocation: AuctionAllocation,
+}
+
+impl CallAuction {
+    pub fn new(allocation: AuctionAllocation) -> Self {
+        Self { allocation }
+    }
+
+    pub fn allocation(&self) -> AuctionAllocation {
+        self.allocation
+    }
+
+    ///Returns the uncrossing of the auction of the symbol, or `None` if no orders cross. The uncrossing
+    /// price is the limit price that executes the most volume. Ties are broken by the smallest imbalance,
+    /// then by the price closest to the reference price of the symbol and then by the lowest price. An
+    /// auction with market orders only uncrosses at the reference price
+    pub fn uncrossing(order_book: &OrderBook, symbol: &str) -> Option<Uncrossing> {
+        let buys = Self::auction_orders(order_book.levels(symbol, Buy).into_iter()
+            .flat_map(|levels| levels.iter().flat_map(|(_, orders)| orders.iter()))
+            .chain(order_book.immediate_orders().iter().filter(|order| order.side() == Buy))
+            .filter(|order| order.symbol() == symbol)
+            .cloned()
+            .collect(), Buy);
+        let sells = Self::auction_orders(order_book.levels(symbol, Sell).into_iter()
+            .flat_map(|levels| levels.iter().flat_map(|(_, orders)| orders.iter()))
+            .chain(order_book.immediate_orders().iter().filter(|order| order.side() == Sell))
+            .filter(|order| order.symbol() == symbol)
+            .cloned()
+            .collect(), Sell);
+        Self::uncrossing_of(&buys, &sells, order_book.reference_price(symbol))
+    }
+
+    ///Moves the symbol to the given trading phase. A symbol that leaves an auction phase for continuous
+    /// trading or the close is uncrossed first. The stop orders triggered by the uncrossing are then
+    /// matched by the matcher, as if they had just arrived. Returns the fills of the uncrossing followed by
+    /// the fills of the stops
+    pub fn change_phase(&mut self, order_book: &mut OrderBook, symbol: &str, phase: TradingPhase, matcher: &mut dyn Matcher) -> Result<Vec<Fill>, String> {
+        let current = order_book.phase(symbol);
+        if !current.can_move_to(phase) {
+            return Err(format!("{} cannot move from {} to {}", symbol, current, phase));
+        }
+        let uncrossed = matches!(phase, TradingPhase::Continuous | TradingPhase::Closed) && current.is_auction();
+        let fills = if uncrossed { self.uncross(order_book, symbol) } else { vec![] };
+        order_book.set_phase(symbol, phase)?;
+        if !uncrossed {
+            return Ok(fills);
+        }
+        let fills = matcher.match_triggered(order_book, symbol, fills);
+        order_book.record_done(&fills);
+        Ok(fills)
+    }
+
+    ///Applies the phase changes of the schedule that are due at `now`, matching the stop orders their
+    /// uncrossings trigger with the matcher. A change that is not allowed from the current phase of its
+    /// symbol is logged and skipped. Returns the fills of the uncrossings
+    pub fn run_schedule(&mut self, order_book: &mut OrderBook, schedule: &mut SessionSchedule, now: NaiveTime, matcher: &mut dyn Matcher) -> Vec<Fill> {
+        let mut fills = vec![];
+        for entry in schedule.due(now) {
+            match self.change_phase(order_book, entry.symbol(), entry.phase(), matcher) {
+                Ok(uncrossed) => fills.extend(uncrossed),
+                Err(e) => warn!("skipping scheduled phase change at {}: {}", entry.time(), e),
+            }
+        }
+        fills
+    }
+
+    ///Uncrosses the orders of the symbol queued in the book. The orders that cross are executed at the
+    /// uncrossing price. An FOK, all-or-none or minimum quantity order the uncrossing cannot fill as
+    /// required is left out of it, and the self-trade prevention of the later order of a pair of orders of
+    /// the same participant applies before the price is found again. The rest go back to the book in their
+    /// original priority, except for market, IOC and FOK orders whose remainder is cancelled. Returns the
+    /// fills and the execution reports of the cancelled orders. The stop orders the fills trigger are left
+    /// to the caller
+    pub fn uncross(&mut self, order_book: &mut OrderBook, symbol: &str) -> Vec<Fill> {
+        let (immediate, others): (VecDeque<OrderSingle>, VecDeque<OrderSingle>) = order_book.take_immediate_orders()
+            .into_iter()
+            .partition(|order| order.symbol() == symbol);
+        others.into_iter().for_each(|order| order_book.add_order_to_order_book(order));
+        let (immediate_buys, immediate_sells): (Vec<OrderSingle>, Vec<OrderSingle>) = immediate.into_iter()
+            .partition(|order| order.side() == Buy);
+
+        let mut buys = order_book.take_orders(symbol, Buy);
+        buys.extend(immediate_buys);
+        let mut buys = Self::auction_orders(buys, Buy);
+        let mut sells = order_book.take_orders(symbol, Sell);
+        sells.extend(immediate_sells);
+        let mut sells = Self::auction_orders(sells, Sell);
+
+        let mut fills = vec![];
+        let mut left_out = vec![];
+        while let Some(uncrossing) = Self::uncrossing_of(&buys, &sells, order_book.reference_price(symbol)) {
+            let buy_allocations = self.allocate(&buys, &uncrossing);
+            let sell_allocations = self.allocate(&sells, &uncrossing);
+            if let Some(index) = Self::unsatisfied(&buys, &buy_allocations) {
+                trace!("leaving {} out of the uncrossing", buys[index].cl_ord_id());
+                left_out.push(buys.remove(index));
+                continue;
+            }
+            if let Some(index) = Self::unsatisfied(&sells, &sell_allocations) {
+                trace!("leaving {} out of the uncrossing", sells[index].cl_ord_id());
+                left_out.push(sells.remove(index));
+                continue;
+            }
+            let pairings = Self::pairings(buy_allocations, sell_allocations);
+            if let Some(&(b, s, _)) = pairings.iter().find(|(b, s, _)| buys[*b].is_self_trade_with(&sells[*s])) {
+                trace!("preventing self trade of {} with {}", buys[b].cl_ord_id(), sells[s].cl_ord_id());
+                let (buy, sell) = (&mut buys[b], &mut sells[s]);
+                let (incoming, resting) = if buy.arrival_seq() > sell.arrival_seq() { (buy, sell) } else { (sell, buy) };
+                fills.extend(incoming.self_trade_prevention().apply(incoming, resting, order_book.id_generator()));
+                buys.retain(|order| order.qty() > 0);
+                sells.retain(|order| order.qty() > 0);
+                continue;
+            }
+            trace!("uncrossing {} at {} for {}", symbol, uncrossing.price(), uncrossing.volume());
+            fills.extend(Self::execute(&mut buys, &mut sells, &pairings, uncrossing.price(), order_book.id_generator()));
+            break;
+        }
+        for mut order in buys.into_iter().chain(sells).chain(left_out).filter(|order| order.qty() > 0) {
+            if order.is_immediate() {
+                fills.push(order.cancel(order_book.id_generator()));
+            } else {
+                order_book.add_order_to_order_book(order);
+            }
+        }
+        order_book.record_done(&fills);
+        fills
+    }
+
+    ///Sorts the orders of one side in auction priority: market orders first and then by price, keeping
+    /// the time priority of orders at the same price
+    fn auction_orders(mut orders: Vec<OrderSingle>, side: Side) -> Vec<OrderSingle> {
+        orders.sort_by(|a, b| b.is_market().cmp(&a.is_market()).then_with(|| match side {
+            Buy => b.price().cmp(&a.price()),
+            Sell => a.price().cmp(&b.price()),
+        }));
+        orders
+    }
+
+    fn uncrossing_of(buys: &[OrderSingle], sells: &[OrderSingle], reference_price: Option<Price>) -> Option<Uncrossing> {
+        let mut prices: Vec<Price> = buys.iter().chain(sells)
+            .filter(|order| !order.is_market())
+            .map(|order| order.price())
+            .collect();
+        if prices.is_empty() {
+            prices.extend(reference_price);
+        }
+        prices.sort();
+        prices.dedup();
+
+        let mut best: Option<Uncrossing> = None;
+        for price in prices {
+            let demand: u32 = buys.iter().filter(|order| order.crosses(price)).map(|order| order.qty()).sum();
+            let supply: u32 = sells.iter().filter(|order| order.crosses(price)).map(|order| order.qty()).sum();
+            let candidate = Uncrossing { price, volume: demand.min(supply), imbalance: demand.abs_diff(supply) };
+            if candidate.volume > 0 && best.map_or(true, |best| candidate.is_better_than(&best, reference_price)) {
+                best = Some(candidate);
+            }
+        }
+        best
+    }
+
+    ///Returns the quantity each order of one side executes in the auction. The orders are filled in
+    /// auction priority and the last price level that trades is allocated by the allocation of the auction
+    fn allocate(&self, orders: &[OrderSingle], uncrossing: &Uncrossing) -> Vec<u32> {
+        let mut allocations = vec![0; orders.len()];
+        let mut remaining = uncrossing.volume();
+        let mut start = 0;
+        while start < orders.len() && remaining > 0 && orders[start].crosses(uncrossing.price()) {
+            let first = &orders[start];
+            let end = start + orders[start..].iter()
+                .take_while(|order| order.is_market() == first.is_market() && order.price() == first.price())
+                .count();
+            let level = &orders[start..end];
+            let level_qty: u32 = level.iter().map(|order| order.qty()).sum();
+            let level_allocations = if level_qty <= remaining {
+                level.iter().map(|order| order.qty()).collect()
+            } else {
+                match self.allocation {
+                    AuctionAllocation::TimePriority => Self::time_priority(level, remaining),
+                    AuctionAllocation::ProRata => Self::pro_rata(level, remaining, level_qty),
+                }
+            };
+            remaining -= level_allocations.iter().sum::<u32>();
+            allocations[start..end].copy_from_slice(&level_allocations);
+            start = end;
+        }
+        allocations
+    }
+
+    fn time_priority(level: &[OrderSingle], qty: u32) -> Vec<u32> {
+        let mut remaining = qty;
+        level.iter().map(|order| {
+            let allocation = order.qty().min(remaining);
+            remaining -= allocation;
+            allocation
+        }).collect()
+    }
+
+    ///Allocates `qty` in proportion to the order quantities, rounding down. Whatever is left by the
+    /// rounding goes to the orders in time priority
+    fn pro_rata(level: &[OrderSingle], qty: u32, level_qty: u32) -> Vec<u32> {
+        let mut allocations: Vec<u32> = level.iter()
+            .map(|order| (qty as u64 * order.qty() as u64 / level_qty as u64) as u32)
+            .collect();
+        let mut remaining = qty - allocations.iter().sum::<u32>();
+        for (allocation, order) in allocations.iter_mut().zip(level) {
+            let extra = remaining.min(order.qty() - *allocation);
+            *allocation += extra;
+            remaining -= extra;
+        }
+        allocations
+    }
+
+    ///Returns the index of the last order in auction priority that the allocation cannot fill as it
+    /// requires: an FOK order that is not filled completely, or an all-or-none or minimum quantity order
+    /// that is filled for less than its minimum
+    fn unsatisfied(orders: &[OrderSingle], allocations: &[u32]) -> Option<usize> {
+        orders.iter().zip(allocations).rposition(|(order, &allocation)| {
+            (order.time_in_force() == FOK && allocation < order.qty()) || (allocation > 0 && !order.accepts_fill(allocation))
+        })
+    }
+
+    ///Pairs the buy and the sell allocations in auction priority. Returns the index of the buy order, the
+    /// index of the sell order and the quantity of each pairing
+    fn pairings(mut buy_allocations: Vec<u32>, mut sell_allocations: Vec<u32>) -> Vec<(usize, usize, u32)> {
+        let mut pairings = vec![];
+        let (mut b, mut s) = (0, 0);
+        while b < buy_allocations.len() && s < sell_allocations.len() {
+            if buy_allocations[b] == 0 {
+                b += 1;
+                continue;
+            }
+            if sell_allocations[s] == 0 {
+                s += 1;
+                continue;
+            }
+            let qty = buy_allocations[b].min(sell_allocations[s]);
+            pairings.push((b, s, qty));
+            buy_allocations[b] -= qty;
+            sell_allocations[s] -= qty;
+        }
+        pairings
+    }
+
+    ///Fills the pairings of buy and sell orders at the auction price, drawing the ids from the generator
+    fn execute(buys: &mut [OrderSingle], sells: &mut [OrderSingle], pairings: &[(usize, usize, u32)], price: Price, ids: &SharedIdGenerator) -> Vec<Fill> {
+        let mut fills = vec![];
+        for &(b, s, qty) in pairings {
+            let trade_id = ids.next_id(IdType::Trade);
+            fills.push(Self::fill(&mut buys[b], sells[s].cl_ord_id(), qty, price, &trade_id, ids));
+            fills.push(Self::fill(&mut sells[s], buys[b].cl_ord_id(), qty, price, &trade_id, ids));
+        }
+        fills
+    }
+
+    fn fill(order: &mut OrderSingle, contra_id: &str, qty: u32, price: Price, trade_id: &str, ids: &SharedIdGenerator) -> Fill {
+        let mut fill = Fill::from(order, ids);
+        order.fill(qty);
+        fill.set_qty(qty);
+        fill.set_cum_qty(order.cum_qty());
+        fill.set_leaves_qty(order.qty());
+        fill.set_price(price);
+        fill.set_secondary_cl_ord_id(contra_id.to_string());
+        fill.set_status(if order.qty() == 0 { Filled } else { PartialFill });
+        fill.set_trade_id(Some(trade_id.to_string()));
+        fill
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::common::utils::create_order_book;
+    use crate::matchers::auction::{AuctionAllocation, CallAuction};
+    use crate::matchers::fifo_matcher::FIFOMatcher;
+    use crate::model::domain::Side::Buy;
+    use crate::model::price::Price;
+    use crate::model::session::TradingPhase;
+
+    #[test]
+    fn test_uncrossing_price_maximises_volume_then_minimises_imbalance() {
+        let input = vec![
+            "id1 IBM 100 603 Buy".to_string(),
+            "id2 IBM 100 602 Buy".to_string(),
+            "id3 IBM 150 601 Buy".to_string(),
+            "id4 IBM 150 600 Sell".to_string(),
+            "id5 IBM 100 602 Sell".to_string(),
+            "id6 IBM 50 MKT Sell".to_string(),
+        ];
+        let order_book = create_order_book(input);
+
+        let uncrossing = CallAuction::uncrossing(&order_book, "IBM").unwrap();
+        assert_eq!(uncrossing.price(), Price::new(602, 0));
+        assert_eq!(uncrossing.volume(), 200);
+        assert_eq!(uncrossing.imbalance(), 100);
+    }
+
+    #[test]
+    fn test_reference_price_breaks_ties() {
+        let input = vec![
+            "id1 IBM 100 603 Buy".to_string(),
+            "id2 IBM 100 600 Sell".to_string(),
+        ];
+        let mut order_book = create_order_book(input);
+        assert_eq!(CallAuction::uncrossing(&order_book, "IBM").unwrap().price(), Price::new(600, 0));
+
+        order_book.set_reference_price("IBM", Price::new(6025, 1));
+        let uncrossing = CallAuction::uncrossing(&order_book, "IBM").unwrap();
+        assert_eq!(uncrossing.price(), Price::new(603, 0));
+        assert_eq!(uncrossing.volume(), 100);
+        assert_eq!(uncrossing.imbalance(), 0);
+    }
+
+    #[test]
+    fn test_pro_rata_allocation_of_the_last_level_is_exact() {
+        let input = vec![
+            "id1 IBM 100 601 Buy".to_string(),
+            "id2 IBM 100 601 Buy".to_string(),
+            "id3 IBM 100 601 Buy".to_string(),
+            "id4 IBM 100 601 Sell".to_string(),
+        ];
+        let mut order_book = create_order_book(input);
+        order_book.set_phase("IBM", TradingPhase::Halted).unwrap();
+        let fills = CallAuction::new(AuctionAllocation::ProRata).change_phase(&mut order_book, "IBM", TradingPhase::Continuous, &mut FIFOMatcher).unwrap();
+        let buy_fills: Vec<(&str, u32)> = fills.iter().filter(|f| f.side() == Buy).map(|f| (f.cl_ord_id(), f.qty())).collect();
+
+        assert_eq!(buy_fills, vec![("id1", 34), ("id2", 33), ("id3", 33)]);
+        assert!(order_book.is_matching("IBM"));
+        assert_eq!(order_book.reference_price("IBM"), Some(Price::new(601, 0)));
+    }
+}
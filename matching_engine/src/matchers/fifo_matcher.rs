@@ -1,168 +1,113 @@
-use std::collections::{HashMap, VecDeque};
-use std::sync::mpsc::{Receiver, Sender};
-use std::vec;
-use colored::Colorize;
-
-use log::{error, info, trace};
+use log::trace;
 
+use crate::common::id_generator::{IdType, SharedIdGenerator};
 use crate::matchers::matcher::Matcher;
-use crate::model::domain::{Fill, OrderBook, OrderBookKey, OrderSingle, Side};
-use crate::model::domain::Side::{Buy, Sell};
+use crate::model::domain::{Fill, OrderBook, OrderBookKey, OrderSingle, PriceLevels};
+use crate::model::domain::Side::Buy;
 use crate::model::domain::Status::{Filled, PartialFill};
-use crate::utils::{Aggregator, Sigma};
+use crate::model::domain::TimeInForce::FOK;
 
 #[derive(Debug)]
 pub struct FIFOMatcher;
 
 impl FIFOMatcher {
-
-
-     fn sum_of_filled_quantities(&self, fills: &Vec<Fill>, side: Side) -> u32 {
-        let fills_for_sum: Vec<Fill> = fills.clone().into_iter().filter(|f| f.side() == side).collect();
-        Aggregator::sigma(&fills_for_sum)
-    }
-
     ///create client side and exchange side fills from client order and exchange order
     fn update_fills(&mut self, client_order: &OrderSingle, exchange_order: &mut OrderSingle,
-                    client_fill: &mut Fill, ex_fill: &mut Fill, cl_cum_map:&mut HashMap<String,u32>, ex_cum_map:&mut HashMap<String,u32>) {
+                    client_fill: &mut Fill, ex_fill: &mut Fill) {
 
         //set the secondary ids
         client_fill.set_secondary_cl_ord_id(exchange_order.cl_ord_id().clone());
         ex_fill.set_secondary_cl_ord_id(client_order.cl_ord_id().clone());
-        let order_qty = client_order.qty();
-        let avail_qty = exchange_order.qty();
-        let leaves_qty = client_fill.leaves_qty();
-        let cl_cum_qty = cl_cum_map.get(client_order.cl_ord_id()).unwrap().clone();
-        if leaves_qty <= avail_qty {
-            self.exchange_partial_fill(cl_cum_qty, avail_qty, leaves_qty, client_fill, ex_fill, exchange_order,ex_cum_map);
-        } else {
-            self.client_order_partial_fill(order_qty, avail_qty, client_fill, ex_fill, exchange_order,cl_cum_map);
-        }
-    }
-
-    fn client_order_partial_fill(&self, order_qty: u32, avail_qty: u32, client_fill: &mut Fill, ex_fill: &mut Fill, exchange_order: &mut OrderSingle,cl_cum_map:&mut HashMap<String,u32>) {
-        let mut cl_cum_qty = cl_cum_map.get(client_fill.cl_ord_id()).unwrap().to_owned();
-        cl_cum_qty += exchange_order.qty();
-        let leaves_qty = order_qty - cl_cum_qty;
-        // trace!(" order qty {} cl_cum_qty {} leaves qty {}",order_qty, cl_cum_qty,leaves_qty);
-        client_fill.set_qty(avail_qty); //  100
-        client_fill.set_cum_qty(cl_cum_qty); //100
-        client_fill.set_leaves_qty(leaves_qty); // 50
-        client_fill.set_status(PartialFill);
-        cl_cum_map.insert(client_fill.cl_ord_id().to_owned(),cl_cum_qty);
-
-        ex_fill.set_qty(avail_qty);
-        ex_fill.set_cum_qty(avail_qty);
-        ex_fill.set_leaves_qty(0);
-        ex_fill.set_status(Filled);
-        exchange_order.set_qty(0);
+        let fill_qty = client_fill.leaves_qty().min(exchange_order.visible_qty());
+
+        client_fill.set_qty(fill_qty);
+        client_fill.set_cum_qty(client_fill.cum_qty() + fill_qty);
+        client_fill.set_leaves_qty(client_fill.leaves_qty() - fill_qty);
+        client_fill.set_status(if client_fill.leaves_qty() == 0 { Filled } else { PartialFill });
+
+        exchange_order.fill(fill_qty);
+        ex_fill.set_qty(fill_qty);
+        ex_fill.set_cum_qty(exchange_order.cum_qty());
+        ex_fill.set_leaves_qty(exchange_order.qty());
+        ex_fill.set_status(if ex_fill.leaves_qty() == 0 { Filled } else { PartialFill });
     }
 
-    fn exchange_partial_fill(&mut self, mut cl_cum_qty: u32, avail_qty: u32, leaves_qty: u32,
-                             client_fill: &mut Fill, ex_fill: &mut Fill, exchange_order: &mut OrderSingle, ex_cum_map:&mut HashMap<String,u32>) {
-
-        let mut ex_cum_qty = ex_cum_map.get(ex_fill.cl_ord_id()).unwrap().to_owned();
-
-        ex_cum_qty += leaves_qty;
-
-        cl_cum_qty = cl_cum_qty + leaves_qty;
-        client_fill.set_qty(leaves_qty);
-        client_fill.set_cum_qty(cl_cum_qty);
-        client_fill.set_leaves_qty(0);
-        client_fill.set_status(Filled);
-
-        ex_fill.set_qty(leaves_qty);
-        ex_fill.set_cum_qty(ex_cum_qty);
-        ex_fill.set_leaves_qty(avail_qty - ex_fill.qty());
-        ex_cum_qty += leaves_qty;
-        if ex_fill.leaves_qty() == 0 {
-            ex_fill.set_status(Filled);
-        } else {
-            ex_fill.set_status(PartialFill);
-        }
-        exchange_order.set_qty(avail_qty - leaves_qty);
-       ex_cum_map.insert(ex_fill.cl_ord_id().to_owned(),ex_cum_qty);
-    }
-    fn print_fills(&self, fills: Vec<Fill>, side: Side) {
-        let exchange_side = if side == Buy { Sell } else { Buy };
-        let client_fills: Vec<Fill> = fills.clone().into_iter().filter(|f| f.side() == side).collect();
-        let exchange_fills: Vec<Fill> = fills.into_iter().filter(|f| f.side() == exchange_side).collect();
-        info!("Client Fills {:#?}",client_fills);
-        info!("Exchange Fills {:#?}",exchange_fills);
-    }
-
-    fn get_fills_for(&mut self, matching_map: &mut HashMap<OrderBookKey, VecDeque<OrderSingle>>, cl_cum_map:&mut HashMap<String, u32>,
-                     order: &OrderSingle) -> Vec<Fill> {
+    /// Sweeps the contra side price levels that the order crosses, best price first and in
+    /// time priority within a level, until the order is filled or no crossing level remains.
+    /// Every fill is done at the price of whichever order arrived first and taken off the order, the order
+    /// that arrived later being the aggressor. A resting iceberg
+    /// order only fills up to its visible peak, after which a new peak is shown at the back of the queue.
+    /// A resting order of the same participant is not traded, the self-trade prevention mode of the order
+    /// applies instead. A resting order whose minimum quantity or all-or-none condition the fill would not
+    /// meet is skipped and keeps its place in the queue. The order does not trade at all unless what it
+    /// can fill in one go meets its own condition. The ids of the fills are drawn from the generator
+    fn get_fills_for(&mut self, contra: &mut PriceLevels, order: &mut OrderSingle, ids: &SharedIdGenerator) -> Vec<Fill> {
         let mut fills = vec![];
-        let key = order.get_order_book_key();
-
-        trace!("order book key {:?}",key);
-        if matching_map.contains_key(&key) {
-
-            let deque = matching_map.get(&key).unwrap().clone();
-            let mut e_map = self.create_cum_qty_map(&deque);
-            let mut client_fill = Fill::from(order);
-
-            // trace!("client fill {:#?}",client_fill);
-            for avail in deque.iter() {
-                let mut exchange_order = avail.clone();
-                let mut ex_fill = Fill::from(avail);
-                self.update_fills(order, &mut exchange_order, &mut client_fill, &mut ex_fill,cl_cum_map, &mut e_map);
-                fills.push(client_fill.clone());
-                fills.push(ex_fill.clone());
-                if exchange_order.qty() == 0 {
-                    matching_map.entry(avail.get_order_book_key()).and_modify(|d| self.remove_order(d));
-                } else {
-                    matching_map.entry(avail.get_order_book_key()).and_modify(|d| self.remove_order(d));
-                    matching_map.entry(avail.get_order_book_key()).and_modify(|d| d.push_front(exchange_order));
+        if !order.accepts_fill(contra.executable_qty(order)) {
+            trace!("order {} cannot be filled for its minimum quantity", order.cl_ord_id());
+            return fills;
+        }
+        let keys: Vec<OrderBookKey> = contra.iter()
+            .map(|(key, _)| key.clone())
+            .take_while(|key| order.crosses(key.price()))
+            .collect();
+        for key in keys {
+            if order.qty() == 0 {
+                break;
+            }
+            trace!("order book key {:?}", key);
+            let deque = contra.get_mut(&key).unwrap();
+            let mut position = 0;
+            while position < deque.len() && order.qty() > 0 {
+                let resting = &deque[position];
+                if !order.is_self_trade_with(resting) && !resting.accepts_fill(order.qty().min(resting.visible_qty())) {
+                    trace!("skipping order {}", resting.cl_ord_id());
+                    position += 1;
+                    continue;
                 }
-                if client_fill.status().clone() == Filled {
-                    break;
+                let mut exchange_order = deque.remove(position).unwrap();
+                trace!("order  removed {}", exchange_order.cl_ord_id());
+                if order.is_self_trade_with(&exchange_order) {
+                    trace!("preventing self trade of {} with {}", order.cl_ord_id(), exchange_order.cl_ord_id());
+                    fills.extend(order.self_trade_prevention().apply(order, &mut exchange_order, ids));
+                } else {
+                    let mut client_fill = Fill::from(order, ids);
+                    client_fill.set_cum_qty(order.cum_qty());
+                    let mut ex_fill = Fill::from(&exchange_order, ids);
+                    client_fill.set_price(order.trade_price_against(&exchange_order));
+                    client_fill.set_liquidity(Some(order.liquidity_against(&exchange_order)));
+                    ex_fill.set_price(client_fill.price());
+                    ex_fill.set_liquidity(Some(exchange_order.liquidity_against(order)));
+                    client_fill.set_trade_id(Some(ids.next_id(IdType::Trade)));
+                    ex_fill.set_trade_id(client_fill.trade_id().map(String::from));
+                    self.update_fills(order, &mut exchange_order, &mut client_fill, &mut ex_fill);
+                    order.fill(client_fill.qty());
+                    fills.push(client_fill);
+                    fills.push(ex_fill);
                 }
-            }
-
-            let v = matching_map.get(&key);
-            if v.is_some() {
-                if v.unwrap().is_empty() {
-                    matching_map.remove(&key);
+                if exchange_order.needs_refresh() {
+                    trace!("refreshing peak of {}", exchange_order.cl_ord_id());
+                    exchange_order.refresh_peak();
+                    deque.push_back(exchange_order);
+                } else if exchange_order.qty() > 0 {
+                    deque.insert(position, exchange_order);
                 }
             }
+            contra.remove_if_empty(&key);
         }
         fills
     }
 
-
-    fn remove_order(&self, deq: &mut VecDeque<OrderSingle>) {
-        let o = deq.pop_front();
-        if o.is_some() {
-            trace!("order  removed {}",o.unwrap().cl_ord_id());
-        }
-    }
 }
 
 
 impl Matcher for FIFOMatcher {
-    fn start(&mut self, tx: &Sender<OrderBook>, rx: &Receiver<OrderBook>) {
-        info!("Starting FIFO Matcher...");
-        loop {
-            let mut ob = rx.recv().unwrap();
-            trace!("[FIFOMatcher]received order book to match {:?}",&ob);
-            if ob.is_empty() {
-                info!("Order Book is empty");
-            }
-            let fills: Vec<Fill> = self.match_order_book(&mut ob);
-            Fill::pretty_print(&fills);
-            if let Err(e) = tx.send(ob) {
-                error!("error sending order book {}",e);
-            }
-        }
-    }
-
-
-
-    /// The logic iterates over the [`OrderBook`] buy side orders and tries to match them
-    /// with any order available on the sell side. It matches to the fullest
-    /// extent possible before attempting a match for the next order in the queue
+    /// For every symbol the orders of the [`OrderBook`] are matched in the order they arrived, as if each
+    /// had been submitted on arrival. An order sweeps every contra level it crosses, best price first,
+    /// and is filled at the price of the resting order. It matches to the fullest extent possible before
+    /// the next order that arrived is matched. Whatever cannot be filled of a market, IOC or FOK order is
+    /// cancelled. The stop orders an order triggers are matched before the next order, and the pegged orders
+    /// the trades reprice are matched once the symbol has been matched
     /// # Example:
     /// ```rust
     /// use matching_engine::common::utils::{create_order_book, read_input};
@@ -175,50 +120,54 @@ impl Matcher for FIFOMatcher {
     /// // match the order book with the matcher to produce executions
     /// let mut fills = matcher.match_order_book(&mut order_book);
     /// ```
-     fn match_order_book(&mut self, order_book: &mut OrderBook) -> Vec<Fill> {
-        let buy = order_book.get_orders_for(Buy);
-        let mut sell = order_book.get_orders_for(Sell);
-        let mut fills = vec![];
-
-        let mut temp = HashMap::new();
-
-        for (key, deque) in buy.iter() {
-            let mut deque_clone = deque.clone();
-            let mut c_map = self.create_cum_qty_map(&deque);
-            for order in deque.iter() {
-                trace!("Matching order with cl_ord_id {}", order.cl_ord_id());
-                let sub_fills: Vec<Fill> = self.get_fills_for(&mut sell, &mut c_map,order);
-                if sub_fills.is_empty() {
-                    continue;
-                }
-                let buy_fills: Vec<Fill> = sub_fills.clone().into_iter().filter(|f| f.side() == Buy).collect();
-                let total = Aggregator::sigma(&buy_fills);
+    fn match_order_book(&mut self, order_book: &mut OrderBook) -> Vec<Fill> {
+        let mut fills = order_book.take_reports();
+        order_book.reprice_pegged_orders();
+        let symbols = order_book.symbols();
+        fills.extend(self.match_symbols(order_book, &symbols));
+        order_book.record_done(&fills);
+        fills
+    }
 
-                if order.qty() == total {
-                    trace!("removing order with id {}",order.cl_ord_id());
-                    deque_clone.pop_front();
-                } else {
-                    let updated_qty = order.qty() - total;
-                    trace!("Updating new quantity {}",updated_qty);
-                    let mut order_clone = order.clone();
-                    order_clone.set_qty(updated_qty);
-                    deque_clone.pop_front();
-                    deque_clone.push_front(order_clone);
-                }
-                for fill in sub_fills {
-                    fills.push(fill);
-                }
+    /// Matches every symbol in continuous trading, its orders in arrival order
+    fn match_symbols(&mut self, order_book: &mut OrderBook, symbols: &[String]) -> Vec<Fill> {
+        let mut fills = vec![];
+        for symbol in symbols {
+            if order_book.is_matching(symbol) {
+                fills.extend(self.match_arrivals(order_book, symbol));
             }
-
-            if !deque_clone.is_empty() {
-                temp.insert(key.clone(), deque_clone);
-            } else {}
         }
+        order_book.remove_empty_levels();
+        fills
+    }
 
-        let mut ob = OrderBook::new(temp, sell);
-        order_book.update_self(&mut ob);
-
-
+    /// Sweeps the contra side with the order, best price first. Whatever is left of a limit order rests in
+    /// the book, while the remainder of a market, IOC or FOK order is cancelled. An FOK order that cannot be
+    /// filled completely is cancelled without any fill. An order that would trade outside the dynamic band
+    /// halts its symbol and its remainder is held for the volatility auction
+    fn match_incoming(&mut self, order_book: &mut OrderBook, mut order: OrderSingle) -> Vec<Fill> {
+        trace!("Matching incoming order with cl_ord_id {}", order.cl_ord_id());
+        let ids = order_book.id_generator().clone();
+        let mut fills = vec![];
+        let (buy, sell) = order_book.sides_mut(order.symbol());
+        let contra = if order.side() == Buy { sell } else { buy };
+        if order.time_in_force() == FOK && contra.executable_qty(&order) < order.qty() {
+            trace!("killing FOK order {}", order.cl_ord_id());
+            fills.push(Fill::cancelled(&order, order.cum_qty(), &ids));
+            return fills;
+        }
+        if order.time_in_force() != FOK || !order_book.breaches_band(&order) {
+            let symbol = order.symbol().clone();
+            fills.extend(order_book.sweep_within_band(&symbol, order.side(), |contra| self.get_fills_for(contra, &mut order, &ids)));
+        }
+        if order_book.breaches_band(&order) {
+            order_book.halt_for_band(order);
+        } else if order.qty() > 0 && order.is_immediate() {
+            trace!("cancelling {} of order {}", order.qty(), order.cl_ord_id());
+            fills.push(order.cancel(&ids));
+        } else if order.qty() > 0 {
+            order_book.add_order_to_order_book(order);
+        }
         fills
     }
 }
@@ -228,8 +177,8 @@ impl Matcher for FIFOMatcher {
 mod tests {
     use crate::matchers::fifo_matcher::FIFOMatcher;
     use crate::matchers::matcher::Matcher;
-    use crate::model::domain::{Fill, OrderBook};
-    use crate::model::domain::Side::{Buy, Sell};
+    use crate::model::domain::Fill;
+    use crate::model::domain::Side::Buy;
     use crate::model::domain::Status::{Filled, PartialFill};
     use crate::utils::{create_order_book, create_order_from_string, read_input};
 
@@ -243,17 +192,13 @@ mod tests {
         order_book.add_order_to_order_book(cl_order.clone());
         //let (sell, buy) = order_book.get_orders_for_matching(Buy);
         let buy = order_book.get_orders_for(Buy);
-        let sell = order_book.get_orders_for(Sell);
-        let sell_orders = sell.get(&key).unwrap();
         let buy_orders = buy.get(&key).unwrap();
-        let mut ex_cum_map = fifo.create_cum_qty_map(&buy_orders);
-        let mut cl_cum_map = fifo.create_cum_qty_map(&sell_orders);
         let mut ex_order = buy_orders.clone().pop_front().unwrap();
 
-        let mut client_fill = Fill::from(&cl_order);
-        let mut ex_fill = Fill::from(&ex_order);
+        let mut client_fill = Fill::from(&cl_order, order_book.id_generator());
+        let mut ex_fill = Fill::from(&ex_order, order_book.id_generator());
 
-        fifo.update_fills(&cl_order, &mut ex_order, &mut client_fill, &mut ex_fill,&mut cl_cum_map,&mut ex_cum_map);
+        fifo.update_fills(&cl_order, &mut ex_order, &mut client_fill, &mut ex_fill);
 
 
         assert_eq!(client_fill.qty(), 100);
@@ -282,21 +227,16 @@ mod tests {
         let key = cl_order.clone().get_order_book_key();
         let mut fifo = FIFOMatcher;
        // let (sell, buy) = order_book.get_orders_for_matching(Buy);
-        let client_orders_map = order_book.get_orders_for(Sell);
         let ex_orders_map = order_book.get_orders_for(Buy);
-        let client_orders = client_orders_map.get(&key).unwrap();
         let ex_orders = ex_orders_map.get(&key).unwrap();
-
-        let mut ex_cum_map = fifo.create_cum_qty_map(ex_orders);
-        let mut cl_cum_map = fifo.create_cum_qty_map(client_orders);
         //trace!("{:#?}",buy);
     //    let orders = sell.get(&key).unwrap();
         let mut ex_order = ex_orders.clone().pop_front().unwrap();
 
-        let mut client_fill = Fill::from(&cl_order);
-        let mut ex_fill = Fill::from(&ex_order);
+        let mut client_fill = Fill::from(&cl_order, order_book.id_generator());
+        let mut ex_fill = Fill::from(&ex_order, order_book.id_generator());
 
-        fifo.update_fills(&cl_order, &mut ex_order, &mut client_fill, &mut ex_fill, &mut cl_cum_map,&mut ex_cum_map);
+        fifo.update_fills(&cl_order, &mut ex_order, &mut client_fill, &mut ex_fill);
         assert_eq!(client_fill.qty(), 100);
         assert_eq!(client_fill.cum_qty(), 100);
         assert_eq!(client_fill.leaves_qty(), 0);
@@ -319,18 +259,13 @@ mod tests {
         order_book.add_order_to_order_book(cl_order.clone());
         let key = cl_order.get_order_book_key();
         let mut fifo = FIFOMatcher;
-        let client_order_map = order_book.get_orders_for(Sell);
         let ex_order_map = order_book.get_orders_for(Buy);
-        let cl_orders = client_order_map.get(&key).unwrap();
         let ex_orders = ex_order_map.get(&key).unwrap();
-        let mut ex_cum_map = fifo.create_cum_qty_map(ex_orders);
-        let mut cl_cum_map = fifo.create_cum_qty_map(cl_orders);
         let mut ex_order = ex_orders.clone().pop_front().unwrap();
-        let mut fifo = FIFOMatcher;
-        let mut client_fill = Fill::from(&cl_order);
-        let mut ex_fill = Fill::from(&ex_order);
+        let mut client_fill = Fill::from(&cl_order, order_book.id_generator());
+        let mut ex_fill = Fill::from(&ex_order, order_book.id_generator());
 
-        fifo.update_fills(&cl_order, &mut ex_order, &mut client_fill, &mut ex_fill,&mut cl_cum_map,&mut ex_cum_map);
+        fifo.update_fills(&cl_order, &mut ex_order, &mut client_fill, &mut ex_fill);
         assert_eq!(client_fill.qty(), 50);
         assert_eq!(client_fill.cum_qty(), 50);
         assert_eq!(client_fill.leaves_qty(), 0);
@@ -352,18 +287,13 @@ mod tests {
         order_book.add_order_to_order_book(cl_order.clone());
         let key = cl_order.get_order_book_key();
         let mut fifo = FIFOMatcher;
-        let client_order_map = order_book.get_orders_for(Sell);
         let ex_order_map = order_book.get_orders_for(Buy);
-        let cl_orders = client_order_map.get(&key).unwrap();
         let ex_orders = ex_order_map.get(&key).unwrap();
-        let mut ex_cum_map = fifo.create_cum_qty_map(ex_orders);
-        let mut cl_cum_map = fifo.create_cum_qty_map(cl_orders);
         let mut ex_order = ex_orders.clone().pop_front().unwrap();
-        let mut fifo = FIFOMatcher;
-        let mut client_fill = Fill::from(&cl_order);
-        let mut ex_fill = Fill::from(&ex_order);
+        let mut client_fill = Fill::from(&cl_order, order_book.id_generator());
+        let mut ex_fill = Fill::from(&ex_order, order_book.id_generator());
 
-        fifo.update_fills(&cl_order, &mut ex_order, &mut client_fill, &mut ex_fill,&mut cl_cum_map,&mut ex_cum_map);
+        fifo.update_fills(&cl_order, &mut ex_order, &mut client_fill, &mut ex_fill);
 
         assert_eq!(client_fill.qty(), 100);
         assert_eq!(client_fill.cum_qty(), 100);
@@ -1,29 +1,131 @@
-use std::collections::{HashMap, VecDeque};
-use std::sync::mpsc::{Receiver, Sender};
+use log::warn;
 
+use crate::model::cancel::{CancelReject, ReplaceRequest};
 use crate::model::domain::{Fill, OrderBook, OrderSingle};
 
 /// The Matcher trait defines the functionalities provided by the matchers that implement this trait
 pub trait Matcher {
-    ///Starts the matcher. The matcher receives the order book through the Receiver , does any matching
-    /// ,updates the order book and sends it back using the Sender
-    fn start(&mut self, tx: &Sender<OrderBook>, rx: &Receiver<OrderBook>);
+    /// Matches the given order book for any matches and returns a list of Fills, preceded by the
+    /// execution reports the order book produced when orders were added to it
+    fn match_order_book(&mut self, order_book: &mut OrderBook) -> Vec<Fill>;
 
 
-    /// Matches the given order book for any matches and returns a list of Fills
-    fn match_order_book(&mut self, order_book: &mut OrderBook) -> Vec<Fill>;
+    /// Matches the given symbols that are in continuous trading, the orders of each symbol in the order they
+    /// arrived through [`Matcher::match_arrivals`]. The orders of other symbols are left in the book, so that
+    /// the symbols of one book can be matched by different matchers
+    fn match_symbols(&mut self, order_book: &mut OrderBook, symbols: &[String]) -> Vec<Fill>;
+
 
+    /// Matches a new order of a symbol in continuous trading against the contra side, the order being the
+    /// aggressor. Whatever is left of a limit order rests in the book and the remainder of a market, IOC or
+    /// FOK order is cancelled. Returns the fills
+    fn match_incoming(&mut self, order_book: &mut OrderBook, order: OrderSingle) -> Vec<Fill>;
 
-    /// When traversing multiple orders to generate a fill, this map helps keep track of the quantity
-    /// filled until now
-    fn create_cum_qty_map(&self, orders: &VecDeque<OrderSingle>) -> HashMap<String, u32> {
-        let mut map = HashMap::new();
 
-        for order in orders {
-            map.insert(order.cl_ord_id().clone(), 0);
+    /// Matches the orders of a symbol in continuous trading in the order they arrived, each one through
+    /// [`Matcher::match_incoming`] as if it had been submitted on arrival, so that matching a loaded book
+    /// produces the same fills as submitting its orders one at a time. The stop orders the trades of an order
+    /// trigger are matched before the next order, and the pegged orders the trades reprice once every order
+    /// has been matched. A pegged order in the book has been priced, so it trades like a limit order. Stop and
+    /// post-only orders are put back untouched, and so is every order left once the symbol stops matching
+    fn match_arrivals(&mut self, order_book: &mut OrderBook, symbol: &str) -> Vec<Fill> {
+        let mut fills = vec![];
+        for order in order_book.take_unmatched_orders(symbol) {
+            if (order.trades_on_arrival() || order.is_pegged()) && order_book.is_matching(symbol) {
+                let round = self.match_incoming(order_book, order);
+                fills.extend(self.match_triggered(order_book, symbol, round));
+            } else {
+                order_book.restore_order(order);
+            }
         }
-        map
+        fills.extend(self.match_repriced(order_book, symbol));
+        fills
     }
-}
 
 
+    /// Triggers the stop orders reached by the trades of the round and matches each of them as an incoming
+    /// order, in the arrival order of the stops, until the trades trigger no more stops. Only the triggered
+    /// orders are matched, the rest of the book is left as it is. Returns the round followed by the fills of
+    /// the stops it triggered
+    fn match_triggered(&mut self, order_book: &mut OrderBook, symbol: &str, mut round: Vec<Fill>) -> Vec<Fill> {
+        let mut fills = vec![];
+        loop {
+            let triggered = order_book.take_triggered_stops(&round);
+            fills.extend(round);
+            if triggered.is_empty() {
+                break;
+            }
+            round = vec![];
+            for order in triggered {
+                if order.trades_on_arrival() && order_book.is_matching(symbol) {
+                    round.extend(self.match_incoming(order_book, order));
+                } else {
+                    order_book.add_order_to_order_book(order);
+                    round.extend(order_book.take_reports());
+                }
+            }
+        }
+        fills
+    }
+
+
+    /// Reprices the pegged orders of the symbol and matches each order whose price changed as an incoming
+    /// order, followed by the stops its trades trigger, until no more pegged orders are repriced. Only the
+    /// repriced orders are matched, the rest of the book is left as it is. Returns the fills
+    fn match_repriced(&mut self, order_book: &mut OrderBook, symbol: &str) -> Vec<Fill> {
+        let mut fills = vec![];
+        loop {
+            let repriced = order_book.take_repriced_pegged_orders(symbol);
+            if repriced.is_empty() {
+                return fills;
+            }
+            for order in repriced {
+                if order_book.is_matching(symbol) {
+                    let round = self.match_incoming(order_book, order);
+                    fills.extend(self.match_triggered(order_book, symbol, round));
+                } else {
+                    order_book.restore_order(order);
+                }
+            }
+        }
+    }
+
+
+    /// Submits a new order to the book and matches it straight away. Only the order, and the stop and
+    /// pegged orders of its symbol that its trades trigger or reprice, are matched as incoming orders, the
+    /// rest of the book is never matched again. An order that fails validation is rejected. Stop, pegged and
+    /// post-only orders and the orders of a symbol outside continuous trading are added to the book without
+    /// trading on arrival. Returns the execution reports of the order and of the orders it traded with
+    fn submit(&mut self, order_book: &mut OrderBook, mut order: OrderSingle) -> Vec<Fill> {
+        if let Err(e) = order_book.validate_order(&order) {
+            warn!("Order {} rejected: {}", order.cl_ord_id(), e);
+            return vec![Fill::rejected(&order, order_book.id_generator())];
+        }
+        let symbol = order.symbol().clone();
+        let round = if order.trades_on_arrival() && order_book.is_matching(&symbol) {
+            order_book.stamp_arrival(&mut order);
+            self.match_incoming(order_book, order)
+        } else {
+            order_book.add_order_to_order_book(order);
+            order_book.take_reports()
+        };
+        let mut reports = self.match_triggered(order_book, &symbol, round);
+        reports.extend(self.match_repriced(order_book, &symbol));
+        order_book.record_done(&reports);
+        reports
+    }
+
+
+    /// Applies the replace request to the order book and matches the symbol of the order straight away, so
+    /// that a replaced order whose new price crosses the contra side trades immediately. Returns the Replaced
+    /// execution report followed by any fills
+    fn replace_order(&mut self, order_book: &mut OrderBook, request: &ReplaceRequest) -> Result<Vec<Fill>, CancelReject> {
+        let report = order_book.replace_order(request)?;
+        let symbols = vec![report.symbol().clone()];
+        let mut reports = vec![report];
+        reports.extend(order_book.take_reports());
+        reports.extend(self.match_symbols(order_book, &symbols));
+        order_book.record_done(&reports);
+        Ok(reports)
+    }
+}
@@ -0,0 +1,160 @@
+use std::collections::BTreeMap;
+use std::str::FromStr;
+
+use log::trace;
+
+use crate::matchers::fifo_matcher::FIFOMatcher;
+use crate::matchers::matcher::Matcher;
+use crate::matchers::pipeline_matcher::PipelineMatcher;
+use crate::matchers::prorata_matcher::ProrataMatcher;
+use crate::model::domain::{Fill, OrderBook, OrderSingle};
+
+///Creates the matcher of a matching algorithm by name, `FIFO` for price time priority, `PRO` for pro-rata
+/// and `PIPELINE` for the default [`PipelineMatcher`]. Pipelines of other steps are registered from code.
+/// Unknown names are an error
+pub fn create_matcher(algo: &str) -> Result<Box<dyn Matcher + Send>, String> {
+    match algo.to_ascii_uppercase().as_str() {
+        "FIFO" => Ok(Box::new(FIFOMatcher)),
+        "PRO" | "PRORATA" => Ok(Box::new(ProrataMatcher::default())),
+        "PIPELINE" => Ok(Box::new(PipelineMatcher::default())),
+        _ => Err(format!("unknown matching algorithm {}", algo)),
+    }
+}
+
+///Dispatches the matching of an [`OrderBook`] to a [`Matcher`] per symbol, so that futures can be matched
+/// pro-rata and equities FIFO in the same book. A symbol is matched by the matcher registered for it,
+/// else by the matcher registered for the group of its instrument, else by the default matcher
+pub struct MatcherRegistry {
+    default: Box<dyn Matcher + Send>,
+    matchers: BTreeMap<String, Box<dyn Matcher + Send>>,
+}
+
+impl MatcherRegistry {
+    pub fn new(default: Box<dyn Matcher + Send>) -> Self {
+        Self { default, matchers: BTreeMap::new() }
+    }
+
+    ///Registers the matcher of a symbol or of an instrument group, replacing any matcher registered for it
+    pub fn register(&mut self, key: &str, matcher: Box<dyn Matcher + Send>) {
+        self.matchers.insert(key.to_string(), matcher);
+    }
+
+    ///Returns `true` if a matcher is registered for the symbol or instrument group
+    pub fn is_registered(&self, key: &str) -> bool {
+        self.matchers.contains_key(key)
+    }
+
+    ///Returns the key of the matcher the symbol is dispatched to, its symbol or its instrument group, or
+    /// `None` for the default matcher
+    pub fn key_for(&self, order_book: &OrderBook, symbol: &str) -> Option<String> {
+        if self.matchers.contains_key(symbol) {
+            return Some(symbol.to_string());
+        }
+        order_book.instruments().get(symbol)
+            .and_then(|instrument| instrument.group())
+            .filter(|group| self.matchers.contains_key(*group))
+            .map(String::from)
+    }
+
+    fn matcher_mut(&mut self, key: &Option<String>) -> &mut Box<dyn Matcher + Send> {
+        match key.as_ref().and_then(|key| self.matchers.get_mut(key)) {
+            Some(matcher) => matcher,
+            None => &mut self.default,
+        }
+    }
+}
+
+impl FromStr for MatcherRegistry {
+    type Err = String;
+
+    ///Parses a registry from the default algorithm followed by comma separated `<symbol|group>=<algo>`
+    /// assignments, such as `FIFO,FUT=PRO,ES=PRO`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut tokens = s.split(',').map(str::trim);
+        let mut registry = MatcherRegistry::new(create_matcher(tokens.next().unwrap_or_default())?);
+        for token in tokens {
+            match token.split_once('=') {
+                Some((key, algo)) if !key.is_empty() => registry.register(key, create_matcher(algo)?),
+                _ => return Err(format!("invalid matcher assignment {}", token)),
+            }
+        }
+        Ok(registry)
+    }
+}
+
+impl Matcher for MatcherRegistry {
+    /// Matches every symbol of the [`OrderBook`] with the matcher it is dispatched to, which also matches the
+    /// stop orders triggered and the pegged orders repriced by the trades of the symbol
+    /// # Example:
+    /// ```rust
+    /// use std::str::FromStr;
+    /// use matching_engine::common::utils::{create_order_book, read_input};
+    /// use matching_engine::matchers::matcher::Matcher;
+    /// use matching_engine::matchers::matcher_registry::MatcherRegistry;
+    /// let mut order_book = create_order_book(read_input("test_data/orders.txt"));
+    /// let mut registry = MatcherRegistry::from_str("FIFO,TATA=PRO").unwrap();
+    /// let fills = registry.match_order_book(&mut order_book);
+    /// ```
+    fn match_order_book(&mut self, order_book: &mut OrderBook) -> Vec<Fill> {
+        let mut fills = order_book.take_reports();
+        order_book.reprice_pegged_orders();
+        let symbols = order_book.symbols();
+        fills.extend(self.match_symbols(order_book, &symbols));
+        order_book.record_done(&fills);
+        fills
+    }
+
+    /// Runs one round of matching for the symbols, each group of symbols with the matcher it is dispatched to
+    fn match_symbols(&mut self, order_book: &mut OrderBook, symbols: &[String]) -> Vec<Fill> {
+        let mut dispatch: BTreeMap<Option<String>, Vec<String>> = BTreeMap::new();
+        for symbol in symbols {
+            dispatch.entry(self.key_for(order_book, symbol)).or_default().push(symbol.clone());
+        }
+        let mut fills = vec![];
+        for (key, symbols) in dispatch {
+            trace!("matching {:?} with the matcher of {:?}", symbols, key);
+            fills.extend(self.matcher_mut(&key).match_symbols(order_book, &symbols));
+        }
+        fills
+    }
+
+    /// Matches the order with the matcher its symbol is dispatched to
+    fn match_incoming(&mut self, order_book: &mut OrderBook, order: OrderSingle) -> Vec<Fill> {
+        let key = self.key_for(order_book, order.symbol());
+        self.matcher_mut(&key).match_incoming(order_book, order)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use crate::common::utils::create_instrument_from_string;
+    use crate::matchers::matcher_registry::{create_matcher, MatcherRegistry};
+    use crate::model::domain::OrderBook;
+    use crate::model::instrument::InstrumentRegistry;
+
+    #[test]
+    fn test_symbols_are_dispatched_by_symbol_then_group() {
+        let mut instruments = InstrumentRegistry::default();
+        instruments.add(create_instrument_from_string("ES 0.25 1 1 2 group=FUT").unwrap());
+        instruments.add(create_instrument_from_string("NQ 0.25 1 1 2 group=FUT").unwrap());
+        instruments.add(create_instrument_from_string("IBM 0.01 1 1 2").unwrap());
+        let order_book = OrderBook::with_instruments(instruments);
+        let registry = MatcherRegistry::from_str("FIFO,FUT=PRO,NQ=FIFO").unwrap();
+
+        assert_eq!(registry.key_for(&order_book, "ES"), Some("FUT".to_string()));
+        assert_eq!(registry.key_for(&order_book, "NQ"), Some("NQ".to_string()));
+        assert_eq!(registry.key_for(&order_book, "IBM"), None);
+    }
+
+    #[test]
+    fn test_unknown_algorithms_are_errors() {
+        assert!(create_matcher("pro").is_ok());
+        assert!(create_matcher("Pipeline").is_ok());
+        assert!(create_matcher("LIFO").is_err());
+        assert!(MatcherRegistry::from_str("LIFO").is_err());
+        assert!(MatcherRegistry::from_str("FIFO,FUT=LIFO").is_err());
+        assert!(MatcherRegistry::from_str("FIFO,FUT").is_err());
+    }
+}
@@ -0,0 +1,316 @@
+use std::collections::VecDeque;
+
+use log::trace;
+
+use crate::common::id_generator::{IdType, SharedIdGenerator};
+use crate::matchers::matcher::Matcher;
+use crate::matchers::prorata_matcher::ProrataMatcher;
+use crate::model::domain::{Fill, Liquidity, OrderBook, OrderBookKey, OrderSingle, PriceLevels};
+use crate::model::domain::Side::Buy;
+use crate::model::domain::Status::{Filled, PartialFill};
+use crate::model::domain::TimeInForce::FOK;
+use crate::model::price::Price;
+
+///A step of an allocation pipeline. Each step allocates part of what is left of the incoming order to
+/// the resting orders of a price level, on top of what the previous steps allocated them
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AllocationStep {
+    ///The orders of lead market makers at the level are guaranteed `percent` percent of what is left,
+    /// split among them by the pro-rata step
+    LeadMarketMaker { percent: u32 },
+    ///The order that set the price of the level by improving the best price is allocated up to `max_qty`
+    TopOrder { max_qty: u32 },
+    ///`percent` percent of what is left is allocated in time priority
+    Fifo { percent: u32 },
+    ///What is left is allocated in proportion to what the resting orders can still take, by the
+    /// remainder policy and the minimum allocation of the pipeline
+    ProRata,
+}
+
+///Matches the book like the [`crate::matchers::fifo_matcher::FIFOMatcher`], each order in arrival sequence
+/// being the aggressor against the resting orders of either side that it crosses, but allocates every
+/// aggressor across the resting orders of a price level through a pipeline of [`AllocationStep`]s, such as a capped top order allocation, a percentage in time priority
+/// and the rest pro-rata. Whatever the steps leave is allocated in time priority, so the resting orders are
+/// allocated exactly what the incoming order executes
+#[derive(Debug)]
+pub struct PipelineMatcher {
+    steps: Vec<AllocationStep>,
+    pro_rata: ProrataMatcher,
+}
+
+impl PipelineMatcher {
+    ///Creates a matcher running the steps in order. The pro-rata steps split their quantity by the
+    /// remainder policy and the minimum allocation of `pro_rata`
+    pub fn new(steps: Vec<AllocationStep>, pro_rata: ProrataMatcher) -> Self {
+        Self { steps, pro_rata }
+    }
+
+    pub fn steps(&self) -> &[AllocationStep] {
+        &self.steps
+    }
+}
+
+impl Default for PipelineMatcher {
+    ///Creates a matcher allocating up to 100 to the top order, 40 percent of the rest in time priority and
+    /// what is left pro-rata, with the remainder in time priority and no minimum allocation
+    fn default() -> Self {
+        let steps = vec![AllocationStep::TopOrder { max_qty: 100 }, AllocationStep::Fifo { percent: 40 }, AllocationStep::ProRata];
+        Self::new(steps, ProrataMatcher::default())
+    }
+}
+
+impl PipelineMatcher {
+    /// Returns the quantity of the incoming order allocated to each resting order of the level by the
+    /// steps of the pipeline. Resting orders whose minimum quantity or all-or-none condition their
+    /// allocation would not meet get nothing and the pipeline is run again without them
+    fn allocations(&self, level: &VecDeque<OrderSingle>, incoming: &OrderSingle) -> Vec<u32> {
+        let mut eligible = vec![true; level.len()];
+        loop {
+            let capacities: Vec<u32> = level.iter().zip(&eligible)
+                .map(|(order, e)| if *e { order.visible_qty() } else { 0 })
+                .collect();
+            let mut allocations = vec![0; level.len()];
+            let mut remaining = incoming.qty().min(capacities.iter().sum());
+            for step in &self.steps {
+                remaining -= match step {
+                    AllocationStep::TopOrder { max_qty } if level.front().is_some_and(|order| order.improves_price()) =>
+                        Self::allocate_fifo(&capacities[..1], &mut allocations[..1], remaining.min(*max_qty)),
+                    AllocationStep::TopOrder { .. } => 0,
+                    AllocationStep::LeadMarketMaker { percent } => {
+                        let open: Vec<u32> = level.iter().zip(&capacities).zip(&allocations)
+                            .map(|((order, c), a)| if order.is_lead_market_maker() { c - a } else { 0 })
+                            .collect();
+                        self.allocate_pro_rata(&open, &mut allocations, Self::percent_of(remaining, *percent))
+                    }
+                    AllocationStep::Fifo { percent } =>
+                        Self::allocate_fifo(&capacities, &mut allocations, Self::percent_of(remaining, *percent)),
+                    AllocationStep::ProRata => {
+                        let open: Vec<u32> = capacities.iter().zip(&allocations).map(|(c, a)| c - a).collect();
+                        self.allocate_pro_rata(&open, &mut allocations, remaining)
+                    }
+                };
+            }
+            Self::allocate_fifo(&capacities, &mut allocations, remaining);
+
+            let mut changed = false;
+            for ((order, e), fill_qty) in level.iter().zip(eligible.iter_mut()).zip(&allocations) {
+                if *e && !order.accepts_fill(*fill_qty) {
+                    *e = false;
+                    changed = true;
+                }
+            }
+            if !changed {
+                return allocations;
+            }
+        }
+    }
+
+    fn percent_of(qty: u32, percent: u32) -> u32 {
+        (qty as u64 * percent as u64 / 100) as u32
+    }
+
+    /// Allocates up to `qty` in proportion to what the orders can still take and returns what was allocated
+    fn allocate_pro_rata(&self, open: &[u32], allocations: &mut [u32], qty: u32) -> u32 {
+        let split = self.pro_rata.split(open, qty);
+        allocations.iter_mut().zip(&split).for_each(|(allocation, qty)| *allocation += qty);
+        split.iter().sum()
+    }
+
+    /// Allocates up to `qty` in time priority without exceeding the capacities and returns what was allocated
+    fn allocate_fifo(capacities: &[u32], allocations: &mut [u32], qty: u32) -> u32 {
+        let mut remaining = qty;
+        for (capacity, allocation) in capacities.iter().zip(allocations.iter_mut()) {
+            let extra = remaining.min(capacity - *allocation);
+            *allocation += extra;
+            remaining -= extra;
+        }
+        qty - remaining
+    }
+
+    /// Fills `qty` of the order at the price and returns its execution report for the trade
+    fn execute(order: &mut OrderSingle, contra_id: &str, qty: u32, price: Price, liquidity: Liquidity, trade_id: &str, ids: &SharedIdGenerator) -> Fill {
+        order.fill(qty);
+        let mut fill = Fill::from(order, ids);
+        fill.set_qty(qty);
+        fill.set_cum_qty(order.cum_qty());
+        fill.set_leaves_qty(order.qty());
+        fill.set_price(price);
+        fill.set_secondary_cl_ord_id(contra_id.to_string());
+        fill.set_status(if order.qty() == 0 { Filled } else { PartialFill });
+        fill.set_liquidity(Some(liquidity));
+        fill.set_trade_id(Some(trade_id.to_string()));
+        fill
+    }
+
+    /// Allocates the incoming order across the resting orders of one price level and fills both at the
+    /// price of the level, or at the price of the incoming order against resting orders that arrived
+    /// after it. Resting orders of the same participant are not allocated, the self-trade prevention
+    /// mode of the incoming order applies to them first. The ids of the fills are drawn from the generator
+    fn allocate_level(&mut self, level: &mut VecDeque<OrderSingle>, incoming: &mut OrderSingle, price: Price, ids: &SharedIdGenerator) -> Vec<Fill> {
+        let mut fills = vec![];
+        for resting in level.iter_mut() {
+            if incoming.qty() > 0 && incoming.is_self_trade_with(resting) {
+                fills.extend(incoming.self_trade_prevention().apply(incoming, resting, ids));
+            }
+        }
+        level.retain(|order| order.qty() > 0);
+        let allocations = self.allocations(level, incoming);
+        for (resting, qty) in level.iter_mut().zip(allocations).filter(|(_, qty)| *qty > 0) {
+            let price = if incoming.is_aggressor_against(resting) { price } else { incoming.price() };
+            let (incoming_liquidity, resting_liquidity) = (incoming.liquidity_against(resting), resting.liquidity_against(incoming));
+            let trade_id = ids.next_id(IdType::Trade);
+            fills.push(Self::execute(incoming, resting.cl_ord_id(), qty, price, incoming_liquidity, &trade_id, ids));
+            fills.push(Self::execute(resting, incoming.cl_ord_id(), qty, price, resting_liquidity, &trade_id, ids));
+        }
+        level.retain(|order| order.qty() > 0);
+        ProrataMatcher::refresh_peaks(level);
+        fills
+    }
+
+    /// Sweeps the contra side price levels that the order crosses, best price first, allocating the
+    /// order across each level until it is filled or no crossing level remains. The order does not
+    /// trade at all unless what it can fill in one go meets its minimum quantity or all-or-none condition
+    fn sweep(&mut self, contra: &mut PriceLevels, order: &mut OrderSingle, ids: &SharedIdGenerator) -> Vec<Fill> {
+        let mut fills = vec![];
+        if !order.accepts_fill(contra.executable_qty(order)) {
+            trace!("order {} cannot be filled for its minimum quantity", order.cl_ord_id());
+            return fills;
+        }
+        let keys: Vec<OrderBookKey> = contra.iter()
+            .map(|(key, _)| key.clone())
+            .take_while(|key| order.crosses(key.price()))
+            .collect();
+        for key in keys {
+            let level = contra.get_mut(&key).unwrap();
+            while order.qty() > 0 {
+                let level_fills = self.allocate_level(level, order, key.price(), ids);
+                if level_fills.is_empty() {
+                    break;
+                }
+                fills.extend(level_fills);
+            }
+            contra.remove_if_empty(&key);
+            if order.qty() == 0 {
+                break;
+            }
+        }
+        fills
+    }
+
+}
+
+impl Matcher for PipelineMatcher {
+    /// Matches every symbol of the [`OrderBook`] through the allocation pipeline, its orders in the order
+    /// they arrived. The stop orders an order triggers are matched before the next order, and the pegged orders
+    /// the trades reprice are matched once the symbol has been matched
+    /// # Example:
+    /// ```rust
+    /// use matching_engine::common::utils::{create_order_book, read_input};
+    /// use matching_engine::matchers::matcher::Matcher;
+    /// use matching_engine::matchers::pipeline_matcher::{AllocationStep, PipelineMatcher};
+    /// use matching_engine::matchers::prorata_matcher::ProrataMatcher;
+    /// let mut order_book = create_order_book(read_input("test_data/orders.txt"));
+    /// let steps = vec![AllocationStep::TopOrder { max_qty: 100 }, AllocationStep::Fifo { percent: 40 }, AllocationStep::ProRata];
+    /// let mut matcher = PipelineMatcher::new(steps, ProrataMatcher::default());
+    /// let fills = matcher.match_order_book(&mut order_book);
+    /// ```
+    fn match_order_book(&mut self, order_book: &mut OrderBook) -> Vec<Fill> {
+        let mut fills = order_book.take_reports();
+        order_book.reprice_pegged_orders();
+        let symbols = order_book.symbols();
+        fills.extend(self.match_symbols(order_book, &symbols));
+        order_book.record_done(&fills);
+        fills
+    }
+
+    /// Matches every symbol in continuous trading, its orders in arrival order
+    fn match_symbols(&mut self, order_book: &mut OrderBook, symbols: &[String]) -> Vec<Fill> {
+        let mut fills = vec![];
+        for symbol in symbols {
+            if order_book.is_matching(symbol) {
+                fills.extend(self.match_arrivals(order_book, symbol));
+            }
+        }
+        order_book.remove_empty_levels();
+        fills
+    }
+
+    /// Sweeps the contra side with the order through the allocation pipeline, best price first. Whatever
+    /// is left of a limit order rests in the book, while the remainder of a market, IOC or FOK order is
+    /// cancelled. An FOK order that cannot be filled completely is cancelled without any fill. An order
+    /// that would trade outside the dynamic band halts its symbol and its remainder is held for the
+    /// volatility auction
+    fn match_incoming(&mut self, order_book: &mut OrderBook, mut order: OrderSingle) -> Vec<Fill> {
+        let ids = order_book.id_generator().clone();
+        let (buy, sell) = order_book.sides_mut(order.symbol());
+        let contra = if order.side() == Buy { sell } else { buy };
+        if order.time_in_force() == FOK && contra.executable_qty(&order) < order.qty() {
+            trace!("killing FOK order {}", order.cl_ord_id());
+            return vec![Fill::cancelled(&order, order.cum_qty(), &ids)];
+        }
+        let mut fills = vec![];
+        if order.time_in_force() != FOK || !order_book.breaches_band(&order) {
+            let symbol = order.symbol().clone();
+            fills = order_book.sweep_within_band(&symbol, order.side(), |contra| self.sweep(contra, &mut order, &ids));
+        }
+        if order_book.breaches_band(&order) {
+            order_book.halt_for_band(order);
+        } else if order.qty() > 0 && order.is_immediate() {
+            fills.push(order.cancel(&ids));
+        } else if order.qty() > 0 {
+            order_book.add_order_to_order_book(order);
+        }
+        fills
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::VecDeque;
+
+    use crate::common::utils::create_order_from_string;
+    use crate::matchers::pipeline_matcher::{AllocationStep, PipelineMatcher};
+    use crate::matchers::prorata_matcher::ProrataMatcher;
+    use crate::model::domain::OrderSingle;
+
+    fn level(lines: &[&str]) -> VecDeque<OrderSingle> {
+        lines.iter().map(|line| create_order_from_string(line.to_string())).collect()
+    }
+
+    #[test]
+    fn test_top_order_then_fifo_percentage_then_pro_rata() {
+        let mut resting = level(&["id1 IBM 100 601 Sell", "id2 IBM 200 601 Sell", "id3 IBM 100 601 Sell"]);
+        resting[0].set_improves_price(true);
+        let incoming = create_order_from_string("id4 IBM 300 601 Buy".to_string());
+        let steps = vec![AllocationStep::TopOrder { max_qty: 50 }, AllocationStep::Fifo { percent: 40 }, AllocationStep::ProRata];
+        let matcher = PipelineMatcher::new(steps, ProrataMatcher::default());
+
+        // 50 to the top order, 40% of 250 in time priority and the 150 left pro-rata on 150 and 100
+        assert_eq!(matcher.allocations(&resting, &incoming), vec![100, 140, 60]);
+        resting[0].set_improves_price(false);
+        // without a top order, 40% of 300 in time priority and the 180 left pro-rata on 180 and 100
+        assert_eq!(matcher.allocations(&resting, &incoming), vec![100, 136, 64]);
+    }
+
+    #[test]
+    fn test_lead_market_makers_are_guaranteed_their_share_before_pro_rata() {
+        let resting = level(&["id1 IBM 300 601 Sell", "id2 IBM 100 601 Sell lmm=Y", "id3 IBM 100 601 Sell lmm=Y"]);
+        let incoming = create_order_from_string("id4 IBM 200 601 Buy".to_string());
+        let steps = vec![AllocationStep::LeadMarketMaker { percent: 40 }, AllocationStep::ProRata];
+        let matcher = PipelineMatcher::new(steps, ProrataMatcher::default());
+
+        // 40% of 200 split between the lead market makers and the 120 left pro-rata on 300, 60 and 60
+        assert_eq!(matcher.allocations(&resting, &incoming), vec![86, 57, 57]);
+        let fifo = PipelineMatcher::new(vec![AllocationStep::LeadMarketMaker { percent: 40 }, AllocationStep::Fifo { percent: 100 }], ProrataMatcher::default());
+        assert_eq!(fifo.allocations(&resting, &incoming), vec![120, 40, 40]);
+    }
+
+    #[test]
+    fn test_quantity_left_by_the_steps_is_allocated_in_time_priority() {
+        let resting = level(&["id1 IBM 100 601 Sell", "id2 IBM 100 601 Sell"]);
+        let incoming = create_order_from_string("id3 IBM 150 601 Buy".to_string());
+        let matcher = PipelineMatcher::new(vec![AllocationStep::Fifo { percent: 10 }], ProrataMatcher::default());
+
+        assert_eq!(matcher.allocations(&resting, &incoming), vec![100, 50]);
+    }
+}
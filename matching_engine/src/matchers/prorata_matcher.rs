@@ -1,126 +1,278 @@
-use std::collections::{HashMap, VecDeque};
-use std::sync::mpsc::{Receiver, Sender};
-use std::thread::sleep;
-use std::time::Duration;
-
-use log::{error, info};
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, VecDeque};
 
+use crate::common::id_generator::{IdType, SharedIdGenerator};
 use crate::matchers::matcher::Matcher;
-use crate::model::domain::{Fill, OrderBook, OrderSingle};
-use crate::model::domain::Side::{Buy, Sell};
+use crate::model::domain::{Fill, OrderBook, OrderBookKey, OrderSingle, PriceLevels};
 use crate::model::domain::Status::{Filled, PartialFill};
-use crate::utils::{Aggregator, Sigma};
+use crate::model::domain::TimeInForce::FOK;
+
+///How the quantity left over by rounding the pro-rata allocations down is allocated, so that the resting
+/// orders are allocated exactly what the incoming order executes
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RemainderPolicy {
+    ///The largest resting orders are allocated the remainder first, the earliest of equal orders first
+    LargestOrder,
+    ///The earliest resting orders are allocated the remainder first
+    #[default]
+    TimePriority,
+    ///The resting orders are allocated one lot each in time priority, in turns, until nothing is left
+    RoundRobin,
+}
 
-pub struct ProrataMatcher ;
+///Allocates incoming orders across the resting orders of a price level in proportion to their visible
+/// quantities. Allocations below the minimum allocation are dropped and whatever is left over by the
+/// rounding is allocated by the remainder policy
+#[derive(Debug, Default)]
+pub struct ProrataMatcher {
+    remainder_policy: RemainderPolicy,
+    min_allocation: u32,
+}
 
 impl ProrataMatcher {
+    pub fn new(remainder_policy: RemainderPolicy, min_allocation: u32) -> Self {
+        Self { remainder_policy, min_allocation }
+    }
+
+    pub fn remainder_policy(&self) -> RemainderPolicy {
+        self.remainder_policy
+    }
 
+    pub fn min_allocation(&self) -> u32 {
+        self.min_allocation
+    }
 
-    fn proportional_match(&mut self, buy_orders: &mut VecDeque<OrderSingle>, sell_orders: &mut VecDeque<OrderSingle>) -> Vec<Fill> {
+    /// Allocates every incoming order across the resting orders in proportion to their visible
+    /// quantities. Fills are done at the price of the resting order. A resting iceberg order whose
+    /// peak is consumed shows a new peak at the back of the queue. Resting orders of the same participant
+    /// as the incoming order are not allocated, the self-trade prevention mode of the incoming order
+    /// applies to them first. An incoming order is only allocated if its allocation meets its minimum
+    /// quantity or all-or-none condition. The ids of the fills are drawn from the generator
+    fn proportional_match(&mut self, buy_orders: &mut VecDeque<OrderSingle>, sell_orders: &mut VecDeque<OrderSingle>, ids: &SharedIdGenerator) -> Vec<Fill> {
         let mut fills = vec![];
-        let mut c_map = self.create_cum_qty_map(buy_orders);
-        let mut e_map = self.create_cum_qty_map(sell_orders);
-        for sell_order in sell_orders.clone().iter_mut() {
-            let sell_order_qty = sell_order.qty();
-            let items: Vec<OrderSingle> = buy_orders.clone().into_iter().collect();
-            let total = Aggregator::sigma(&items);
-            for order in buy_orders.clone().iter_mut() {
-                let ratio: f64 = order.qty() as f64 / total as f64;
-                let qty: f64 = sell_order_qty as f64 * ratio;
-                let fill_qty = qty.floor() as u32;
-                fills.push(self.create_client_fill(order, sell_order.cl_ord_id(), &fill_qty, &mut c_map, buy_orders));
-                fills.push(self.create_exchange_fill(sell_order, order.cl_ord_id(), &fill_qty, &mut e_map, sell_orders));
-            }
-            let total_filled_qty = Aggregator::sigma(&fills);
-            if total_filled_qty >= sell_order.qty() {
-                sell_orders.pop_front();
-            } else {
-                sell_order.set_qty(sell_order.qty() - total_filled_qty);
+        let mut e_map: BTreeMap<String, u32> = sell_orders.iter().map(|order| (order.cl_ord_id().clone(), 0)).collect();
+        for sell_order in sell_orders.iter_mut() {
+            for order in buy_orders.iter_mut() {
+                if sell_order.qty() > 0 && sell_order.is_self_trade_with(order) {
+                    fills.extend(sell_order.self_trade_prevention().apply(sell_order, order, ids));
+                }
+            }
+            buy_orders.retain(|o| o.qty() > 0);
+            let total: u32 = buy_orders.iter().map(|o| o.visible_qty()).sum();
+            if total == 0 {
+                break;
             }
+            let allocations = self.allocations(buy_orders, sell_order);
+            for (order, fill_qty) in buy_orders.iter_mut().zip(allocations) {
+                if fill_qty == 0 {
+                    continue;
+                }
+                let trade_id = ids.next_id(IdType::Trade);
+                let mut cl_fill = self.create_client_fill(order, sell_order.cl_ord_id(), &fill_qty, ids);
+                cl_fill.set_liquidity(Some(order.liquidity_against(sell_order)));
+                cl_fill.set_trade_id(Some(trade_id.clone()));
+                fills.push(cl_fill);
+                let mut ex_fill = self.create_exchange_fill(sell_order, order.cl_ord_id(), &fill_qty, &mut e_map, ids);
+                ex_fill.set_price(order.price());
+                ex_fill.set_liquidity(Some(sell_order.liquidity_against(order)));
+                ex_fill.set_trade_id(Some(trade_id));
+                fills.push(ex_fill);
+            }
+            sell_order.fill(e_map[sell_order.cl_ord_id()]);
+            buy_orders.retain(|o| o.qty() > 0);
+            Self::refresh_peaks(buy_orders);
         }
+        sell_orders.retain(|o| o.qty() > 0);
 
         fills
     }
 
-    fn create_client_fill(&self, order: &mut OrderSingle, sec_id: &String,
-                          fill_qty: &u32,
-                          c_map: &mut HashMap<String, u32>,
-                          buy_orders: &mut VecDeque<OrderSingle>) -> Fill {
+    /// Returns the quantity of the incoming order allocated to each resting order, in proportion to their
+    /// visible quantities. Resting orders whose minimum quantity or all-or-none condition their allocation
+    /// would not meet get nothing and the quantity is allocated across the others
+    fn allocations(&self, resting: &VecDeque<OrderSingle>, incoming: &OrderSingle) -> Vec<u32> {
+        let mut eligible = vec![true; resting.len()];
+        loop {
+            let capacities: Vec<u32> = resting.iter().zip(&eligible)
+                .map(|(order, e)| if *e { order.visible_qty() } else { 0 })
+                .collect();
+            let qty = incoming.qty().min(capacities.iter().sum());
+            let allocations = self.split(&capacities, qty);
+
+            let mut changed = false;
+            for ((order, e), fill_qty) in resting.iter().zip(eligible.iter_mut()).zip(&allocations) {
+                if *e && !order.accepts_fill(*fill_qty) {
+                    *e = false;
+                    changed = true;
+                }
+            }
+            if !changed {
+                return allocations;
+            }
+        }
+    }
+
+    /// Splits `qty` across orders that can take up to the given capacities, in proportion to the capacities
+    /// and rounded down. An order whose allocation is below the minimum allocation gets nothing and the
+    /// remainder policy allocates the quantity left over to the orders above the threshold only, what they
+    /// cannot take being left unallocated. When no order reaches the threshold the whole quantity goes to
+    /// the largest order with the largest order policy and to the earliest order otherwise, and to the
+    /// next orders in that priority once it is full
+    pub(crate) fn split(&self, capacities: &[u32], qty: u32) -> Vec<u32> {
+        let total: u64 = capacities.iter().map(|capacity| *capacity as u64).sum();
+        let qty = qty.min(total as u32);
+        let mut allocations: Vec<u32> = capacities.iter().map(|capacity| {
+            if total == 0 {
+                return 0;
+            }
+            let allocation = (qty as u64 * *capacity as u64 / total) as u32;
+            if allocation < self.min_allocation { 0 } else { allocation }
+        }).collect();
+        let above_threshold: Vec<bool> = capacities.iter().zip(&allocations)
+            .map(|(capacity, allocation)| *capacity > 0 && (self.min_allocation == 0 || *allocation > 0))
+            .collect();
+        if !above_threshold.contains(&true) {
+            return self.allocate_in_priority(capacities, qty);
+        }
+        let remainder = qty - allocations.iter().sum::<u32>();
+        self.allocate_remainder(capacities, &above_threshold, &mut allocations, remainder);
+        allocations
+    }
+
+    /// Allocates `qty` to the largest order first with the largest order policy and to the earliest order
+    /// first otherwise, each order up to its capacity
+    fn allocate_in_priority(&self, capacities: &[u32], mut qty: u32) -> Vec<u32> {
+        let mut indices: Vec<usize> = (0..capacities.len()).collect();
+        if self.remainder_policy == RemainderPolicy::LargestOrder {
+            indices.sort_by_key(|i| Reverse(capacities[*i]));
+        }
+        let mut allocations = vec![0; capacities.len()];
+        for i in indices {
+            allocations[i] = qty.min(capacities[i]);
+            qty -= allocations[i];
+        }
+        allocations
+    }
+
+    /// Allocates the remainder to the candidate orders by the remainder policy, never beyond the capacity
+    /// of an order. Returns what could not be allocated
+    fn allocate_remainder(&self, capacities: &[u32], candidates: &[bool], allocations: &mut [u32], mut remainder: u32) -> u32 {
+        let mut indices: Vec<usize> = (0..capacities.len()).filter(|i| candidates[*i]).collect();
+        if self.remainder_policy == RemainderPolicy::LargestOrder {
+            indices.sort_by_key(|i| Reverse(capacities[*i]));
+        }
+        if self.remainder_policy == RemainderPolicy::RoundRobin {
+            while remainder > 0 {
+                let mut allocated = false;
+                for &i in &indices {
+                    if remainder == 0 {
+                        break;
+                    }
+                    if allocations[i] == capacities[i] {
+                        continue;
+                    }
+                    allocations[i] += 1;
+                    remainder -= 1;
+                    allocated = true;
+                }
+                if !allocated {
+                    break;
+                }
+            }
+        } else {
+            for i in indices {
+                let extra = remainder.min(capacities[i] - allocations[i]);
+                allocations[i] += extra;
+                remainder -= extra;
+            }
+        }
+        remainder
+    }
 
+    /// Shows a new peak for the iceberg orders whose peak has been consumed and moves them to the back
+    /// of the queue, keeping their relative order
+    pub(crate) fn refresh_peaks(orders: &mut VecDeque<OrderSingle>) {
+        let (mut refreshed, kept): (VecDeque<OrderSingle>, VecDeque<OrderSingle>) =
+            orders.drain(..).partition(|o| o.needs_refresh());
+        refreshed.iter_mut().for_each(|o| o.refresh_peak());
+        orders.extend(kept);
+        orders.extend(refreshed);
+    }
 
-        //println!("sell order qty {} fill qty {}", sell_order.qty(), fill_qty);
-        let mut cl_fill = Fill::from(order);
-        let mut cl_cum_qty = c_map.get(order.cl_ord_id()).unwrap().clone();
-        // println!("order cl_ord_id {}, order cum_qty {}",order.qty(),cl_cum_qty);
-        cl_fill.set_qty(fill_qty.clone());
-        cl_cum_qty += fill_qty;
-        cl_fill.set_cum_qty(cl_cum_qty);
-        cl_fill.set_secondary_cl_ord_id(sec_id.clone());
-        c_map.insert(order.cl_ord_id().clone(), cl_cum_qty);
+    /// Runs proportional matches between the resting and the incoming orders of a price level for as
+    /// long as they produce fills, so that the hidden quantity of iceberg orders is reached as their
+    /// peaks are refreshed
+    fn allocate(&mut self, resting: &mut VecDeque<OrderSingle>, incoming: &mut VecDeque<OrderSingle>, ids: &SharedIdGenerator) -> Vec<Fill> {
+        let mut fills = vec![];
+        loop {
+            let level_fills = self.proportional_match(resting, incoming, ids);
+            if level_fills.is_empty() {
+                return fills;
+            }
+            fills.extend(level_fills);
+        }
+    }
 
-        cl_fill.set_leaves_qty(order.qty() - fill_qty);
+    fn create_client_fill(&self, order: &mut OrderSingle, sec_id: &str, fill_qty: &u32, ids: &SharedIdGenerator) -> Fill {
+        let mut cl_fill = Fill::from(order, ids);
+        order.fill(*fill_qty);
+        cl_fill.set_qty(*fill_qty);
+        cl_fill.set_cum_qty(order.cum_qty());
+        cl_fill.set_leaves_qty(order.qty());
+        cl_fill.set_secondary_cl_ord_id(sec_id.to_string());
         if cl_fill.leaves_qty() == 0 {
             cl_fill.set_status(Filled);
-            buy_orders.pop_front();
         } else {
-            order.set_qty(order.qty() - fill_qty);
             cl_fill.set_status(PartialFill);
-            buy_orders.pop_front();
-            buy_orders.push_back(order.clone());
         }
         cl_fill
     }
 
-    fn create_exchange_fill(&self, sell_order: &mut OrderSingle, sec_id: &String, fill_qty: &u32, e_map: &mut HashMap<String, u32>, sell_orders: &mut VecDeque<OrderSingle>) -> Fill {
-        let mut ex_fill = Fill::from(sell_order);
-        let mut ex_cum_qty = e_map.get(sell_order.cl_ord_id()).unwrap().clone();
+    fn create_exchange_fill(&self, sell_order: &OrderSingle, sec_id: &str, fill_qty: &u32, e_map: &mut BTreeMap<String, u32>, ids: &SharedIdGenerator) -> Fill {
+        let mut ex_fill = Fill::from(sell_order, ids);
+        let mut ex_cum_qty = e_map[sell_order.cl_ord_id()];
         ex_cum_qty += fill_qty;
-        ex_fill.set_qty(fill_qty.clone());
-        ex_fill.set_cum_qty(ex_cum_qty);
+        ex_fill.set_qty(*fill_qty);
+        ex_fill.set_cum_qty(sell_order.cum_qty() + ex_cum_qty);
         ex_fill.set_leaves_qty(sell_order.qty() - ex_cum_qty);
-        ex_fill.set_secondary_cl_ord_id(sec_id.clone());
+        ex_fill.set_secondary_cl_ord_id(sec_id.to_string());
         e_map.insert(sell_order.cl_ord_id().clone(), ex_cum_qty);
-        // self.set_ex_cum_qty(ex_fill.cum_qty());
-        ex_cum_qty = ex_fill.cum_qty();
         if ex_fill.leaves_qty() == 0 {
             ex_fill.set_status(Filled);
-            sell_orders.pop_front();
         } else {
             ex_fill.set_status(PartialFill);
         }
         ex_fill
     }
 
-/*    fn create_cum_qty_map(&self, buy_orders: &VecDeque<OrderSingle>) -> HashMap<String, u32> {
-        let mut map = HashMap::new();
-
-        for order in buy_orders {
-            map.insert(order.cl_ord_id().clone(), 0);
+    /// Allocates the order across the contra price levels that it crosses, one level at a time
+    /// starting with the best price, until it is filled. The order does not trade at all unless what it
+    /// can fill in one go meets its minimum quantity or all-or-none condition. Returns the fills and
+    /// whatever is left of the order
+    fn sweep(&mut self, contra: &mut PriceLevels, order: &OrderSingle, ids: &SharedIdGenerator) -> (Vec<Fill>, Option<OrderSingle>) {
+        if !order.accepts_fill(contra.executable_qty(order)) {
+            return (vec![], Some(order.clone()));
         }
-        map
-    }*/
-}
-
-
-impl Matcher for ProrataMatcher {
-    fn start(&mut self, tx: &Sender<OrderBook>, rx: &Receiver<OrderBook>) {
-        info!("Starting Prorata Matcher...");
-        loop {
-            let mut ob = match rx.recv() {
-                Ok(ob) => ob,
-                Err(e) => {
-                    error!("Error receiving Order Book {}",e);
-                    OrderBook::default()
-                }
-            };
-            self.match_order_book(&mut ob);
-            if let Err(e) = tx.send(ob) {
-                error!("Error sending order book {}",e);
+        let keys: Vec<OrderBookKey> = contra.iter()
+            .map(|(key, _)| key.clone())
+            .take_while(|key| order.crosses(key.price()))
+            .collect();
+        let mut fills = vec![];
+        let mut incoming = VecDeque::from([order.clone()]);
+        for key in keys {
+            if incoming.is_empty() {
+                break;
             }
-            sleep(Duration::from_secs(1));
+            let resting = contra.get_mut(&key).unwrap();
+            fills.extend(self.allocate(resting, &mut incoming, ids));
+            contra.remove_if_empty(&key);
         }
+        (fills, incoming.pop_front())
     }
+}
 
+
+impl Matcher for ProrataMatcher {
     /// Matches the [`OrderBook`] according to the ratios of buy side order quantities
     ///
     /// # Logic:
@@ -135,6 +287,12 @@ impl Matcher for ProrataMatcher {
     ///
     /// O1 fill = n1/(n1+n2) or 3/4th of 300  = 225
     /// 02 fill = n2/(n1+n2) or 1/4th of 300 = 75
+    ///
+    /// For every symbol the orders of the book are matched in the order they arrived, as if each had been
+    /// submitted on arrival, the order that arrived later being allocated across the resting orders it
+    /// crosses, level by level from the best contra price. Whatever cannot be filled of a market, IOC or FOK
+    /// order is cancelled. The stop orders an order triggers are matched before the next order, and the
+    /// pegged orders the trades reprice are matched once the symbol has been matched
     ///```rust
     /// use matching_engine::common::utils::{create_order_book, read_input};
     /// use matching_engine::matchers::fifo_matcher::FIFOMatcher;
@@ -143,38 +301,60 @@ impl Matcher for ProrataMatcher {
     /// let input = read_input("test_data/orders.txt");
     /// let mut order_book = create_order_book(input);
     /// //create a matcher
-    /// let mut  matcher = ProrataMatcher;
+    /// let mut  matcher = ProrataMatcher::default();
     /// // match the order book with the matcher to produce executions
     /// let mut fills = matcher.match_order_book(&mut order_book);
     /// ```
     fn match_order_book(&mut self, order_book: &mut OrderBook) -> Vec<Fill> {
-        let mut buy_map = order_book.get_orders_for(Buy);
-        let mut sell_map = order_book.get_orders_for(Sell);
-        let mut all_fills = vec![];
-        for (key, buy_orders) in buy_map.clone().iter_mut() {
-            if sell_map.contains_key(key) {
-                let mut clone = sell_map.clone();
-                let mut sell_orders = clone.get_mut(key).unwrap();
-                let fills = self.proportional_match(buy_orders, &mut sell_orders);
-                for fill in fills {
-                    all_fills.push(fill);
-                }
-                if sell_orders.is_empty() {
-                    sell_map.remove(key);
-                }
-            } else {
-                continue;
-            }
+        let mut all_fills = order_book.take_reports();
+        order_book.reprice_pegged_orders();
+        let symbols = order_book.symbols();
+        all_fills.extend(self.match_symbols(order_book, &symbols));
+        order_book.record_done(&all_fills);
+        all_fills
+    }
 
-            if buy_orders.is_empty() {
-                buy_map.remove(key);
+    /// Matches every symbol in continuous trading, its orders in arrival order
+    fn match_symbols(&mut self, order_book: &mut OrderBook, symbols: &[String]) -> Vec<Fill> {
+        let mut fills = vec![];
+        for symbol in symbols {
+            if order_book.is_matching(symbol) {
+                fills.extend(self.match_arrivals(order_book, symbol));
             }
         }
+        order_book.remove_empty_levels();
+        fills
+    }
 
-        order_book.update_order_book(buy_map, Buy);
-        order_book.update_order_book(sell_map, Sell);
-        Fill::pretty_print(&all_fills);
-        all_fills
+    /// Allocates the order across the contra price levels that it crosses, best price first. Whatever is
+    /// left of a limit order rests in the book, while the remainder of a market, IOC or FOK order is
+    /// cancelled. An FOK order is allocated on a copy of the contra side first and is cancelled without
+    /// any fill unless the allocation fills it completely. An order that would trade outside the dynamic
+    /// band halts its symbol and its remainder is held for the volatility auction
+    fn match_incoming(&mut self, order_book: &mut OrderBook, order: OrderSingle) -> Vec<Fill> {
+        let symbol = order.symbol().clone();
+        let ids = order_book.id_generator().clone();
+        if order.time_in_force() == FOK {
+            if order_book.breaches_band(&order) {
+                order_book.halt_for_band(order);
+                return vec![];
+            }
+            return order_book.sweep_within_band(&symbol, order.side(), |contra| {
+                // the trial draws from a generator of its own, so that a killed order uses up no ids
+                match self.sweep(&mut contra.clone(), &order, &SharedIdGenerator::default()) {
+                    (_, None) => self.sweep(contra, &order, &ids).0,
+                    (_, Some(_)) => vec![Fill::cancelled(&order, order.cum_qty(), &ids)],
+                }
+            });
+        }
+        let (mut fills, remainder) = order_book.sweep_within_band(&symbol, order.side(), |contra| self.sweep(contra, &order, &ids));
+        match remainder {
+            Some(remainder) if order_book.breaches_band(&remainder) => order_book.halt_for_band(remainder),
+            Some(remainder) if order.is_immediate() => fills.push(Fill::cancelled(&order, remainder.cum_qty(), &ids)),
+            Some(remainder) => order_book.add_order_to_order_book(remainder),
+            None => {}
+        }
+        fills
     }
 }
 
@@ -182,11 +362,14 @@ impl Matcher for ProrataMatcher {
 mod tests {
     use std::collections::VecDeque;
 
-    use crate::matchers::prorata_matcher::ProrataMatcher;
-    use crate::model::domain::{Fill, Status};
+    use crate::common::id_generator::SharedIdGenerator;
+    use crate::matchers::matcher::Matcher;
+    use crate::matchers::prorata_matcher::{ProrataMatcher, RemainderPolicy};
+    use crate::model::domain::{Fill, OrderSingle, Status};
     use crate::model::domain::Side::{Buy, Sell};
-    use crate::model::domain::Status::{Filled, PartialFill};
-    use crate::utils::create_order_from_string;
+    use crate::model::domain::Status::{Cancelled, Filled, PartialFill};
+    use crate::model::price::Price;
+    use crate::utils::{Aggregator, create_order_book, create_order_from_string, Sigma};
 
     #[test]
     fn test_proportional_match() {
@@ -196,8 +379,8 @@ mod tests {
         let mut sell_orders = VecDeque::new();
         sell_orders.push_back(create_order_from_string("id9 IBM 300 602.5 Sell".to_string()));
         sell_orders.push_back(create_order_from_string("id10 IBM 100 602.5 Sell".to_string()));
-        let mut pro = ProrataMatcher;
-        let fills = pro.proportional_match(&mut buy_orders, &mut sell_orders);
+        let mut pro = ProrataMatcher::default();
+        let fills = pro.proportional_match(&mut buy_orders, &mut sell_orders, &SharedIdGenerator::default());
 
         //assertions
         let _client_fills: VecDeque<Fill> = fills.clone().into_iter().filter(|f| f.side() == Buy).collect();
@@ -244,6 +427,150 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_market_order_is_allocated_level_by_level() {
+        let input = vec![
+            "id1 IBM 300 602 Buy".to_string(),
+            "id2 IBM 100 602 Buy".to_string(),
+            "id3 IBM 100 601 Buy".to_string(),
+            "id4 IBM 600 MKT Sell".to_string(),
+        ];
+        let mut order_book = create_order_book(input);
+        let fills = ProrataMatcher::default().match_order_book(&mut order_book);
+        let market_fills: Vec<Fill> = fills.into_iter().filter(|f| f.cl_ord_id() == "id4").collect();
+
+        assert_eq!(market_fills.len(), 4);
+        assert_fills(&market_fills[0], 300, 300, 300, PartialFill);
+        assert_eq!(market_fills[0].price(), Price::new(602, 0));
+        assert_fills(&market_fills[1], 100, 400, 200, PartialFill);
+        assert_fills(&market_fills[2], 100, 500, 100, PartialFill);
+        assert_eq!(market_fills[2].price(), Price::new(601, 0));
+        assert_fills(&market_fills[3], 0, 500, 0, Cancelled);
+        assert!(order_book.is_empty());
+    }
+
+    #[test]
+    fn test_fok_order_is_not_partially_allocated() {
+        let input = vec![
+            "id1 IBM 30 602 Buy".to_string(),
+            "id2 IBM 30 602 Buy".to_string(),
+            "id3 IBM 30 602 Buy".to_string(),
+            "id4 IBM 100 602 Sell tif=FOK".to_string(),
+        ];
+        let mut order_book = create_order_book(input);
+        let fills = ProrataMatcher::default().match_order_book(&mut order_book);
+
+        assert_eq!(fills.len(), 1);
+        assert_fills(&fills[0], 0, 0, 0, Cancelled);
+        let resting: Vec<OrderSingle> = order_book.get_orders_for(Buy).into_values().flatten().collect();
+        assert_eq!(Aggregator::sigma(&resting), 90);
+    }
+
+    #[test]
+    fn test_allocation_is_based_on_displayed_size() {
+        let input = vec![
+            "id1 IBM 900 602 Buy display=100".to_string(),
+            "id2 IBM 100 602 Buy".to_string(),
+            "id3 IBM 100 602 Sell".to_string(),
+        ];
+        let mut order_book = create_order_book(input);
+        let fills = ProrataMatcher::default().match_order_book(&mut order_book);
+        let client_fills: Vec<Fill> = fills.into_iter().filter(|f| f.side() == Buy).collect();
+
+        assert_eq!(client_fills.len(), 2);
+        assert_fills(&client_fills[0], 50, 50, 850, PartialFill);
+        assert_fills(&client_fills[1], 50, 50, 50, PartialFill);
+    }
+
+    #[test]
+    fn test_resting_orders_of_the_same_participant_are_not_allocated() {
+        let input = vec![
+            "id1 IBM 300 602 Buy participant=P1".to_string(),
+            "id2 IBM 100 602 Buy participant=P2".to_string(),
+            "id3 IBM 100 602 Sell participant=P1 stp=CO".to_string(),
+        ];
+        let mut order_book = create_order_book(input);
+        let fills = ProrataMatcher::default().match_order_book(&mut order_book);
+
+        assert_eq!(fills[0].cl_ord_id(), "id1");
+        assert_eq!(*fills[0].status(), Cancelled);
+        let client_fills: Vec<Fill> = fills.into_iter().filter(|f| f.side() == Buy && f.qty() > 0).collect();
+        assert_eq!(client_fills.len(), 1);
+        assert_eq!(client_fills[0].cl_ord_id(), "id2");
+        assert_fills(&client_fills[0], 100, 100, 0, Filled);
+        assert!(order_book.is_empty());
+    }
+
+    #[test]
+    fn test_all_or_none_order_is_left_out_of_a_partial_allocation() {
+        let input = vec![
+            "id1 IBM 300 602 Buy aon=Y".to_string(),
+            "id2 IBM 100 602 Buy".to_string(),
+            "id3 IBM 100 602 Buy".to_string(),
+            "id4 IBM 100 602 Sell".to_string(),
+        ];
+        let mut order_book = create_order_book(input);
+        let fills = ProrataMatcher::default().match_order_book(&mut order_book);
+        let client_fills: Vec<Fill> = fills.into_iter().filter(|f| f.side() == Buy).collect();
+
+        assert_eq!(client_fills.len(), 2);
+        assert_eq!(client_fills[0].cl_ord_id(), "id2");
+        assert_fills(&client_fills[0], 50, 50, 50, PartialFill);
+        assert_eq!(client_fills[1].cl_ord_id(), "id3");
+        assert_fills(&client_fills[1], 50, 50, 50, PartialFill);
+    }
+
+    #[test]
+    fn test_remainder_is_allocated_by_the_remainder_policy() {
+        let resting: VecDeque<OrderSingle> = ["id1 IBM 30 602 Buy", "id2 IBM 50 602 Buy", "id3 IBM 30 602 Buy"].iter()
+            .map(|line| create_order_from_string(line.to_string()))
+            .collect();
+        let incoming = create_order_from_string("id4 IBM 24 602 Sell".to_string());
+        let allocate = |policy, min_allocation| ProrataMatcher::new(policy, min_allocation).allocations(&resting, &incoming);
+
+        // 24 is allocated 6, 10 and 6 rounded down from 6.5, 10.9 and 6.5, leaving 2
+        assert_eq!(allocate(RemainderPolicy::TimePriority, 0), vec![8, 10, 6]);
+        assert_eq!(allocate(RemainderPolicy::LargestOrder, 0), vec![6, 12, 6]);
+        assert_eq!(allocate(RemainderPolicy::RoundRobin, 0), vec![7, 11, 6]);
+        assert_eq!(allocate(RemainderPolicy::TimePriority, 7), vec![0, 24, 0]);
+        // no allocation reaches 11, so the whole quantity goes to a single order
+        assert_eq!(allocate(RemainderPolicy::RoundRobin, 11), vec![24, 0, 0]);
+        assert_eq!(allocate(RemainderPolicy::LargestOrder, 11), vec![0, 24, 0]);
+    }
+
+    #[test]
+    fn test_the_remainder_only_goes_to_the_orders_above_the_minimum_allocation() {
+        let matcher = ProrataMatcher::new(RemainderPolicy::RoundRobin, 5);
+
+        // 30 is split 15, 3 and 12, the 3 is dropped and goes to the first and the third order in turn
+        assert_eq!(matcher.split(&[50, 10, 40], 30), vec![17, 0, 13]);
+        // the orders above the threshold are full, so what they cannot take is left unallocated
+        assert_eq!(matcher.split(&[6, 4, 6], 15), vec![6, 0, 6]);
+        // no order reaches the threshold, so the quantity goes to the orders in time priority
+        assert_eq!(matcher.split(&[3, 3, 3, 3], 8), vec![3, 3, 2, 0]);
+    }
+
+    #[test]
+    fn test_fill_totals_equal_the_executed_quantity() {
+        let input = vec![
+            "id1 IBM 7 602 Buy".to_string(),
+            "id2 IBM 11 602 Buy".to_string(),
+            "id3 IBM 13 602 Buy".to_string(),
+            "id4 IBM 17 602 Buy".to_string(),
+            "id5 IBM 29 602 Sell".to_string(),
+        ];
+        for policy in [RemainderPolicy::LargestOrder, RemainderPolicy::TimePriority, RemainderPolicy::RoundRobin] {
+            let mut order_book = create_order_book(input.clone());
+            let fills = ProrataMatcher::new(policy, 2).match_order_book(&mut order_book);
+            let client_qty: u32 = fills.iter().filter(|f| f.side() == Buy).map(|f| f.qty()).sum();
+            let exchange_fills: Vec<&Fill> = fills.iter().filter(|f| f.side() == Sell).collect();
+
+            assert_eq!(client_qty, 29);
+            assert_eq!(exchange_fills.iter().map(|f| f.qty()).sum::<u32>(), 29);
+            assert_fills(exchange_fills.last().unwrap(), exchange_fills.last().unwrap().qty(), 29, 0, Filled);
+        }
+    }
+
     fn assert_fills(fill: &Fill, fill_qty: u32, cum_qty: u32, leaves_qty: u32, status: Status) {
         assert_eq!(fill.qty(), fill_qty);
         assert_eq!(fill.cum_qty(), cum_qty);
@@ -1,3 +1,8 @@
 //pub mod execution;
 
+pub mod cancel;
+pub mod command;
 pub mod domain;
+pub mod instrument;
+pub mod price;
+pub mod session;
@@ -0,0 +1,149 @@
+use std::fmt::{Display, Formatter};
+
+use serde::{Deserialize, Serialize};
+
+use crate::model::domain::Side;
+use crate::model::price::Price;
+
+///Requests the cancellation of the resting order with the given `cl_ord_id`. When the side or the
+/// symbol is given, only an order with that side or symbol is cancelled
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CancelRequest {
+    cl_ord_id: String,
+    #[serde(default)]
+    symbol: Option<String>,
+    #[serde(default)]
+    side: Option<Side>,
+}
+
+impl CancelRequest {
+    pub fn new(cl_ord_id: String, symbol: Option<String>, side: Option<Side>) -> Self {
+        Self { cl_ord_id, symbol, side }
+    }
+
+    pub fn cl_ord_id(&self) -> &str {
+        &self.cl_ord_id
+    }
+
+    pub fn symbol(&self) -> Option<&str> {
+        self.symbol.as_deref()
+    }
+
+    pub fn side(&self) -> Option<Side> {
+        self.side
+    }
+}
+
+///Requests a change of the price and/or the quantity of the resting order with the given `cl_ord_id`.
+/// The quantity is the new total quantity of the order, including what has already been filled.
+/// When the side or the symbol is given, only an order with that side or symbol is replaced
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReplaceRequest {
+    cl_ord_id: String,
+    #[serde(default)]
+    symbol: Option<String>,
+    #[serde(default)]
+    side: Option<Side>,
+    #[serde(default)]
+    qty: Option<u32>,
+    #[serde(default)]
+    price: Option<Price>,
+}
+
+impl ReplaceRequest {
+    pub fn new(cl_ord_id: String, qty: Option<u32>, price: Option<Price>) -> Self {
+        Self { cl_ord_id, qty, price, ..Self::default() }
+    }
+
+    pub fn cl_ord_id(&self) -> &str {
+        &self.cl_ord_id
+    }
+
+    pub fn symbol(&self) -> Option<&str> {
+        self.symbol.as_deref()
+    }
+
+    pub fn set_symbol(&mut self, symbol: Option<String>) {
+        self.symbol = symbol;
+    }
+
+    pub fn side(&self) -> Option<Side> {
+        self.side
+    }
+
+    pub fn set_side(&mut self, side: Option<Side>) {
+        self.side = side;
+    }
+
+    pub fn qty(&self) -> Option<u32> {
+        self.qty
+    }
+
+    pub fn price(&self) -> Option<Price> {
+        self.price
+    }
+}
+
+///The reason a cancel or a replace request is rejected
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CancelRejectReason {
+    TooLateToCancel,
+    UnknownOrder,
+    InvalidRequest,
+}
+
+impl CancelRejectReason {
+    ///Returns the FIX specific character for the reason as per FIX specification
+    pub fn char_value(&self) -> char {
+        match self {
+            CancelRejectReason::TooLateToCancel => '0',
+            CancelRejectReason::UnknownOrder => '1',
+            CancelRejectReason::InvalidRequest => '2',
+        }
+    }
+
+    pub fn string_value(&self) -> &str {
+        match self {
+            CancelRejectReason::TooLateToCancel => "Too late to cancel",
+            CancelRejectReason::UnknownOrder => "Unknown order",
+            CancelRejectReason::InvalidRequest => "Invalid request",
+        }
+    }
+}
+
+///Sent back instead of an execution report when a cancel or a replace request cannot be applied to the book
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CancelReject {
+    cl_ord_id: String,
+    reason: CancelRejectReason,
+    #[serde(default)]
+    text: String,
+}
+
+impl CancelReject {
+    pub fn new(cl_ord_id: String, reason: CancelRejectReason) -> Self {
+        Self::with_text(cl_ord_id, reason, reason.string_value().to_string())
+    }
+
+    pub fn with_text(cl_ord_id: String, reason: CancelRejectReason, text: String) -> Self {
+        Self { cl_ord_id, reason, text }
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn cl_ord_id(&self) -> &str {
+        &self.cl_ord_id
+    }
+
+    pub fn reason(&self) -> CancelRejectReason {
+        self.reason
+    }
+}
+
+impl Display for CancelReject {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "request for {} rejected: {}", self.cl_ord_id, self.text)
+    }
+}
@@ -0,0 +1,24 @@
+use chrono::NaiveDate;
+
+use crate::matchers::auction::AuctionAllocation;
+use crate::model::cancel::{CancelRequest, ReplaceRequest};
+use crate::model::domain::{OrderBook, OrderSingle};
+use crate::model::session::{SessionSchedule, TradingPhase};
+
+///A request of the user sent to the matcher thread, which owns the order book and applies the
+/// commands in the order they are received
+#[derive(Debug)]
+pub enum Command {
+    ///Replaces the order book and the schedule of the matcher and matches the loaded book
+    Load(OrderBook, SessionSchedule),
+    ///Submits a new order and matches it straight away
+    Submit(OrderSingle),
+    ///Cancels a resting order
+    Cancel(CancelRequest),
+    ///Replaces a resting order and matches it straight away
+    Replace(ReplaceRequest),
+    ///Moves a symbol to a trading phase, uncrossing its auction with the allocation when it leaves one
+    Phase(String, TradingPhase, AuctionAllocation),
+    ///Ends the session of the given date, expiring its Day and GTD orders
+    EndSession(NaiveDate),
+}
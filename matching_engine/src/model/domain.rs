@@ -1,49 +1,245 @@
-use std::collections::{HashMap, VecDeque};
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
 use std::fmt::{Display, Formatter};
 use std::hash::{Hash, Hasher};
+use std::str::FromStr;
 
-
+use chrono::NaiveDate;
 use colored::Colorize;
+use log::{error, info, warn};
 use prettytable::{row, Table};
 use serde::{Deserialize, Serialize};
 
-use crate::model::domain::OrderType::{Limit, Market};
+use crate::common::id_generator::{IdGenerator, IdType, SharedIdGenerator};
+use crate::model::cancel::{CancelReject, CancelRequest, ReplaceRequest};
+use crate::model::cancel::CancelRejectReason::{InvalidRequest, TooLateToCancel, UnknownOrder};
+use crate::model::domain::OrderType::{Limit, Market, Stop, StopLimit};
 use crate::model::domain::Side::{Buy, Sell};
-use crate::model::domain::Status::{Filled, New, PartialFill, PendingNew, Rejected, Replaced, UNKNOWN};
-use crate::utils::{Aggregator, generate_id, Sigma};
-
-///Order TYpe . Can be either Limit or Market
-#[derive(PartialEq, Debug, Eq, Clone, Copy, Serialize, Deserialize)]
+use crate::model::domain::Status::{Cancelled, Expired, Filled, New, PartialFill, PendingNew, Rejected, Replaced, Restated, UNKNOWN};
+use crate::model::instrument::InstrumentRegistry;
+use crate::model::price::Price;
+use crate::model::session::{PhaseChange, TradingPhase};
+use crate::model::session::TradingPhase::Halted;
+
+///Order TYpe . Can be Limit, Market, Stop or StopLimit. Stop and StopLimit orders wait until a trade
+/// reaches their stop price and then become Market and Limit orders respectively
+#[derive(PartialEq, Debug, Eq, Clone, Copy, Default, Serialize, Deserialize)]
 pub enum OrderType {
     Market,
+    #[default]
     Limit,
+    Stop,
+    StopLimit,
 }
 
 impl OrderType {
     pub fn string_value(&self) -> String {
         match self {
             Limit => "Limit".to_owned(),
-            Market => "Market".to_owned()
+            Market => "Market".to_owned(),
+            Stop => "Stop".to_owned(),
+            StopLimit => "StopLimit".to_owned(),
         }
     }
 
     pub fn from(str: &String) -> Self {
         if str == "Limit" {
             OrderType::Limit
+        } else if str == "Stop" {
+            OrderType::Stop
+        } else if str == "StopLimit" {
+            OrderType::StopLimit
         } else {
             OrderType::Market
         }
     }
 }
 
-impl Default for OrderType {
-    fn default() -> Self {
-        Limit
+///Time in force of an order. GTC orders rest until they are filled. Day orders expire when the
+/// session ends and GTD orders at the end of the session on their expire date. IOC orders are
+/// filled as far as possible and the remainder is cancelled, FOK orders are either filled completely
+/// or cancelled without any fill
+#[derive(PartialEq, Debug, Eq, Clone, Copy, Default, Serialize, Deserialize)]
+pub enum TimeInForce {
+    Day,
+    #[default]
+    GTC,
+    IOC,
+    FOK,
+    GTD,
+}
+
+impl TimeInForce {
+    ///Returns the FIX specific character for the time in force as per FIX specification
+    pub fn char_value(&self) -> char {
+        match self {
+            TimeInForce::Day => '0',
+            TimeInForce::GTC => '1',
+            TimeInForce::IOC => '3',
+            TimeInForce::FOK => '4',
+            TimeInForce::GTD => '6',
+        }
+    }
+
+    pub fn string_value(&self) -> String {
+        match self {
+            TimeInForce::Day => "Day".to_owned(),
+            TimeInForce::GTC => "GTC".to_owned(),
+            TimeInForce::IOC => "IOC".to_owned(),
+            TimeInForce::FOK => "FOK".to_owned(),
+            TimeInForce::GTD => "GTD".to_owned(),
+        }
+    }
+
+    ///Returns `true` for IOC and FOK, which never rest in the order book
+    pub fn is_immediate(&self) -> bool {
+        matches!(self, TimeInForce::IOC | TimeInForce::FOK)
+    }
+}
+
+impl FromStr for TimeInForce {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_uppercase().as_str() {
+            "DAY" => Ok(TimeInForce::Day),
+            "GTC" => Ok(TimeInForce::GTC),
+            "IOC" => Ok(TimeInForce::IOC),
+            "FOK" => Ok(TimeInForce::FOK),
+            "GTD" => Ok(TimeInForce::GTD),
+            _ => Err(format!("invalid time in force {}", s)),
+        }
+    }
+}
+
+///The reference price a pegged order follows. Midpoint tracks the mid of the best bid and offer,
+/// Primary the best price on the side of the order and Market the best price on the opposite side
+#[derive(PartialEq, Debug, Eq, Clone, Copy, Serialize, Deserialize)]
+pub enum PegType {
+    Midpoint,
+    Primary,
+    Market,
+}
+
+impl PegType {
+    pub fn string_value(&self) -> String {
+        match self {
+            PegType::Midpoint => "Midpoint".to_owned(),
+            PegType::Primary => "Primary".to_owned(),
+            PegType::Market => "Market".to_owned(),
+        }
+    }
+}
+
+impl FromStr for PegType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_uppercase().as_str() {
+            "MID" | "MIDPOINT" => Ok(PegType::Midpoint),
+            "PRIMARY" => Ok(PegType::Primary),
+            "MARKET" => Ok(PegType::Market),
+            _ => Err(format!("invalid peg type {}", s)),
+        }
     }
 }
 
+///What happens to a post-only order that would cross the contra side on arrival. The order is either
+/// rejected or repriced one tick away from the best contra price, so that it never takes liquidity
 #[derive(PartialEq, Debug, Eq, Clone, Copy, Serialize, Deserialize)]
+pub enum PostOnly {
+    Reject,
+    Reprice,
+}
+
+impl PostOnly {
+    pub fn string_value(&self) -> String {
+        match self {
+            PostOnly::Reject => "Reject".to_owned(),
+            PostOnly::Reprice => "Reprice".to_owned(),
+        }
+    }
+}
+
+impl FromStr for PostOnly {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_uppercase().as_str() {
+            "REJECT" => Ok(PostOnly::Reject),
+            "REPRICE" => Ok(PostOnly::Reprice),
+            _ => Err(format!("invalid post only instruction {}", s)),
+        }
+    }
+}
+
+///What happens when an incoming order would trade against a resting order of the same participant.
+/// CancelNewest cancels the incoming order, CancelOldest the resting order and CancelBoth cancels both.
+/// DecrementAndCancel takes the smaller open quantity off both orders and cancels the order left with
+/// nothing open. The mode of the incoming order applies
+#[derive(PartialEq, Debug, Eq, Clone, Copy, Default, Serialize, Deserialize)]
+pub enum SelfTradePrevention {
+    #[default]
+    CancelNewest,
+    CancelOldest,
+    CancelBoth,
+    DecrementAndCancel,
+}
+
+impl SelfTradePrevention {
+    pub fn string_value(&self) -> String {
+        match self {
+            SelfTradePrevention::CancelNewest => "CancelNewest".to_owned(),
+            SelfTradePrevention::CancelOldest => "CancelOldest".to_owned(),
+            SelfTradePrevention::CancelBoth => "CancelBoth".to_owned(),
+            SelfTradePrevention::DecrementAndCancel => "DecrementAndCancel".to_owned(),
+        }
+    }
+
+    ///Applies the mode to an incoming and a resting order of the same participant instead of trading
+    /// them and returns the execution reports. A cancelled order is left with no open quantity and a
+    /// decremented order gets a Restated report with its new open quantity
+    pub fn apply(&self, incoming: &mut OrderSingle, resting: &mut OrderSingle, ids: &SharedIdGenerator) -> Vec<Fill> {
+        let mut reports = vec![];
+        match self {
+            SelfTradePrevention::CancelNewest => reports.push(incoming.cancel(ids)),
+            SelfTradePrevention::CancelOldest => reports.push(resting.cancel(ids)),
+            SelfTradePrevention::CancelBoth => {
+                reports.push(incoming.cancel(ids));
+                reports.push(resting.cancel(ids));
+            }
+            SelfTradePrevention::DecrementAndCancel => {
+                let qty = incoming.qty().min(resting.qty());
+                for order in [incoming, resting] {
+                    if order.qty() == qty {
+                        reports.push(order.cancel(ids));
+                    } else {
+                        order.set_qty(order.qty() - qty);
+                        reports.push(Fill::restated(order, ids));
+                    }
+                }
+            }
+        }
+        reports
+    }
+}
+
+impl FromStr for SelfTradePrevention {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_uppercase().as_str() {
+            "CN" | "CANCELNEWEST" => Ok(SelfTradePrevention::CancelNewest),
+            "CO" | "CANCELOLDEST" => Ok(SelfTradePrevention::CancelOldest),
+            "CB" | "CANCELBOTH" => Ok(SelfTradePrevention::CancelBoth),
+            "DC" | "DECREMENTANDCANCEL" => Ok(SelfTradePrevention::DecrementAndCancel),
+            _ => Err(format!("invalid self trade prevention mode {}", s)),
+        }
+    }
+}
+
+#[derive(PartialEq, Debug, Eq, Clone, Copy, Default, Serialize, Deserialize)]
 pub enum Side {
+    #[default]
     Buy,
     Sell,
 }
@@ -66,26 +262,25 @@ impl Side {
     }
 }
 
-impl Default for Side {
-    fn default() -> Self {
-        Buy
-    }
-}
-
 ///Order Status and Execution Status. Can be one of New,
 ///    PendingNew,
 ///    PartialFill,
 ///    Filled,
+///     Cancelled,
 ///     Rejected,
 ///     Replaced,
+///     Restated,
 #[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub enum Status {
     New,
     PendingNew,
     PartialFill,
     Filled,
+    Cancelled,
     Rejected,
     Replaced,
+    Restated,
+    Expired,
     UNKNOWN,
 }
 
@@ -97,8 +292,11 @@ impl Status {
             PendingNew => 'A',
             PartialFill => '1',
             Filled => '2',
-            Rejected => '4',
+            Cancelled => '4',
+            Rejected => '8',
             Replaced => '5',
+            Restated => 'D',
+            Expired => 'C',
             UNKNOWN => 'U'
         }
     }
@@ -109,8 +307,11 @@ impl Status {
             PendingNew => "Pending",
             PartialFill => "PartialFill",
             Filled => "Filled",
+            Cancelled => "Cancelled",
             Rejected => "Rejected",
             Replaced => "Replaced",
+            Restated => "Restated",
+            Expired => "Expired",
             UNKNOWN => "???"
         }
     }
@@ -124,15 +325,77 @@ impl PartialEq for Status {
     }
 }
 
-///Defines an order.
+///Whether the order of a fill added liquidity to the book, as the resting order that arrived first, or
+/// removed it, as the aggressor that arrived later
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Liquidity {
+    Added,
+    Removed,
+}
+
+impl Liquidity {
+    ///Returns the FIX specific character for the liquidity indicator as per FIX specification
+    pub fn char_value(&self) -> char {
+        match self {
+            Liquidity::Added => '1',
+            Liquidity::Removed => '2',
+        }
+    }
+
+    pub fn string_value(&self) -> &str {
+        match self {
+            Liquidity::Added => "Added",
+            Liquidity::Removed => "Removed",
+        }
+    }
+}
+
+///Defines an order. The price of a market order is ignored. `qty` is the quantity still open and
+/// `cum_qty` the quantity filled so far. The price of a pegged order is derived from the book and
+/// changes whenever the best bid or offer it follows moves
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct OrderSingle {
     qty: u32,
     symbol: String,
-    price: f64,
+    #[serde(default)]
+    price: Price,
     side: Side,
     order_type: OrderType,
     cl_ord_id: String,
+    #[serde(default)]
+    time_in_force: TimeInForce,
+    #[serde(default)]
+    expire_date: Option<NaiveDate>,
+    #[serde(default)]
+    cum_qty: u32,
+    #[serde(default)]
+    display_qty: Option<u32>,
+    #[serde(default)]
+    peak_qty: u32,
+    #[serde(default)]
+    stop_price: Option<Price>,
+    #[serde(default)]
+    peg: Option<PegType>,
+    #[serde(default)]
+    peg_offset: Price,
+    #[serde(default)]
+    post_only: Option<PostOnly>,
+    #[serde(default)]
+    participant: Option<String>,
+    #[serde(default)]
+    self_trade_prevention: SelfTradePrevention,
+    #[serde(default)]
+    min_qty: Option<u32>,
+    #[serde(default)]
+    all_or_none: bool,
+    #[serde(default)]
+    improves_price: bool,
+    #[serde(default)]
+    lead_market_maker: bool,
+    #[serde(default)]
+    arrival_seq: u64,
+    #[serde(default)]
+    order_id: String,
 }
 
 ///Defines a Fill from an Execution
@@ -146,59 +409,43 @@ pub struct Fill {
     qty: u32,
     leaves_qty: u32,
     cum_qty: u32,
-    price: f64,
+    price: Price,
     side: Side,
     cl_ord_id: String,
     secondary_cl_ord_id: String,
     status: Status,
+    #[serde(default)]
+    liquidity: Option<Liquidity>,
+    #[serde(default)]
+    trade_id: Option<String>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
 pub struct OrderBookKey {
-    price: f64,
+    price: Price,
     symbol: String,
 }
 
 ///The key on which [`OrderSingle`] instances are stored in the [`OrderBook`]
 impl OrderBookKey {
-    pub fn new(price: f64, symbol: String) -> Self {
+    pub fn new(price: Price, symbol: String) -> Self {
         Self { price, symbol }
     }
 
     pub fn is_valid(&self) -> bool {
-        self.price > 0.0 && self.symbol.len() > 0
-    }
-}
-
-impl Eq for OrderBookKey {}
-
-impl PartialEq for OrderBookKey {
-    fn eq(&self, other: &Self) -> bool {
-        self.price == other.price && self.symbol == other.symbol
-    }
-}
-
-
-impl Hash for OrderBookKey {
-    fn hash<H>(&self, state: &mut H)
-        where
-            H: Hasher,
-    {
-        let float_bits: u64 = unsafe { std::mem::transmute(self.price) };
-        float_bits.hash(state);
-        self.symbol.as_bytes().hash(state);
+        self.price.is_positive() && !self.symbol.is_empty()
     }
 }
 
 impl OrderBookKey {
-    pub fn price(&self) -> f64 {
+    pub fn price(&self) -> Price {
         self.price
     }
     pub fn symbol(&self) -> &str {
         &self.symbol
     }
 
-    pub fn set_price(&mut self, price: f64) {
+    pub fn set_price(&mut self, price: Price) {
         self.price = price;
     }
     pub fn set_symbol(&mut self, symbol: String) {
@@ -208,6 +455,7 @@ impl OrderBookKey {
 
 
 impl Fill {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         symbol: String,
         order_id: String,
@@ -216,7 +464,7 @@ impl Fill {
         qty: u32,
         leaves_qty: u32,
         cum_qty: u32,
-        price: f64,
+        price: Price,
         side: Side,
         cl_ord_id: String,
         status: Status) -> Self {
@@ -233,11 +481,13 @@ impl Fill {
             cl_ord_id,
             secondary_cl_ord_id: "".to_string(),
             status,
+            liquidity: None,
+            trade_id: None,
         }
     }
 
     ///Returns a string formatted a table of all the fills in the `fills` argument
-    pub fn pretty_print(fills: &Vec<Fill>) -> String {
+    pub fn pretty_print(fills: &[Fill]) -> String {
         if fills.is_empty() {
             return "No fills".to_string();
         }
@@ -247,15 +497,15 @@ impl Fill {
 
 
         let mut table = Table::new();
-        table.add_row(row!["Symbol","Qty","Price","client_order_id","exchange_order_id","Side","Order Status"]);
+        table.add_row(row!["Symbol","Qty","Price","client_order_id","exchange_order_id","Side","Order Status","Liquidity"]);
         for fill in fills {
-            table.add_row(row![fill.symbol,fill.qty,fill.price,fill.cl_ord_id,fill.secondary_cl_ord_id,fill.side.string_value(),fill.status.string_value()]);
+            let liquidity = fill.liquidity.as_ref().map_or("", |liquidity| liquidity.string_value());
+            table.add_row(row![fill.symbol,fill.qty,fill.price,fill.cl_ord_id,fill.secondary_cl_ord_id,fill.side.string_value(),fill.status.string_value(),liquidity]);
         }
 
         table.printstd();
-        let f_str = format!("{}","Fills:");
-        let mut titled_fills = String::from(f_str);
-        titled_fills.push_str("\n");
+        let mut titled_fills = String::from("Fills:");
+        titled_fills.push('\n');
         titled_fills.push_str(table.to_string().as_str());
         titled_fills
     }
@@ -264,10 +514,14 @@ impl Fill {
         self.qty = qty;
     }
 
-    pub fn price(&self) -> f64 {
+    pub fn price(&self) -> Price {
         self.price
     }
 
+    pub fn set_price(&mut self, price: Price) {
+        self.price = price;
+    }
+
     pub fn set_cum_qty(&mut self, cum_qty: u32) {
         self.cum_qty = cum_qty;
     }
@@ -314,24 +568,48 @@ impl Fill {
         self.order_id = order_id;
     }
 
+    pub fn order_id(&self) -> &str {
+        &self.order_id
+    }
+
+    pub fn execution_id(&self) -> &str {
+        &self.execution_id
+    }
+
+    pub fn set_execution_id(&mut self, execution_id: String) {
+        self.execution_id = execution_id;
+    }
+
+    ///Returns the id of the trade shared by the fills of both sides of a match, `None` for reports that
+    /// are not trades
+    pub fn trade_id(&self) -> Option<&str> {
+        self.trade_id.as_deref()
+    }
+
+    pub fn set_trade_id(&mut self, trade_id: Option<String>) {
+        self.trade_id = trade_id;
+    }
+
     pub fn status(&self) -> &Status {
         &self.status
     }
 
     pub fn side(&self) -> Side {
-        self.side.clone()
+        self.side
     }
     pub fn set_side(&mut self, side: Side) {
         self.side = side
     }
 
-    pub fn from(order: &OrderSingle) -> Self {
+    ///Creates an execution report of the order with a new execution id drawn from the generator. The report
+    /// carries the order id the order was accepted with
+    pub fn from(order: &OrderSingle, ids: &SharedIdGenerator) -> Self {
         Self {
             qty: order.qty(),
             cum_qty: 0,
             leaves_qty: order.qty(),
-            order_id: generate_id(),
-            execution_id: generate_id(),
+            order_id: order.order_id().to_string(),
+            execution_id: ids.next_id(IdType::Execution),
             price: order.price(),
             symbol: order.symbol().clone(),
             status: Status::Filled,
@@ -339,16 +617,77 @@ impl Fill {
             cl_ord_id: order.cl_ord_id().clone(),
             secondary_cl_ord_id: "".to_string(),
             exec_type: Status::New,
-
+            liquidity: None,
+            trade_id: None,
         }
     }
+
+    ///Creates the execution report cancelling whatever is left of the order once `cum_qty` has been filled
+    pub fn cancelled(order: &OrderSingle, cum_qty: u32, ids: &SharedIdGenerator) -> Self {
+        Fill::done(order, cum_qty, Cancelled, ids)
+    }
+
+    ///Creates the execution report for a resting order whose price or quantity has been replaced
+    pub fn replaced(order: &OrderSingle, ids: &SharedIdGenerator) -> Self {
+        Fill::amended(order, Replaced, ids)
+    }
+
+    ///Creates the execution report for an order that the engine repriced, such as a post-only order
+    /// moved away from the contra side
+    pub fn restated(order: &OrderSingle, ids: &SharedIdGenerator) -> Self {
+        Fill::amended(order, Restated, ids)
+    }
+
+    ///Creates the execution report for an order that the engine refused to accept into the book
+    pub fn rejected(order: &OrderSingle, ids: &SharedIdGenerator) -> Self {
+        Fill::done(order, order.cum_qty(), Rejected, ids)
+    }
+
+    fn amended(order: &OrderSingle, exec_type: Status, ids: &SharedIdGenerator) -> Self {
+        let mut fill = Fill::from(order, ids);
+        fill.set_qty(0);
+        fill.set_cum_qty(order.cum_qty());
+        fill.set_leaves_qty(order.qty());
+        fill.set_status(if order.cum_qty() > 0 { PartialFill } else { New });
+        fill.exec_type = exec_type;
+        fill
+    }
+
+    ///Creates the execution report for a resting order that expired at the end of the session
+    pub fn expired(order: &OrderSingle, ids: &SharedIdGenerator) -> Self {
+        Fill::done(order, order.cum_qty(), Expired, ids)
+    }
+
+    fn done(order: &OrderSingle, cum_qty: u32, status: Status, ids: &SharedIdGenerator) -> Self {
+        let mut fill = Fill::from(order, ids);
+        fill.set_qty(0);
+        fill.set_cum_qty(cum_qty);
+        fill.set_leaves_qty(0);
+        fill.set_status(status);
+        fill.exec_type = status;
+        fill
+    }
+
+    pub fn exec_type(&self) -> &Status {
+        &self.exec_type
+    }
+
+    ///Returns whether the order added or removed liquidity, `None` for execution reports other than trades
+    /// and for auction trades, which have no aggressor
+    pub fn liquidity(&self) -> Option<Liquidity> {
+        self.liquidity
+    }
+
+    pub fn set_liquidity(&mut self, liquidity: Option<Liquidity>) {
+        self.liquidity = liquidity;
+    }
 }
 
 
 impl OrderSingle {
     pub fn new(qty: u32,
                symbol: String,
-               price: f64,
+               price: Price,
                side: Side,
                order_type: OrderType,
                cl_ord_id: String) -> Self {
@@ -359,35 +698,220 @@ impl OrderSingle {
             side,
             order_type,
             cl_ord_id,
+            time_in_force: TimeInForce::default(),
+            expire_date: None,
+            cum_qty: 0,
+            display_qty: None,
+            peak_qty: 0,
+            stop_price: None,
+            peg: None,
+            peg_offset: Price::ZERO,
+            post_only: None,
+            participant: None,
+            self_trade_prevention: SelfTradePrevention::default(),
+            min_qty: None,
+            all_or_none: false,
+            improves_price: false,
+            lead_market_maker: false,
+            arrival_seq: 0,
+            order_id: String::new(),
         }
     }
 
-    /// Returns `true` if the order is valid. `false` otherwise. As you can see Market orders are
-    /// not supported as af now
+    /// Returns `true` if the order is valid. `false` otherwise. Limit and stop limit orders need a
+    /// positive price, market and stop orders are valid without one. Stop and stop limit orders need a
+    /// positive stop price. Pegged orders get their price from the book and must be resting limit orders.
+    /// Post-only orders must be resting limit orders that are not pegged. Minimum quantity and all-or-none
+    /// orders cannot be iceberg orders
     ///
     /// # Example
-    /// self.symbol.trim().len() > 0 &&
-    ///             self.price() > 0.0 &&
-    ///             self.qty() > 0 &&
-    ///             (self.side.string_value() == "Buy" || self.side.string_value() == "Sell") &&
-    ///            self.order_type.string_value() == "Limit
+    /// ```rust
+    /// use matching_engine::common::utils::create_order_from_string;
+    /// assert!(create_order_from_string("id1 IBM 100 601 Buy".to_string()).is_valid());
+    /// assert!(!create_order_from_string("id2 IBM 0 601 Buy".to_string()).is_valid());
+    /// ```
     pub fn is_valid(&self) -> bool {
-        self.symbol.trim().len() > 0 &&
-            self.price() > 0.0 &&
+        !self.symbol.trim().is_empty() &&
+            (!self.has_limit_price() || self.is_pegged() || self.price().is_positive()) &&
+            (!self.is_stop() || self.stop_price.is_some_and(|stop| stop.is_positive())) &&
             self.qty() > 0 &&
             (self.side.string_value() == "Buy" || self.side.string_value() == "Sell") &&
-            self.order_type.string_value() == "Limit" &&
-            self.cl_ord_id.trim().len() > 0
+            !self.cl_ord_id.trim().is_empty() &&
+            (self.time_in_force != TimeInForce::GTD || self.expire_date.is_some()) &&
+            self.display_qty != Some(0) &&
+            (!self.is_pegged() || (self.order_type == Limit && !self.time_in_force.is_immediate())) &&
+            (self.post_only.is_none() || (self.order_type == Limit && !self.time_in_force.is_immediate() && !self.is_pegged())) &&
+            self.min_qty != Some(0) &&
+            (self.display_qty.is_none() || (self.min_qty.is_none() && !self.all_or_none))
+    }
+
+    pub fn is_market(&self) -> bool {
+        self.order_type == Market
+    }
+
+    ///Returns `true` for the order types that carry a limit price, Limit and StopLimit
+    pub fn has_limit_price(&self) -> bool {
+        matches!(self.order_type, Limit | StopLimit)
+    }
+
+    ///Returns `true` for a stop or stop limit order that has not been triggered yet
+    pub fn is_stop(&self) -> bool {
+        matches!(self.order_type, Stop | StopLimit)
+    }
+
+    ///Returns `true` if a trade at `price` triggers the stop order. A buy stop is triggered by a trade
+    /// at or above its stop price and a sell stop by a trade at or below it
+    pub fn is_triggered_by(&self, price: Price) -> bool {
+        match (self.is_stop(), self.stop_price) {
+            (true, Some(stop_price)) => match self.side {
+                Buy => price >= stop_price,
+                Sell => price <= stop_price,
+            },
+            _ => false,
+        }
+    }
+
+    ///Turns a triggered stop order into a market order and a stop limit order into a limit order
+    pub fn trigger(&mut self) {
+        self.order_type = match self.order_type {
+            Stop => Market,
+            StopLimit => Limit,
+            order_type => order_type,
+        };
+    }
+
+    ///Returns `true` if the order must be matched on arrival and never rests in the book, which is the
+    /// case for market orders and for IOC and FOK orders
+    pub fn is_immediate(&self) -> bool {
+        self.is_market() || self.time_in_force.is_immediate()
+    }
+
+    ///Returns `true` if a resting order expires at the end of the session held on `session_date`
+    pub fn expires_on(&self, session_date: NaiveDate) -> bool {
+        match self.time_in_force {
+            TimeInForce::Day => true,
+            TimeInForce::GTD => self.expire_date.map_or(true, |date| date <= session_date),
+            _ => false,
+        }
+    }
+
+    ///Takes `qty` off the open quantity of the order and adds it to the filled quantity. The visible
+    /// peak of an iceberg order is consumed as well
+    pub fn fill(&mut self, qty: u32) {
+        self.qty -= qty;
+        self.cum_qty += qty;
+        self.peak_qty = self.peak_qty.saturating_sub(qty);
+    }
+
+    ///Returns `true` for an iceberg order, which only shows its display quantity in the book
+    pub fn is_iceberg(&self) -> bool {
+        self.display_qty.is_some()
+    }
+
+    ///Returns the quantity shown in the book and available to a contra order at a time. This is the
+    /// remaining peak of an iceberg order and the open quantity of any other order
+    pub fn visible_qty(&self) -> u32 {
+        if self.is_iceberg() { self.peak_qty } else { self.qty }
     }
 
+    ///Returns `true` if the visible peak of an iceberg order has been consumed while hidden quantity
+    /// is left. The refreshed peak loses time priority
+    pub fn needs_refresh(&self) -> bool {
+        self.is_iceberg() && self.peak_qty == 0 && self.qty > 0
+    }
+
+    ///Shows a new peak of the display quantity, or whatever is left of the order if that is less
+    pub fn refresh_peak(&mut self) {
+        if let Some(display_qty) = self.display_qty {
+            self.peak_qty = display_qty.min(self.qty);
+        }
+    }
+
+    ///Cancels whatever is open of the order and returns the Cancelled execution report
+    pub fn cancel(&mut self, ids: &SharedIdGenerator) -> Fill {
+        let report = Fill::cancelled(self, self.cum_qty, ids);
+        self.set_qty(0);
+        report
+    }
+
+    ///Returns the smallest quantity the order accepts in a single execution. This is the whole open
+    /// quantity of an all-or-none order and the minimum quantity of a min qty order, or what is left of
+    /// the order if that is less. Any other order accepts any quantity
+    pub fn min_fill_qty(&self) -> u32 {
+        if self.all_or_none {
+            self.qty
+        } else {
+            self.min_qty.unwrap_or(0).min(self.qty)
+        }
+    }
+
+    ///Returns `true` if an execution of `qty` meets the minimum quantity or all-or-none condition of the order
+    pub fn accepts_fill(&self, qty: u32) -> bool {
+        qty >= self.min_fill_qty()
+    }
+
+    ///Returns `true` if the order trades against the book on arrival. Stop orders wait to be triggered,
+    /// pegged orders to be priced and post-only orders never take liquidity
+    pub fn trades_on_arrival(&self) -> bool {
+        !self.is_stop() && !self.is_pegged() && self.post_only.is_none()
+    }
+
+    ///Returns `true` if both orders belong to the same participant, in which case they must not trade
+    /// with each other
+    pub fn is_self_trade_with(&self, other: &OrderSingle) -> bool {
+        self.participant.is_some() && self.participant == other.participant
+    }
+
+    ///Returns `true` for an order whose price follows the best bid and offer of its symbol
+    pub fn is_pegged(&self) -> bool {
+        self.peg.is_some()
+    }
+
+    ///Returns the price of a pegged order for the given best bid and offer: the reference price of its
+    /// peg type plus the offset, rounded to the tick away from the contra side. `None` is returned if
+    /// the reference price is missing or out of range or the result is not a positive price
+    pub fn peg_price(&self, bid: Option<Price>, ask: Option<Price>, tick_size: Price) -> Option<Price> {
+        let (same, opposite) = match self.side {
+            Buy => (bid, ask),
+            Sell => (ask, bid),
+        };
+        let reference = match self.peg? {
+            PegType::Midpoint => Price::midpoint(bid?, ask?).map_err(|e| warn!("{}", e)).ok()?,
+            PegType::Primary => same?,
+            PegType::Market => opposite?,
+        };
+        let price = reference + self.peg_offset;
+        let price = match self.side {
+            Buy => price.floor_to(tick_size),
+            Sell => price.ceil_to(tick_size),
+        };
+        Some(price).filter(|price| price.is_positive())
+    }
+
+    ///Returns the key of the price level the order rests at. For a pegged order this is the price it
+    /// was last repriced to
     pub fn get_order_book_key(&self) -> OrderBookKey {
         OrderBookKey::new(self.price, self.symbol.clone())
     }
 
+    ///Returns `true` if the order can trade against a contra order resting at `price`.
+    /// A buy order crosses any price at or below its limit and a sell order any price at or above it.
+    /// A market order crosses any price
+    pub fn crosses(&self, price: Price) -> bool {
+        if self.is_market() {
+            return true;
+        }
+        match self.side {
+            Buy => price <= self.price,
+            Sell => price >= self.price,
+        }
+    }
+
     pub fn set_qty(&mut self, qty: u32) {
         self.qty = qty;
+        self.peak_qty = self.peak_qty.min(qty);
     }
-    pub fn set_price(&mut self, price: f64) {
+    pub fn set_price(&mut self, price: Price) {
         self.price = price;
     }
 
@@ -403,7 +927,7 @@ impl OrderSingle {
     }
 
     pub fn qty(&self) -> u32 {
-        self.qty.clone()
+        self.qty
     }
 
 
@@ -412,20 +936,177 @@ impl OrderSingle {
     }
 
     pub fn side(&self) -> Side {
-        self.side.clone()
+        self.side
     }
 
     pub fn order_type(&self) -> OrderType {
-        self.order_type.clone()
+        self.order_type
     }
 
     pub fn cl_ord_id(&self) -> &String {
         &self.cl_ord_id
     }
 
-    pub fn price(&self) -> f64 {
+    pub fn price(&self) -> Price {
         self.price
     }
+
+    pub fn time_in_force(&self) -> TimeInForce {
+        self.time_in_force
+    }
+
+    pub fn set_time_in_force(&mut self, time_in_force: TimeInForce) {
+        self.time_in_force = time_in_force;
+    }
+
+    pub fn expire_date(&self) -> Option<NaiveDate> {
+        self.expire_date
+    }
+
+    pub fn set_expire_date(&mut self, expire_date: Option<NaiveDate>) {
+        self.expire_date = expire_date;
+    }
+
+    pub fn cum_qty(&self) -> u32 {
+        self.cum_qty
+    }
+
+    pub fn set_order_type(&mut self, order_type: OrderType) {
+        self.order_type = order_type;
+    }
+
+    pub fn stop_price(&self) -> Option<Price> {
+        self.stop_price
+    }
+
+    pub fn set_stop_price(&mut self, stop_price: Option<Price>) {
+        self.stop_price = stop_price;
+    }
+
+    pub fn display_qty(&self) -> Option<u32> {
+        self.display_qty
+    }
+
+    pub fn peg(&self) -> Option<PegType> {
+        self.peg
+    }
+
+    pub fn set_peg(&mut self, peg: Option<PegType>) {
+        self.peg = peg;
+    }
+
+    pub fn peg_offset(&self) -> Price {
+        self.peg_offset
+    }
+
+    pub fn set_peg_offset(&mut self, peg_offset: Price) {
+        self.peg_offset = peg_offset;
+    }
+
+    pub fn post_only(&self) -> Option<PostOnly> {
+        self.post_only
+    }
+
+    pub fn set_post_only(&mut self, post_only: Option<PostOnly>) {
+        self.post_only = post_only;
+    }
+
+    pub fn participant(&self) -> Option<&str> {
+        self.participant.as_deref()
+    }
+
+    pub fn set_participant(&mut self, participant: Option<String>) {
+        self.participant = participant;
+    }
+
+    pub fn self_trade_prevention(&self) -> SelfTradePrevention {
+        self.self_trade_prevention
+    }
+
+    pub fn set_self_trade_prevention(&mut self, self_trade_prevention: SelfTradePrevention) {
+        self.self_trade_prevention = self_trade_prevention;
+    }
+
+    pub fn min_qty(&self) -> Option<u32> {
+        self.min_qty
+    }
+
+    pub fn set_min_qty(&mut self, min_qty: Option<u32>) {
+        self.min_qty = min_qty;
+    }
+
+    pub fn all_or_none(&self) -> bool {
+        self.all_or_none
+    }
+
+    pub fn set_all_or_none(&mut self, all_or_none: bool) {
+        self.all_or_none = all_or_none;
+    }
+
+    ///Returns `true` if the order set a new best price on its side when it was added to the book
+    pub fn improves_price(&self) -> bool {
+        self.improves_price
+    }
+
+    pub fn set_improves_price(&mut self, improves_price: bool) {
+        self.improves_price = improves_price;
+    }
+
+    ///Returns `true` for an order of a lead market maker, which is guaranteed a share of the incoming
+    /// orders at its price level by the matchers that have a lead market maker allocation
+    pub fn is_lead_market_maker(&self) -> bool {
+        self.lead_market_maker
+    }
+
+    pub fn set_lead_market_maker(&mut self, lead_market_maker: bool) {
+        self.lead_market_maker = lead_market_maker;
+    }
+
+    ///Returns the sequence number the order book stamped the order with when it arrived, 0 until then
+    pub fn arrival_seq(&self) -> u64 {
+        self.arrival_seq
+    }
+
+    pub fn set_arrival_seq(&mut self, arrival_seq: u64) {
+        self.arrival_seq = arrival_seq;
+    }
+
+    ///Returns the id the order book assigned to the order when it accepted it, empty until then. Every
+    /// execution report of the order carries it
+    pub fn order_id(&self) -> &str {
+        &self.order_id
+    }
+
+    pub fn set_order_id(&mut self, order_id: String) {
+        self.order_id = order_id;
+    }
+
+    ///Returns `true` if the order is the aggressor when it trades with `contra`, which is the case if it
+    /// arrived later. An order without a limit price, such as a market order, is always the aggressor
+    pub fn is_aggressor_against(&self, contra: &OrderSingle) -> bool {
+        if !self.has_limit_price() || !contra.has_limit_price() {
+            return !self.has_limit_price();
+        }
+        self.arrival_seq > contra.arrival_seq
+    }
+
+    ///Returns whether the order adds or removes liquidity when it trades with `contra`
+    pub fn liquidity_against(&self, contra: &OrderSingle) -> Liquidity {
+        if self.is_aggressor_against(contra) { Liquidity::Removed } else { Liquidity::Added }
+    }
+
+    ///Returns the price the order trades at with `contra`, the price of whichever of them was resting
+    /// in the book first
+    pub fn trade_price_against(&self, contra: &OrderSingle) -> Price {
+        if self.is_aggressor_against(contra) { contra.price() } else { self.price() }
+    }
+
+    ///Makes the order an iceberg showing `display_qty` at a time, or a plain order for `None`
+    pub fn set_display_qty(&mut self, display_qty: Option<u32>) {
+        self.display_qty = display_qty;
+        self.peak_qty = 0;
+        self.refresh_peak();
+    }
 }
 
 impl Display for OrderSingle {
@@ -450,41 +1131,332 @@ impl Hash for OrderSingle {
     }
 }
 
+///The orders resting on one side of the [`OrderBook`] for a single symbol, kept sorted by price level.
+/// Each level holds its orders in time priority
 #[derive(Clone, Debug, Serialize, Deserialize)]
-pub struct OrderBook {
-    buy_orders: HashMap<OrderBookKey, VecDeque<OrderSingle>>,
-    sell_orders: HashMap<OrderBookKey, VecDeque<OrderSingle>>,
+pub struct PriceLevels {
+    side: Side,
+    levels: BTreeMap<OrderBookKey, VecDeque<OrderSingle>>,
 }
 
-impl Default for OrderBook {
-    fn default() -> Self {
-        Self {
-            buy_orders: HashMap::new(),
-            sell_orders: HashMap::new(),
+impl PriceLevels {
+    pub fn new(side: Side) -> Self {
+        Self { side, levels: BTreeMap::new() }
+    }
+
+    pub fn side(&self) -> Side {
+        self.side
+    }
+
+    ///Returns the number of price levels
+    pub fn len(&self) -> usize {
+        self.levels.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.levels.is_empty()
+    }
+
+    ///Returns the key of the best price level, the highest bid or the lowest offer
+    pub fn best_key(&self) -> Option<&OrderBookKey> {
+        match self.side {
+            Buy => self.levels.keys().next_back(),
+            Sell => self.levels.keys().next(),
+        }
+    }
+
+    ///Returns the best price on this side, if any
+    pub fn best_price(&self) -> Option<Price> {
+        self.best_key().map(|key| key.price())
+    }
+
+    ///Returns the best price on this side among the orders that are not pegged, which is the price
+    /// pegged orders follow
+    pub fn best_unpegged_price(&self) -> Option<Price> {
+        self.iter()
+            .find(|(_, orders)| orders.iter().any(|o| !o.is_pegged()))
+            .map(|(key, _)| key.price())
+    }
+
+    ///Iterates over the price levels best price first
+    pub fn iter(&self) -> Box<dyn Iterator<Item=(&OrderBookKey, &VecDeque<OrderSingle>)> + '_> {
+        match self.side {
+            Buy => Box::new(self.levels.iter().rev()),
+            Sell => Box::new(self.levels.iter()),
+        }
+    }
+
+    pub fn get(&self, key: &OrderBookKey) -> Option<&VecDeque<OrderSingle>> {
+        self.levels.get(key)
+    }
+
+    pub fn get_mut(&mut self, key: &OrderBookKey) -> Option<&mut VecDeque<OrderSingle>> {
+        self.levels.get_mut(key)
+    }
+
+    pub fn contains_key(&self, key: &OrderBookKey) -> bool {
+        self.levels.contains_key(key)
+    }
+
+    ///Adds the order to the back of the queue at its price level
+    pub fn push_back(&mut self, order: OrderSingle) {
+        self.levels.entry(order.get_order_book_key()).or_default().push_back(order);
+    }
+
+    ///Returns the order with the given `cl_ord_id`, if it rests at any level
+    pub fn find(&self, cl_ord_id: &str) -> Option<&OrderSingle> {
+        self.levels.values().flat_map(|orders| orders.iter()).find(|o| o.cl_ord_id() == cl_ord_id)
+    }
+
+    pub fn find_mut(&mut self, cl_ord_id: &str) -> Option<&mut OrderSingle> {
+        self.levels.values_mut().flat_map(|orders| orders.iter_mut()).find(|o| o.cl_ord_id() == cl_ord_id)
+    }
+
+    ///Returns the quantity resting at the price levels that the order crosses
+    pub fn crossing_qty(&self, order: &OrderSingle) -> u32 {
+        self.iter()
+            .take_while(|(key, _)| order.crosses(key.price()))
+            .map(|(_, orders)| orders.iter().map(|o| o.qty()).sum::<u32>())
+            .sum()
+    }
+
+    ///Returns the quantity the order can fill in one go against the price levels it crosses, sweeping them
+    /// in price and time priority. Resting orders of the same participant and resting orders whose
+    /// minimum quantity or all-or-none condition would not be met are left out
+    pub fn executable_qty(&self, order: &OrderSingle) -> u32 {
+        self.executable_qty_within(order, None)
+    }
+
+    ///Returns the quantity the order can fill in one go like [`PriceLevels::executable_qty`], leaving out
+    /// the price levels outside the given lowest and highest price
+    pub fn executable_qty_within(&self, order: &OrderSingle, limits: Option<(Price, Price)>) -> u32 {
+        let mut open = order.qty();
+        'levels: for (_, orders) in self.iter()
+            .take_while(|(key, _)| order.crosses(key.price()))
+            .filter(|(key, _)| limits.map_or(true, |(low, high)| low <= key.price() && key.price() <= high)) {
+            for resting in orders {
+                if open == 0 {
+                    break 'levels;
+                }
+                let qty = open.min(resting.qty());
+                if !order.is_self_trade_with(resting) && resting.accepts_fill(qty) {
+                    open -= qty;
+                }
+            }
         }
+        order.qty() - open
+    }
+
+    ///Removes the orders for which the predicate holds from every level, in price and time priority,
+    /// and drops the levels left empty
+    pub fn remove_where<F: Fn(&OrderSingle) -> bool>(&mut self, predicate: F) -> Vec<OrderSingle> {
+        let mut removed = vec![];
+        let keys: Vec<OrderBookKey> = self.iter().map(|(key, _)| key.clone()).collect();
+        for key in keys {
+            let orders = self.levels.get_mut(&key).unwrap();
+            let (matching, kept): (VecDeque<OrderSingle>, VecDeque<OrderSingle>) = orders.drain(..).partition(|o| predicate(o));
+            *orders = kept;
+            removed.extend(matching);
+            self.remove_if_empty(&key);
+        }
+        removed
+    }
+
+    ///Removes the level if no orders are left at it
+    pub fn remove_if_empty(&mut self, key: &OrderBookKey) {
+        if self.levels.get(key).is_some_and(|orders| orders.is_empty()) {
+            self.levels.remove(key);
+        }
+    }
+
+    ///Removes and returns the price levels outside the given lowest and highest price
+    pub fn split_outside(&mut self, (low, high): (Price, Price)) -> PriceLevels {
+        let (inside, outside) = std::mem::take(&mut self.levels).into_iter()
+            .partition(|(key, _)| low <= key.price() && key.price() <= high);
+        self.levels = inside;
+        PriceLevels { side: self.side, levels: outside }
+    }
+
+    ///Moves the price levels of `other`, which must not overlap with these, back into these levels
+    pub fn append(&mut self, mut other: PriceLevels) {
+        self.levels.append(&mut other.levels);
+    }
+}
+
+///The stop and stop limit orders of a symbol waiting to be triggered, kept per side by stop price and
+/// arrival sequence number so that the trades of the symbol only visit the stops they reach
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct StopOrders {
+    buy: BTreeMap<(Price, u64), OrderSingle>,
+    sell: BTreeMap<(Price, u64), OrderSingle>,
+}
+
+impl StopOrders {
+    pub fn len(&self) -> usize {
+        self.buy.len() + self.sell.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.buy.is_empty() && self.sell.is_empty()
+    }
+
+    ///Adds the stop order under its stop price and arrival sequence number
+    pub fn insert(&mut self, order: OrderSingle) {
+        let key = (order.stop_price().unwrap_or_default(), order.arrival_seq());
+        match order.side() {
+            Buy => self.buy.insert(key, order),
+            Sell => self.sell.insert(key, order),
+        };
+    }
+
+    ///Removes and returns the stops triggered by trades between the lowest and the highest price, the buy
+    /// stops at or below the highest price and the sell stops at or above the lowest price
+    pub fn take_triggered(&mut self, low: Price, high: Price) -> Vec<OrderSingle> {
+        let waiting = self.buy.split_off(&(high, u64::MAX));
+        let mut triggered: Vec<OrderSingle> = std::mem::replace(&mut self.buy, waiting).into_values().collect();
+        triggered.extend(self.sell.split_off(&(low, 0)).into_values());
+        triggered
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item=&OrderSingle> {
+        self.buy.values().chain(self.sell.values())
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item=&mut OrderSingle> {
+        self.buy.values_mut().chain(self.sell.values_mut())
+    }
+
+    ///Removes the orders for which the predicate holds
+    pub fn remove_where<F: Fn(&OrderSingle) -> bool>(&mut self, predicate: F) -> Vec<OrderSingle> {
+        let mut removed = vec![];
+        for stops in [&mut self.buy, &mut self.sell] {
+            let (matching, kept): (BTreeMap<_, _>, BTreeMap<_, _>) = std::mem::take(stops).into_iter().partition(|(_, o)| predicate(o));
+            *stops = kept;
+            removed.extend(matching.into_values());
+        }
+        removed
     }
 }
 
+///Holds the buy and sell orders of every symbol. Orders are kept per symbol and per side in
+/// [`PriceLevels`] so that the best price is available without scanning the book. New orders are
+/// checked against the [`InstrumentRegistry`] of the book before they are accepted
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct OrderBook {
+    buy_orders: BTreeMap<String, PriceLevels>,
+    sell_orders: BTreeMap<String, PriceLevels>,
+    immediate_orders: VecDeque<OrderSingle>,
+    stop_orders: BTreeMap<String, StopOrders>,
+    #[serde(default)]
+    pegged_orders: VecDeque<OrderSingle>,
+    #[serde(default)]
+    reports: Vec<Fill>,
+    instruments: InstrumentRegistry,
+    done_orders: BTreeSet<String>,
+    #[serde(default)]
+    phases: BTreeMap<String, TradingPhase>,
+    #[serde(default)]
+    status_events: Vec<PhaseChange>,
+    #[serde(default)]
+    reference_prices: BTreeMap<String, Price>,
+    #[serde(default)]
+    arrival_seq: u64,
+    #[serde(default)]
+    peg_references: BTreeMap<String, (Option<Price>, Option<Price>)>,
+    #[serde(skip)]
+    id_generator: SharedIdGenerator,
+}
+
 impl OrderBook {
-    pub fn new(buy_orders: HashMap<OrderBookKey, VecDeque<OrderSingle>>,
-               sell_orders: HashMap<OrderBookKey, VecDeque<OrderSingle>>) -> Self {
+    ///Creates an empty order book that only accepts orders valid for the given instruments
+    pub fn with_instruments(instruments: InstrumentRegistry) -> Self {
         Self {
-            buy_orders,
-            sell_orders,
+            instruments,
+            ..Self::default()
+        }
+    }
 
+    pub fn instruments(&self) -> &InstrumentRegistry {
+        &self.instruments
+    }
+
+    ///Returns the generator the book and its matchers draw the order, execution and trade ids from
+    pub fn id_generator(&self) -> &SharedIdGenerator {
+        &self.id_generator
+    }
+
+    ///Replaces the generator the book and its matchers draw their ids from
+    pub fn set_id_generator(&mut self, generator: Box<dyn IdGenerator + Send>) {
+        self.id_generator = SharedIdGenerator::new(generator);
+    }
+
+    ///Returns an error describing why the order cannot be accepted into the book
+    pub fn validate_order(&self, order: &OrderSingle) -> Result<(), String> {
+        if !order.is_valid() {
+            return Err(format!("invalid order {}", order));
+        }
+        let phase = self.phase(order.symbol());
+        if !phase.accepts_orders() {
+            return Err(format!("{} does not accept orders while {}", order.symbol(), phase));
+        }
+        self.instruments.validate(order)?;
+        match self.price_band_limits(order.symbol()) {
+            Some((low, high)) if order.has_limit_price() && !order.is_pegged() && (order.price() < low || order.price() > high) =>
+                Err(format!("price {} is outside the price band {} - {} for {}", order.price(), low, high, order.symbol())),
+            _ => Ok(()),
         }
     }
 
+    ///Returns the lowest and the highest price a limit order of the symbol may have, the price band of its
+    /// instrument around its reference price. There are no limits without a band or a reference price
+    pub fn price_band_limits(&self, symbol: &str) -> Option<(Price, Price)> {
+        let band = self.instruments.get(symbol)?.price_band()?;
+        Some(band.limits(self.reference_price(symbol)?))
+    }
 
-    pub fn update_self(&mut self, order_book: &mut Self) {
-        self.buy_orders = order_book.buy_orders.clone();
-        self.sell_orders = order_book.sell_orders.clone();
+    ///Returns the lowest and the highest price the symbol may trade at before it is halted, the dynamic band
+    /// of its instrument around its reference price. There are no limits without a band or a reference price
+    pub fn dynamic_band_limits(&self, symbol: &str) -> Option<(Price, Price)> {
+        let band = self.instruments.get(symbol)?.dynamic_band()?;
+        Some(band.limits(self.reference_price(symbol)?))
     }
 
-    pub fn update_order_book(&mut self, orders: HashMap<OrderBookKey, VecDeque<OrderSingle>>, side: Side) {
-        match side {
-            Buy => self.buy_orders = orders,
-            Sell => self.sell_orders = orders,
+    ///Runs `sweep` on the contra side of an order of the symbol, leaving out the price levels outside the
+    /// dynamic band of the symbol, so that no matcher trades outside it. Returns what `sweep` returns
+    pub fn sweep_within_band<R>(&mut self, symbol: &str, side: Side, sweep: impl FnOnce(&mut PriceLevels) -> R) -> R {
+        let limits = self.dynamic_band_limits(symbol);
+        let (buy, sell) = self.sides_mut(symbol);
+        let contra = if side == Buy { sell } else { buy };
+        let outside = limits.map(|limits| contra.split_outside(limits));
+        let result = sweep(contra);
+        if let Some(outside) = outside {
+            contra.append(outside);
+        }
+        result
+    }
+
+    ///Returns `true` if the order would have to trade outside the dynamic band of its symbol: an FOK order
+    /// that can be filled, but not within the band, or any other order still crossing the best contra price
+    /// when that price is outside the band
+    pub fn breaches_band(&self, order: &OrderSingle) -> bool {
+        let limits = self.dynamic_band_limits(order.symbol());
+        let (Some((low, high)), Some(contra)) = (limits, self.levels(order.symbol(), order.matching_side())) else {
+            return false;
+        };
+        if order.time_in_force() == TimeInForce::FOK {
+            return contra.executable_qty(order) >= order.qty() && contra.executable_qty_within(order, limits) < order.qty();
+        }
+        order.qty() > 0 && contra.best_price().is_some_and(|price| order.crosses(price) && (price < low || price > high))
+    }
+
+    ///Holds the order for a volatility auction and halts its symbol, as the order would have to trade
+    /// outside the dynamic band. The orders of the symbol are queued until it resumes
+    pub fn halt_for_band(&mut self, order: OrderSingle) {
+        let symbol = order.symbol().clone();
+        info!("halting {}, the next trade is outside the dynamic band {:?}", symbol, self.dynamic_band_limits(&symbol));
+        self.add_order_to_order_book(order);
+        if let Err(e) = self.set_phase(&symbol, Halted) {
+            error!("error halting {} {}", symbol, e);
         }
     }
 
@@ -492,11 +1464,38 @@ impl OrderBook {
         self.clone()
     }
 
-    pub fn get_orders_for(&self, side: Side) -> HashMap<OrderBookKey, VecDeque<OrderSingle>> {
-        match side {
-            Buy => self.buy_orders.clone(),
-            Sell => self.sell_orders.clone(),
+    ///Returns a snapshot of all the orders on the given side keyed by price level
+    pub fn get_orders_for(&self, side: Side) -> BTreeMap<OrderBookKey, VecDeque<OrderSingle>> {
+        let mut orders = BTreeMap::new();
+        for levels in self.order_map_for(side).values() {
+            for (key, deque) in levels.iter() {
+                orders.insert(key.clone(), deque.clone());
+            }
         }
+        orders
+    }
+
+    ///Returns the price levels of the symbol on the given side, if any order exists for them
+    pub fn levels(&self, symbol: &str, side: Side) -> Option<&PriceLevels> {
+        self.order_map_for(side).get(symbol)
+    }
+
+    ///Returns the buy and the sell price levels of the symbol for matching
+    pub fn sides_mut(&mut self, symbol: &str) -> (&mut PriceLevels, &mut PriceLevels) {
+        let buy = self.buy_orders.entry(symbol.to_string()).or_insert_with(|| PriceLevels::new(Buy));
+        let sell = self.sell_orders.entry(symbol.to_string()).or_insert_with(|| PriceLevels::new(Sell));
+        (buy, sell)
+    }
+
+    ///Returns the best price of the symbol on the given side
+    pub fn best_price(&self, symbol: &str, side: Side) -> Option<Price> {
+        self.levels(symbol, side).and_then(|levels| levels.best_price())
+    }
+
+    ///Drops the symbols for which no orders are left on a side
+    pub fn remove_empty_levels(&mut self) {
+        self.buy_orders.retain(|_, levels| !levels.is_empty());
+        self.sell_orders.retain(|_, levels| !levels.is_empty());
     }
 
     pub fn print_order_book(&self) {
@@ -505,24 +1504,476 @@ impl OrderBook {
     }
 
     pub fn is_empty(&self) -> bool {
-        self.buy_orders.is_empty() && self.sell_orders.is_empty()
+        self.buy_orders.values().all(|levels| levels.is_empty()) &&
+            self.sell_orders.values().all(|levels| levels.is_empty()) &&
+            self.immediate_orders.is_empty() &&
+            self.stop_orders.values().all(|stops| stops.is_empty()) &&
+            self.pegged_orders.is_empty()
+    }
+
+
+    ///Adds the order to the back of the queue at its price level. Market, IOC and FOK orders never
+    /// rest in the book, they are held in arrival order until the matcher sweeps them against the book.
+    /// Stop and stop limit orders are held out of the book, in arrival order, until they are triggered.
+    /// Pegged orders are held out of the book until [`OrderBook::reprice_pegged_orders`] gives them a price,
+    /// while the remainder of a pegged order that was priced in the book rests at its price again.
+    /// A post-only order that would cross the contra side is rejected or repriced one tick away from it,
+    /// and the execution report is kept until [`OrderBook::take_reports`] is called. A new order is stamped
+    /// with the next arrival sequence number
+    pub fn add_order_to_order_book(&mut self, mut order: OrderSingle) {
+        let priced = order.arrival_seq() != 0 && order.price().is_positive();
+        self.stamp_arrival(&mut order);
+        if order.needs_refresh() {
+            order.refresh_peak();
+        }
+        if order.is_stop() {
+            self.stop_orders.entry(order.symbol().clone()).or_default().insert(order);
+            return;
+        }
+        if order.is_pegged() && !priced {
+            order.set_price(Price::ZERO);
+            self.pegged_orders.push_back(order);
+            return;
+        }
+        if order.is_immediate() {
+            self.immediate_orders.push_back(order);
+            return;
+        }
+        if let Some(post_only) = order.post_only() {
+            match self.post_only_price(&order, post_only) {
+                Some(price) if price == order.price() => {}
+                Some(price) => {
+                    order.set_price(price);
+                    self.reports.push(Fill::restated(&order, &self.id_generator));
+                }
+                None => {
+                    self.reports.push(Fill::rejected(&order, &self.id_generator));
+                    return;
+                }
+            }
+        }
+        self.rest(order);
+    }
+
+    ///Stamps the order with the next arrival sequence number, unless it was stamped when it arrived. The
+    /// order that arrives later is the aggressor when two orders trade. An order is assigned its order id
+    /// the first time it is stamped and keeps it when it is stamped anew
+    pub fn stamp_arrival(&mut self, order: &mut OrderSingle) {
+        if order.arrival_seq() == 0 {
+            self.arrival_seq += 1;
+            order.set_arrival_seq(self.arrival_seq);
+        }
+        if order.order_id().is_empty() {
+            order.set_order_id(self.id_generator.next_id(IdType::Order));
+        }
     }
 
+    ///Returns the price a post-only order can rest at without taking liquidity, which is its own price
+    /// unless it crosses the best contra price. A crossing order is repriced one tick away from the
+    /// contra side, using the tick size of the instrument or else the last decimal place of the contra
+    /// price. `None` is returned if the order must be rejected
+    fn post_only_price(&self, order: &OrderSingle, post_only: PostOnly) -> Option<Price> {
+        let contra = match self.best_price(order.symbol(), order.matching_side()) {
+            Some(contra) if order.crosses(contra) => contra,
+            _ => return Some(order.price()),
+        };
+        if post_only == PostOnly::Reject {
+            return None;
+        }
+        let tick_size = self.instruments.get(order.symbol())
+            .map_or(Price::new(1, contra.precision()), |instrument| instrument.tick_size());
+        let price = match order.side() {
+            Buy => contra - tick_size,
+            Sell => contra + tick_size,
+        };
+        Some(price).filter(|price| price.is_positive())
+    }
+
+    ///Removes and returns the execution reports produced when orders were added to the book, such as the
+    /// reject or the reprice of a post-only order
+    pub fn take_reports(&mut self) -> Vec<Fill> {
+        std::mem::take(&mut self.reports)
+    }
 
-    pub fn add_order_to_order_book(&mut self, order: OrderSingle) {
+    fn rest(&mut self, mut order: OrderSingle) {
         let side = order.side();
-        let key = OrderBookKey::new(order.price(), order.symbol().to_owned());
-        let order_map = self.order_map(side);
-        if order_map.contains_key(&key) {
-            order_map.entry(key).and_modify(|dequue| dequue.push_back(order));
+        let improves_price = match (self.best_price(order.symbol(), side), side) {
+            (None, _) => true,
+            (Some(best), Buy) => order.price() > best,
+            (Some(best), Sell) => order.price() < best,
+        };
+        order.set_improves_price(improves_price);
+        self.order_map(side)
+            .entry(order.symbol().to_owned())
+            .or_insert_with(|| PriceLevels::new(side))
+            .push_back(order);
+    }
+
+    ///Removes and returns the market, IOC and FOK orders waiting to be matched, in arrival order
+    pub fn take_immediate_orders(&mut self) -> VecDeque<OrderSingle> {
+        std::mem::take(&mut self.immediate_orders)
+    }
+
+    ///Removes and returns, in arrival order, the orders of the symbol that matching every order on arrival
+    /// would still have to match: its market, IOC and FOK orders and every order, stop orders included, that
+    /// arrived since the first order resting in the crossed part of the book. The orders that arrived earlier
+    /// never crossed each other, so they keep their place
+    pub fn take_unmatched_orders(&mut self, symbol: &str) -> Vec<OrderSingle> {
+        let (immediate, others): (VecDeque<OrderSingle>, VecDeque<OrderSingle>) = std::mem::take(&mut self.immediate_orders)
+            .into_iter()
+            .partition(|order| order.symbol() == symbol);
+        self.immediate_orders = others;
+        let mut orders = Vec::from(immediate);
+        let crossed = self.first_crossed_arrival(symbol);
+        let first = orders.iter().map(|order| order.arrival_seq()).chain(crossed).min();
+        if let Some(first) = first {
+            for side in [Buy, Sell] {
+                if let Some(levels) = self.order_map(side).get_mut(symbol) {
+                    orders.extend(levels.remove_where(|order| order.arrival_seq() >= first));
+                }
+            }
+            if let Some(stops) = self.stop_orders.get_mut(symbol) {
+                orders.extend(stops.remove_where(|order| order.arrival_seq() >= first));
+                if stops.is_empty() {
+                    self.stop_orders.remove(symbol);
+                }
+            }
+            self.remove_empty_levels();
+        }
+        orders.sort_by_key(|order| order.arrival_seq());
+        orders
+    }
+
+    ///Returns the earliest arrival among the resting orders of the symbol priced at or through the best
+    /// contra price, `None` if the book of the symbol is not crossed
+    fn first_crossed_arrival(&self, symbol: &str) -> Option<u64> {
+        let bid = self.best_price(symbol, Buy)?;
+        let ask = self.best_price(symbol, Sell)?;
+        if bid < ask {
+            return None;
+        }
+        let buys = self.levels(symbol, Buy)?.iter().take_while(|(key, _)| key.price() >= ask);
+        let sells = self.levels(symbol, Sell)?.iter().take_while(|(key, _)| key.price() <= bid);
+        buys.chain(sells)
+            .flat_map(|(_, orders)| orders.iter().map(|order| order.arrival_seq()))
+            .min()
+    }
+
+    ///Puts an order taken by [`OrderBook::take_unmatched_orders`] back without checking it again, a market,
+    /// IOC or FOK order with the orders waiting to be matched, a stop order with the stops waiting to be
+    /// triggered and any other order at its price level
+    pub fn restore_order(&mut self, order: OrderSingle) {
+        if order.is_stop() {
+            self.stop_orders.entry(order.symbol().clone()).or_default().insert(order);
+        } else if order.is_immediate() {
+            self.immediate_orders.push_back(order);
+        } else {
+            self.rest(order);
+        }
+    }
+
+    ///Returns the market, IOC and FOK orders waiting to be matched, in arrival order
+    pub fn immediate_orders(&self) -> &VecDeque<OrderSingle> {
+        &self.immediate_orders
+    }
+
+    ///Returns the stop and stop limit orders waiting to be triggered, in arrival order
+    pub fn stop_orders(&self) -> Vec<&OrderSingle> {
+        let mut orders: Vec<&OrderSingle> = self.stop_orders.values().flat_map(|stops| stops.iter()).collect();
+        orders.sort_by_key(|order| order.arrival_seq());
+        orders
+    }
+
+    ///Returns the pegged orders waiting for a price, in arrival order. This is the case while the best
+    /// bid or offer they follow is missing
+    pub fn pegged_orders(&self) -> &VecDeque<OrderSingle> {
+        &self.pegged_orders
+    }
+
+    ///Reprices the pegged orders against the current best bid and offer of their symbol. Only orders
+    /// that are not pegged make up the best bid and offer. An order whose price changes moves to the back
+    /// of the queue at its new price level, an order that cannot be priced is held out of the book until
+    /// it can. Returns the number of orders whose price changed
+    pub fn reprice_pegged_orders(&mut self) -> usize {
+        let mut symbols: BTreeSet<String> = self.pegged_orders.iter().map(|o| o.symbol().clone()).collect();
+        for order_map in [&self.buy_orders, &self.sell_orders] {
+            symbols.extend(order_map.iter()
+                .filter(|(_, levels)| levels.iter().any(|(_, orders)| orders.iter().any(|o| o.is_pegged())))
+                .map(|(symbol, _)| symbol.clone()));
+        }
+        symbols.iter().map(|symbol| self.reprice_symbol(symbol)).sum()
+    }
+
+    ///Reprices the pegged orders of the symbol like [`OrderBook::reprice_pegged_orders`]. Nothing is
+    /// visited unless the symbol has pegged orders and its best bid or offer moved since they were priced
+    pub fn reprice_pegged_orders_of(&mut self, symbol: &str) -> usize {
+        if !self.has_pegged_orders(symbol) {
+            return 0;
+        }
+        self.reprice_symbol(symbol)
+    }
+
+    ///Reprices the pegged orders of the symbol like [`OrderBook::reprice_pegged_orders_of`], but takes the
+    /// orders whose price changed out of the book instead of resting them, so that they can be matched as
+    /// incoming orders. Returns them in arrival order
+    pub fn take_repriced_pegged_orders(&mut self, symbol: &str) -> Vec<OrderSingle> {
+        if !self.has_pegged_orders(symbol) {
+            return vec![];
+        }
+        let (mut priced, _) = self.reprice(symbol);
+        priced.sort_by_key(|order| order.arrival_seq());
+        priced
+    }
+
+    fn has_pegged_orders(&self, symbol: &str) -> bool {
+        self.peg_references.contains_key(symbol) || self.pegged_orders.iter().any(|o| o.symbol() == symbol)
+    }
+
+    fn reprice_symbol(&mut self, symbol: &str) -> usize {
+        let (priced, repriced) = self.reprice(symbol);
+        priced.into_iter().for_each(|order| self.rest(order));
+        repriced
+    }
+
+    ///Reprices the pegged orders of the symbol and returns the orders given a new price, which are out of
+    /// the book, and the number of orders whose price changed
+    fn reprice(&mut self, symbol: &str) -> (Vec<OrderSingle>, usize) {
+        let bid = self.levels(symbol, Buy).and_then(|levels| levels.best_unpegged_price());
+        let ask = self.levels(symbol, Sell).and_then(|levels| levels.best_unpegged_price());
+        let waiting = self.pegged_orders.iter().any(|o| o.symbol() == symbol);
+        if !waiting && self.peg_references.get(symbol) == Some(&(bid, ask)) {
+            return (vec![], 0);
+        }
+        let tick_size = self.instruments.get(symbol).map_or(Price::ZERO, |instrument| instrument.tick_size());
+        let mut moved = vec![];
+        for side in [Buy, Sell] {
+            if let Some(levels) = self.order_map(side).get_mut(symbol) {
+                moved.extend(levels.remove_where(|o| o.is_pegged() && o.peg_price(bid, ask, tick_size) != Some(o.price())));
+            }
+        }
+        let (waiting, others): (VecDeque<OrderSingle>, VecDeque<OrderSingle>) = std::mem::take(&mut self.pegged_orders)
+            .into_iter()
+            .partition(|o| o.symbol() == symbol);
+        self.pegged_orders = others;
+        moved.extend(waiting);
+        let mut repriced = 0;
+        let mut priced = vec![];
+        for mut order in moved {
+            let price = order.peg_price(bid, ask, tick_size).unwrap_or(Price::ZERO);
+            if price != order.price() {
+                repriced += 1;
+            }
+            order.set_price(price);
+            if price.is_positive() {
+                priced.push(order);
+            } else {
+                self.pegged_orders.push_back(order);
+            }
+        }
+        self.remove_empty_levels();
+        let pegged = !priced.is_empty() || [Buy, Sell].into_iter()
+            .filter_map(|side| self.levels(symbol, side))
+            .any(|levels| levels.iter().any(|(_, orders)| orders.iter().any(|o| o.is_pegged())));
+        if pegged {
+            self.peg_references.insert(symbol.to_string(), (bid, ask));
         } else {
-            let mut deque = VecDeque::new();
-            deque.push_back(order);
-            order_map.insert(key, deque);
+            self.peg_references.remove(symbol);
+        }
+        (priced, repriced)
+    }
+
+    ///Triggers the stop orders reached by the trades in the fills and returns them as market or limit
+    /// orders, in the arrival order of the stops, to be matched as incoming orders. A stop is triggered if
+    /// any trade of its symbol is at or beyond its stop price. A triggered order is stamped with a new arrival
+    /// sequence number, as it takes liquidity like a newly arrived order
+    pub fn take_triggered_stops(&mut self, fills: &[Fill]) -> Vec<OrderSingle> {
+        let mut ranges: BTreeMap<&str, (Price, Price)> = BTreeMap::new();
+        for fill in fills.iter().filter(|fill| fill.qty() > 0) {
+            let range = ranges.entry(fill.symbol()).or_insert((fill.price(), fill.price()));
+            range.0 = range.0.min(fill.price());
+            range.1 = range.1.max(fill.price());
+        }
+        let mut triggered = vec![];
+        for (symbol, (low, high)) in ranges {
+            if let Some(stops) = self.stop_orders.get_mut(symbol) {
+                triggered.extend(stops.take_triggered(low, high));
+                if stops.is_empty() {
+                    self.stop_orders.remove(symbol);
+                }
+            }
+        }
+        triggered.sort_by_key(|order| order.arrival_seq());
+        for order in triggered.iter_mut() {
+            order.trigger();
+            order.set_arrival_seq(0);
+            self.stamp_arrival(order);
+        }
+        triggered
+    }
+
+    ///Ends the session held on `session_date`. Day orders and GTD orders expiring on or before the
+    /// date are removed from the book and an Expired execution report is returned for each of them
+    pub fn end_session(&mut self, session_date: NaiveDate) -> Vec<Fill> {
+        let ids = &self.id_generator;
+        let mut expired = vec![];
+        for levels in self.buy_orders.values_mut().chain(self.sell_orders.values_mut()) {
+            expired.extend(levels.remove_where(|order| order.expires_on(session_date)).iter().map(|order| Fill::expired(order, ids)));
+        }
+        for stops in self.stop_orders.values_mut() {
+            expired.extend(stops.remove_where(|order| order.expires_on(session_date)).iter().map(|order| Fill::expired(order, ids)));
+        }
+        self.stop_orders.retain(|_, stops| !stops.is_empty());
+        let (done, waiting): (VecDeque<OrderSingle>, VecDeque<OrderSingle>) = std::mem::take(&mut self.pegged_orders)
+            .into_iter()
+            .partition(|order| order.expires_on(session_date));
+        self.pegged_orders = waiting;
+        expired.extend(done.iter().map(|order| Fill::expired(order, &self.id_generator)));
+        self.remove_empty_levels();
+        self.record_done(&expired);
+        expired
+    }
+
+    ///Remembers the orders that the execution reports leave with no open quantity, so that a later
+    /// cancel request for them is rejected as too late rather than as unknown. The price of the last
+    /// trade of each symbol is kept as the reference price of its next auction
+    pub fn record_done(&mut self, reports: &[Fill]) {
+        for report in reports.iter().filter(|report| report.leaves_qty() == 0) {
+            self.done_orders.insert(report.cl_ord_id().to_string());
+        }
+        for report in reports.iter().filter(|report| report.qty() > 0) {
+            self.reference_prices.insert(report.symbol().clone(), report.price());
         }
     }
 
-    pub fn order_map(&mut self, side: Side) -> &mut HashMap<OrderBookKey, VecDeque<OrderSingle>> {
+    ///Returns the trading phase of the symbol. Symbols that were never moved to another phase trade continuously
+    pub fn phase(&self, symbol: &str) -> TradingPhase {
+        self.phases.get(symbol).copied().unwrap_or_default()
+    }
+
+    ///Moves the symbol to the given trading phase and publishes the change as a status event. Uncrossing
+    /// the orders queued by an auction phase is left to the caller
+    pub fn set_phase(&mut self, symbol: &str, phase: TradingPhase) -> Result<(), String> {
+        let current = self.phase(symbol);
+        if !current.can_move_to(phase) {
+            return Err(format!("{} cannot move from {} to {}", symbol, current, phase));
+        }
+        self.phases.insert(symbol.to_string(), phase);
+        self.status_events.push(PhaseChange::new(symbol.to_string(), current, phase));
+        Ok(())
+    }
+
+    ///Removes and returns the status events published by the phase changes since the last call
+    pub fn take_status_events(&mut self) -> Vec<PhaseChange> {
+        std::mem::take(&mut self.status_events)
+    }
+
+    ///Returns `true` if the orders of the symbol are matched as they arrive rather than queued
+    pub fn is_matching(&self, symbol: &str) -> bool {
+        self.phase(symbol).is_matching()
+    }
+
+    pub fn is_in_auction(&self, symbol: &str) -> bool {
+        self.phase(symbol).is_auction()
+    }
+
+    ///Returns the price that breaks ties between uncrossing prices of an auction of the symbol. This is
+    /// the price of its last trade unless it was set explicitly
+    pub fn reference_price(&self, symbol: &str) -> Option<Price> {
+        self.reference_prices.get(symbol).copied()
+    }
+
+    pub fn set_reference_price(&mut self, symbol: &str, price: Price) {
+        self.reference_prices.insert(symbol.to_string(), price);
+    }
+
+    ///Removes and returns every resting order of the symbol on the given side, in price and time priority
+    pub fn take_orders(&mut self, symbol: &str, side: Side) -> Vec<OrderSingle> {
+        let orders = self.order_map(side).get_mut(symbol)
+            .map_or(vec![], |levels| levels.remove_where(|_| true));
+        self.remove_empty_levels();
+        orders
+    }
+
+    ///Returns the resting, untriggered stop or unpriced pegged order with the given `cl_ord_id`. When the
+    /// symbol or the side is given the order must also have that symbol or side
+    pub fn find_order(&self, cl_ord_id: &str, symbol: Option<&str>, side: Option<Side>) -> Option<&OrderSingle> {
+        [(Buy, &self.buy_orders), (Sell, &self.sell_orders)].into_iter()
+            .filter(|(s, _)| side.map_or(true, |side| side == *s))
+            .flat_map(|(_, order_map)| order_map.iter())
+            .filter(|(s, _)| symbol.map_or(true, |symbol| symbol == s.as_str()))
+            .find_map(|(_, levels)| levels.find(cl_ord_id))
+            .or_else(|| self.stop_orders.values().flat_map(|stops| stops.iter()).chain(self.pegged_orders.iter()).find(|o| o.cl_ord_id() == cl_ord_id &&
+                side.map_or(true, |side| side == o.side()) &&
+                symbol.map_or(true, |symbol| symbol == o.symbol())))
+    }
+
+    ///Removes the resting order of the request from the book and returns its Cancelled execution
+    /// report. The cancel is rejected if no such order rests in the book, as too late to cancel if the
+    /// order has already been filled, cancelled or expired
+    pub fn cancel_order(&mut self, request: &CancelRequest) -> Result<Fill, CancelReject> {
+        let order = self.find_order(request.cl_ord_id(), request.symbol(), request.side()).cloned()
+            .ok_or_else(|| self.reject(request.cl_ord_id()))?;
+        self.take_order(&order);
+        let report = Fill::cancelled(&order, order.cum_qty(), &self.id_generator);
+        self.record_done(std::slice::from_ref(&report));
+        Ok(report)
+    }
+
+    ///Changes the price and/or the quantity of the resting order of the request and returns its
+    /// Replaced execution report. The quantity of the request is the new total quantity of the order,
+    /// including what has already been filled. A quantity decrease at the same price keeps the time
+    /// priority of the order, any other change moves it to the back of the queue at its new price.
+    /// The replace is rejected like a cancel, or if the replaced order would not be valid
+    pub fn replace_order(&mut self, request: &ReplaceRequest) -> Result<Fill, CancelReject> {
+        let order = self.find_order(request.cl_ord_id(), request.symbol(), request.side()).cloned()
+            .ok_or_else(|| self.reject(request.cl_ord_id()))?;
+        let new_qty = request.qty().unwrap_or(order.qty() + order.cum_qty());
+        if new_qty <= order.cum_qty() {
+            return Err(CancelReject::with_text(order.cl_ord_id().clone(), InvalidRequest,
+                                               format!("quantity {} is not above the filled quantity {}", new_qty, order.cum_qty())));
+        }
+        let mut replaced = order.clone();
+        replaced.set_qty(new_qty - order.cum_qty());
+        replaced.set_price(request.price().unwrap_or(order.price()));
+        if let Err(e) = self.validate_order(&replaced) {
+            return Err(CancelReject::with_text(order.cl_ord_id().clone(), InvalidRequest, e));
+        }
+        if replaced.price() == order.price() && replaced.qty() <= order.qty() {
+            self.find_order_mut(&order).unwrap().set_qty(replaced.qty());
+        } else {
+            self.take_order(&order);
+            replaced.refresh_peak();
+            replaced.set_arrival_seq(0);
+            self.add_order_to_order_book(replaced.clone());
+        }
+        Ok(Fill::replaced(&replaced, &self.id_generator))
+    }
+
+    fn find_order_mut(&mut self, order: &OrderSingle) -> Option<&mut OrderSingle> {
+        let levels = if order.side() == Sell { &mut self.sell_orders } else { &mut self.buy_orders };
+        self.stop_orders.values_mut().flat_map(|stops| stops.iter_mut()).chain(self.pegged_orders.iter_mut())
+            .find(|o| o.cl_ord_id() == order.cl_ord_id())
+            .or_else(|| levels.get_mut(order.symbol()).and_then(|levels| levels.find_mut(order.cl_ord_id())))
+    }
+
+    ///Removes the resting order from the book
+    fn take_order(&mut self, order: &OrderSingle) {
+        if let Some(stops) = self.stop_orders.get_mut(order.symbol()) {
+            stops.remove_where(|o| o.cl_ord_id() == order.cl_ord_id());
+        }
+        self.pegged_orders.retain(|o| o.cl_ord_id() != order.cl_ord_id());
+        if let Some(levels) = self.order_map(order.side()).get_mut(order.symbol()) {
+            levels.remove_where(|o| o.cl_ord_id() == order.cl_ord_id());
+        }
+        self.remove_empty_levels();
+    }
+
+    fn reject(&self, cl_ord_id: &str) -> CancelReject {
+        let reason = if self.done_orders.contains(cl_ord_id) { TooLateToCancel } else { UnknownOrder };
+        CancelReject::new(cl_ord_id.to_string(), reason)
+    }
+
+    pub fn order_map(&mut self, side: Side) -> &mut BTreeMap<String, PriceLevels> {
         if side == Sell {
             &mut self.sell_orders
         } else {
@@ -530,6 +1981,14 @@ impl OrderBook {
         }
     }
 
+    fn order_map_for(&self, side: Side) -> &BTreeMap<String, PriceLevels> {
+        if side == Sell {
+            &self.sell_orders
+        } else {
+            &self.buy_orders
+        }
+    }
+
     pub fn pretty_print_self(&self) -> String {
         let keys = self.get_excl_keys();
         let mut strings = String::new();
@@ -541,40 +2000,45 @@ impl OrderBook {
     }
 
 
+    ///Returns the symbols with resting orders or market, IOC and FOK orders waiting to be matched, in sorted order
+    pub fn symbols(&self) -> Vec<String> {
+        let mut symbols: Vec<String> = self.get_excl_keys().into_iter()
+            .chain(self.immediate_orders.iter().map(|order| order.symbol().as_str()))
+            .map(String::from)
+            .collect();
+        symbols.sort();
+        symbols.dedup();
+        symbols
+    }
+
+    ///Returns the symbols in the order book in sorted order
     pub fn get_excl_keys(&self) -> Vec<&str> {
-        let mut all_keys = vec![];
-        for (key, _) in &self.buy_orders {
-            all_keys.push(key.symbol());
-        }
-        for (key, _) in &self.sell_orders {
-            all_keys.push(key.symbol());
-        }
-        let mut excl_keys: Vec<&str> = vec![];
-        for symbol in all_keys {
-            if !excl_keys.contains(&symbol) {
-                excl_keys.push(&symbol)
-            }
-        }
+        let mut excl_keys: Vec<&str> = self.buy_orders.keys()
+            .chain(self.sell_orders.keys())
+            .map(|symbol| symbol.as_str())
+            .collect();
+        excl_keys.sort();
+        excl_keys.dedup();
         excl_keys
     }
     pub fn print_market_depth_for(&self, symbol: &str) -> String {
-        let mut md_buy = self.get_md(symbol, &self.buy_orders,Buy).clone();
-        let mut md_sell = self.get_md(symbol, &self.sell_orders,Sell ).clone();
+        let md_buy = self.get_md(symbol, Buy);
+        let md_sell = self.get_md(symbol, Sell);
         let s = format!("market depth for {}", symbol);
         let mut strings = String::new();
         println!("\n{}", s.reversed());
         println!();
         println!("{}", "Bids:".green().bold());
         strings.push_str("Bids:\n");
-        strings.push_str(self.print_md(&mut md_buy).as_str());
+        strings.push_str(self.print_md(&md_buy).as_str());
         println!();
         println!("{}", "Offers:\n".red().bold());
         strings.push_str("Offers:\n");
-        strings.push_str(self.print_md(&mut md_sell).as_str());
+        strings.push_str(self.print_md(&md_sell).as_str());
         strings
     }
 
-    fn print_md(&self, mds_buy: &Vec<MarketDepth>) -> String {
+    fn print_md(&self, mds_buy: &[MarketDepth]) -> String {
         let mut table = Table::new();
         table.add_row(row!["Symbol","Quantity","Price" , "Side"]);
         for md in mds_buy {
@@ -586,44 +2050,38 @@ impl OrderBook {
     }
 
 
-    fn get_md(&self, symbol: &str, order_map: &HashMap<OrderBookKey, VecDeque<OrderSingle>>, side: Side) -> Vec<MarketDepth> {
+    ///Returns the aggregated depth of the symbol on the given side, best price first. Only the visible
+    /// peak of iceberg orders is shown
+    fn get_md(&self, symbol: &str, side: Side) -> Vec<MarketDepth> {
         let mut depths = vec![];
 
-        for (key, orders) in order_map {
-            let side = &orders[0].side();
-            if key.symbol() == symbol {
-                let orders: Vec<OrderSingle> = orders.clone().into_iter().collect();
-                let aggregate = Aggregator::sigma(&orders);
-                let md = MarketDepth::new(key.price, aggregate, side.clone(),symbol.to_string());
+        if let Some(levels) = self.levels(symbol, side) {
+            for (key, orders) in levels.iter() {
+                let aggregate = orders.iter().map(|o| o.visible_qty()).sum();
+                let md = MarketDepth::new(key.price, aggregate, side, symbol.to_string());
                 depths.push(md);
             }
         }
-        depths.sort_by(|md1,md2| md1.price().partial_cmp(&md2.price()).unwrap());
-        if side == Buy{
-            depths.reverse();
-        }
 
         depths
-
-
     }
 }
 
 #[derive(Clone, Debug)]
 struct MarketDepth {
-    price: f64,
+    price: Price,
     qty: u32,
     side: Side,
     symbol: String,
 }
 
 impl MarketDepth {
-    fn new(price: f64, qty: u32, side: Side,symbol:String) -> Self {
+    fn new(price: Price, qty: u32, side: Side,symbol:String) -> Self {
         Self { price, qty, side,symbol }
     }
 
 
-    pub fn price(&self) -> f64 {
+    pub fn price(&self) -> Price {
         self.price
     }
     pub fn qty(&self) -> u32 {
@@ -634,26 +2092,34 @@ impl MarketDepth {
     &self.symbol
 }
     pub fn side(&self) -> Side {
-        self.side.clone()
+        self.side
     }
 }
 
+#[cfg(test)]
 mod tests {
     use std::collections::hash_map::DefaultHasher;
     use std::hash::{Hash, Hasher};
 
+    use chrono::NaiveDate;
     use log::debug;
 
-    use crate::common::utils::{create_order_book, read_input};
+    use crate::common::id_generator::SharedIdGenerator;
+    use crate::common::utils::{create_order_book, create_order_from_string, read_input};
     use crate::matchers::fifo_matcher::FIFOMatcher;
     use crate::matchers::matcher::Matcher;
-    use crate::model::domain::{Fill, OrderBook, OrderBookKey};
+    use crate::model::domain::{Fill, Liquidity, OrderBookKey};
+    use crate::model::domain::Side::{Buy, Sell};
+    use crate::model::cancel::CancelRejectReason::{TooLateToCancel, UnknownOrder};
+    use crate::model::cancel::CancelRequest;
+    use crate::model::domain::Status::{Cancelled, Expired, Filled, New, Restated};
+    use crate::model::price::Price;
 
     #[test]
     fn test_partial_equals() {
-        let ok1 = OrderBookKey::new(101.5, "infy".to_string());
-        let ok2 = OrderBookKey::new(101.1, "infy".to_string());
-        let ok3 = OrderBookKey::new(101.5, "infy".to_string());
+        let ok1 = OrderBookKey::new(Price::new(1015, 1), "infy".to_string());
+        let ok2 = OrderBookKey::new(Price::new(1011, 1), "infy".to_string());
+        let ok3 = OrderBookKey::new(Price::new(10150, 2), "infy".to_string());
 
         assert_ne!(ok1, ok2);
         assert_eq!(ok1, ok3);
@@ -661,9 +2127,9 @@ mod tests {
 
     #[test]
     fn test_orderbookkey_hash() {
-        let ok1 = OrderBookKey::new(101.5, "infy".to_string());
-        let ok2 = OrderBookKey::new(101.5, "infy".to_string());
-        let ok3 = OrderBookKey::new(101.45, "infy".to_string());
+        let ok1 = OrderBookKey::new(Price::new(1015, 1), "infy".to_string());
+        let ok2 = OrderBookKey::new(Price::new(10150, 2), "infy".to_string());
+        let ok3 = OrderBookKey::new(Price::new(10145, 2), "infy".to_string());
 
         let mut hasher = DefaultHasher::new();
 
@@ -690,8 +2156,7 @@ mod tests {
 
     #[test]
     fn test_print_md() {
-        let mut ob = create_order_book(read_input("test_data/orders.txt"));
-        let key = &String::from("IBM");
+        let ob = create_order_book(read_input("test_data/orders.txt"));
         let s = ob.pretty_print_self();
 
         println!("{}", s);
@@ -706,11 +2171,178 @@ mod tests {
         println!("{}",s);
 
     }
-}
 
+    #[test]
+    fn test_price_levels_are_kept_best_price_first() {
+        let order_book = create_order_book(read_input("test_data/orders.txt"));
+        let bids: Vec<String> = order_book.levels("IBM", Buy).unwrap().iter().map(|(key, _)| key.price().to_string()).collect();
+        let offers: Vec<String> = order_book.levels("IBM", Sell).unwrap().iter().map(|(key, _)| key.price().to_string()).collect();
 
-   
+        assert_eq!(bids, vec!["602.5", "602", "601.9", "601.1"]);
+        assert_eq!(offers, vec!["602.1", "602.5"]);
+        assert_eq!(order_book.best_price("IBM", Buy), Some(Price::new(6025, 1)));
+        assert_eq!(order_book.best_price("IBM", Sell), Some(Price::new(6021, 1)));
+        assert_eq!(order_book.best_price("TATA", Buy), None);
+    }
 
+    #[test]
+    fn test_end_session_expires_day_and_gtd_orders() {
+        let input = vec![
+            "id1 IBM 100 601 Buy tif=Day".to_string(),
+            "id2 IBM 100 601 Buy tif=GTD expire=2026-10-19".to_string(),
+            "id3 IBM 100 601 Buy tif=GTD expire=2026-10-20".to_string(),
+            "id4 IBM 100 601 Buy".to_string(),
+            "id5 IBM 40 601 Sell".to_string(),
+        ];
+        let mut order_book = create_order_book(input);
+        FIFOMatcher.match_order_book(&mut order_book);
+
+        let expired = order_book.end_session(NaiveDate::from_ymd_opt(2026, 10, 19).unwrap());
+        assert_eq!(expired.len(), 2);
+        assert_eq!(expired[0].cl_ord_id(), "id1");
+        assert_eq!(*expired[0].status(), Expired);
+        assert_eq!(expired[0].cum_qty(), 40);
+        assert_eq!(expired[0].leaves_qty(), 0);
+        assert_eq!(expired[1].cl_ord_id(), "id2");
+
+        let expired = order_book.end_session(NaiveDate::from_ymd_opt(2026, 10, 20).unwrap());
+        assert_eq!(expired.len(), 1);
+        assert_eq!(expired[0].cl_ord_id(), "id3");
+        let resting: Vec<&String> = order_book.levels("IBM", Buy).unwrap().iter()
+            .flat_map(|(_, orders)| orders.iter().map(|o| o.cl_ord_id()))
+            .collect();
+        assert_eq!(resting, vec!["id4"]);
+    }
 
+    #[test]
+    fn test_cancel_order() {
+        let input = vec![
+            "id1 IBM 100 601 Buy".to_string(),
+            "id2 IBM 100 602 Buy".to_string(),
+            "id3 IBM 150 601 Sell".to_string(),
+        ];
+        let mut order_book = create_order_book(input);
+        FIFOMatcher.match_order_book(&mut order_book);
+
+        let wrong_side = CancelRequest::new("id1".to_string(), None, Some(Sell));
+        assert_eq!(order_book.cancel_order(&wrong_side).unwrap_err().reason(), UnknownOrder);
+
+        let report = order_book.cancel_order(&CancelRequest::new("id1".to_string(), Some("IBM".to_string()), Some(Buy))).unwrap();
+        assert_eq!(*report.status(), Cancelled);
+        assert_eq!(report.cum_qty(), 50);
+        assert_eq!(report.leaves_qty(), 0);
+        assert!(order_book.is_empty());
+
+        let again = CancelRequest::new("id1".to_string(), None, None);
+        assert_eq!(order_book.cancel_order(&again).unwrap_err().reason(), TooLateToCancel);
+        let filled = CancelRequest::new("id2".to_string(), None, None);
+        assert_eq!(order_book.cancel_order(&filled).unwrap_err().reason(), TooLateToCancel);
+        let unknown = CancelRequest::new("id9".to_string(), None, None);
+        assert_eq!(order_book.cancel_order(&unknown).unwrap_err().reason(), UnknownOrder);
+    }
 
+    #[test]
+    fn test_decrement_and_cancel_self_trade_prevention() {
+        let mut incoming = create_order_from_string("id1 IBM 100 601 Buy participant=P1 stp=DC".to_string());
+        let mut resting = create_order_from_string("id2 IBM 40 601 Sell participant=P1".to_string());
+        assert!(incoming.is_self_trade_with(&resting));
+
+        let reports = incoming.self_trade_prevention().apply(&mut incoming, &mut resting, &SharedIdGenerator::default());
+        assert_eq!(reports.len(), 2);
+        assert_eq!(reports[0].cl_ord_id(), "id1");
+        assert_eq!(*reports[0].exec_type(), Restated);
+        assert_eq!(reports[0].leaves_qty(), 60);
+        assert_eq!(reports[1].cl_ord_id(), "id2");
+        assert_eq!(*reports[1].exec_type(), Cancelled);
+        assert_eq!(incoming.qty(), 60);
+        assert_eq!(resting.qty(), 0);
+        assert!(!incoming.is_self_trade_with(&create_order_from_string("id3 IBM 40 601 Sell".to_string())));
+    }
+
+    #[test]
+    fn test_reprice_pegged_orders_follows_the_bbo() {
+        let input = vec![
+            "id1 IBM 100 PEG Buy peg=MID".to_string(),
+            "id2 IBM 100 PEG Buy peg=PRIMARY offset=-0.5".to_string(),
+            "id3 IBM 100 PEG Sell peg=MARKET offset=0.5".to_string(),
+            "id4 IBM 100 601 Buy".to_string(),
+        ];
+        let mut order_book = create_order_book(input);
+        assert_eq!(order_book.pegged_orders().len(), 3);
+
+        assert_eq!(order_book.reprice_pegged_orders(), 2);
+        assert_eq!(order_book.find_order("id2", None, None).unwrap().price(), Price::new(6005, 1));
+        assert_eq!(order_book.find_order("id3", None, None).unwrap().price(), Price::new(6015, 1));
+        let waiting: Vec<&String> = order_book.pegged_orders().iter().map(|o| o.cl_ord_id()).collect();
+        assert_eq!(waiting, vec!["id1"]);
+        assert_eq!(order_book.reprice_pegged_orders(), 0);
+
+        order_book.add_order_to_order_book(create_order_from_string("id5 IBM 100 602 Sell".to_string()));
+        order_book.add_order_to_order_book(create_order_from_string("id6 IBM 100 601.5 Buy".to_string()));
+        assert_eq!(order_book.reprice_pegged_orders(), 3);
+        assert_eq!(order_book.find_order("id1", None, None).unwrap().price(), Price::new(60175, 2));
+        assert_eq!(order_book.find_order("id2", None, None).unwrap().price(), Price::new(601, 0));
+        assert_eq!(order_book.find_order("id3", None, None).unwrap().price(), Price::new(602, 0));
+        assert!(order_book.pegged_orders().is_empty());
+    }
+
+    #[test]
+    fn test_orders_are_stamped_in_arrival_order() {
+        let input = vec![
+            "id1 IBM 100 601 Buy".to_string(),
+            "id2 IBM 100 MKT Sell".to_string(),
+            "id3 IBM 100 602 Sell stop=603".to_string(),
+        ];
+        let order_book = create_order_book(input);
+        let id1 = order_book.find_order("id1", None, None).unwrap().clone();
+        let id2 = order_book.immediate_orders()[0].clone();
+        let id3 = order_book.stop_orders()[0].clone();
+        assert_eq!((id1.arrival_seq(), id2.arrival_seq(), id3.arrival_seq()), (1, 2, 3));
+        assert!(!id1.order_id().is_empty());
+        assert_ne!(id1.order_id(), id2.order_id());
+
+        assert!(id2.is_aggressor_against(&id1));
+        assert!(!id1.is_aggressor_against(&id3));
+        assert_eq!(id3.liquidity_against(&id1), Liquidity::Removed);
+        assert_eq!(id3.trade_price_against(&id1), Price::new(601, 0));
+        assert_eq!(id1.trade_price_against(&id3), Price::new(601, 0));
+    }
+
+    #[test]
+    fn test_only_the_stops_reached_by_a_trade_are_triggered() {
+        let input = vec![
+            "id1 IBM 100 MKT Buy stop=605".to_string(),
+            "id2 IBM 100 MKT Buy stop=603".to_string(),
+            "id3 IBM 100 MKT Sell stop=598".to_string(),
+            "id4 IBM 100 MKT Sell stop=604".to_string(),
+            "id5 INFY 100 MKT Buy stop=10".to_string(),
+        ];
+        let mut order_book = create_order_book(input);
+        let trade = |symbol: &str, price| Fill::new(symbol.to_string(), String::new(), String::new(), New, 100, 0, 100,
+                                                    Price::new(price, 0), Buy, "id0".to_string(), Filled);
+
+        let triggered = order_book.take_triggered_stops(&[trade("IBM", 604)]);
+        let triggered: Vec<&String> = triggered.iter().map(|o| o.cl_ord_id()).collect();
+        assert_eq!(triggered, vec!["id2", "id4"]);
+        let waiting: Vec<&String> = order_book.stop_orders().iter().map(|o| o.cl_ord_id()).collect();
+        assert_eq!(waiting, vec!["id1", "id3", "id5"]);
+        assert!(order_book.take_triggered_stops(&[trade("IBM", 599)]).is_empty());
+    }
 
+    #[test]
+    fn test_pegged_orders_of_a_symbol_are_repriced_when_its_bbo_moves() {
+        let input = vec![
+            "id1 IBM 100 PEG Buy peg=PRIMARY".to_string(),
+            "id2 IBM 100 601 Buy".to_string(),
+            "id3 INFY 100 1500 Buy".to_string(),
+        ];
+        let mut order_book = create_order_book(input);
+        assert_eq!(order_book.reprice_pegged_orders_of("INFY"), 0);
+        assert_eq!(order_book.reprice_pegged_orders_of("IBM"), 1);
+        assert_eq!(order_book.reprice_pegged_orders_of("IBM"), 0);
+
+        order_book.add_order_to_order_book(create_order_from_string("id4 IBM 100 601.5 Buy".to_string()));
+        assert_eq!(order_book.reprice_pegged_orders_of("IBM"), 1);
+        assert_eq!(order_book.find_order("id1", None, None).unwrap().price(), Price::new(6015, 1));
+    }
+}
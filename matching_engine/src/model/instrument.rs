@@ -0,0 +1,239 @@
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::model::domain::OrderSingle;
+use crate::model::price::Price;
+
+///Width of a price band on either side of a reference price, either a percentage of the reference
+/// price or an absolute amount. `5%` parses to a percentage band and `2.5` to an absolute band
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PriceBand {
+    Percent(Price),
+    Absolute(Price),
+}
+
+impl PriceBand {
+    ///Returns the lowest and the highest price of the band around the reference price
+    pub fn limits(&self, reference: Price) -> (Price, Price) {
+        let width = match self {
+            PriceBand::Percent(percent) => reference.percent(*percent),
+            PriceBand::Absolute(amount) => *amount,
+        };
+        (reference - width, reference + width)
+    }
+
+    pub fn contains(&self, reference: Price, price: Price) -> bool {
+        let (low, high) = self.limits(reference);
+        low <= price && price <= high
+    }
+}
+
+impl Display for PriceBand {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PriceBand::Percent(percent) => write!(f, "{}%", percent),
+            PriceBand::Absolute(amount) => write!(f, "{}", amount),
+        }
+    }
+}
+
+impl FromStr for PriceBand {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let band = match s.strip_suffix('%') {
+            Some(percent) => PriceBand::Percent(Price::from_str(percent)?),
+            None => PriceBand::Absolute(Price::from_str(s)?),
+        };
+        match band {
+            PriceBand::Percent(width) | PriceBand::Absolute(width) if width.is_positive() => Ok(band),
+            _ => Err(format!("invalid price band {}", s)),
+        }
+    }
+}
+
+///Reference data of a tradable symbol. Orders for the symbol must be priced in multiples of the
+/// tick size with no more decimals than the price precision, and their quantity must be a multiple
+/// of the lot size and at least the minimum quantity. Market orders are only checked for quantity and
+/// stop prices are checked like prices. The optional price band rejects limit orders priced too far from
+/// the reference price of the symbol and the optional dynamic band halts the symbol when a trade would
+/// print too far from it. The optional group, such as `FUT` or `EQ`, lets the symbols of a kind share a
+/// matching algorithm
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Instrument {
+    symbol: String,
+    tick_size: Price,
+    lot_size: u32,
+    min_qty: u32,
+    price_precision: u32,
+    #[serde(default)]
+    price_band: Option<PriceBand>,
+    #[serde(default)]
+    dynamic_band: Option<PriceBand>,
+    #[serde(default)]
+    group: Option<String>,
+}
+
+impl Instrument {
+    pub fn new(symbol: String, tick_size: Price, lot_size: u32, min_qty: u32, price_precision: u32) -> Self {
+        Self {
+            symbol,
+            tick_size,
+            lot_size,
+            min_qty,
+            price_precision,
+            price_band: None,
+            dynamic_band: None,
+            group: None,
+        }
+    }
+
+    ///Returns an error describing the first rule of the instrument that the order violates
+    pub fn validate(&self, order: &OrderSingle) -> Result<(), String> {
+        if order.qty() < self.min_qty {
+            return Err(format!("quantity {} is below the minimum quantity {} for {}", order.qty(), self.min_qty, self.symbol));
+        }
+        if self.lot_size > 0 && order.qty() % self.lot_size != 0 {
+            return Err(format!("quantity {} is not a multiple of the lot size {} for {}", order.qty(), self.lot_size, self.symbol));
+        }
+        if let Some(stop_price) = order.stop_price() {
+            self.validate_price(stop_price)?;
+        }
+        if !order.has_limit_price() {
+            return Ok(());
+        }
+        self.validate_price(order.price())
+    }
+
+    fn validate_price(&self, price: Price) -> Result<(), String> {
+        if price.precision() > self.price_precision {
+            return Err(format!("price {} has more than {} decimal places for {}", price, self.price_precision, self.symbol));
+        }
+        if !price.is_multiple_of(self.tick_size) {
+            return Err(format!("price {} is not a multiple of the tick size {} for {}", price, self.tick_size, self.symbol));
+        }
+        Ok(())
+    }
+
+    pub fn symbol(&self) -> &str {
+        &self.symbol
+    }
+
+    pub fn tick_size(&self) -> Price {
+        self.tick_size
+    }
+
+    pub fn lot_size(&self) -> u32 {
+        self.lot_size
+    }
+
+    pub fn min_qty(&self) -> u32 {
+        self.min_qty
+    }
+
+    pub fn price_precision(&self) -> u32 {
+        self.price_precision
+    }
+
+    pub fn price_band(&self) -> Option<PriceBand> {
+        self.price_band
+    }
+
+    pub fn set_price_band(&mut self, price_band: Option<PriceBand>) {
+        self.price_band = price_band;
+    }
+
+    pub fn dynamic_band(&self) -> Option<PriceBand> {
+        self.dynamic_band
+    }
+
+    pub fn set_dynamic_band(&mut self, dynamic_band: Option<PriceBand>) {
+        self.dynamic_band = dynamic_band;
+    }
+
+    pub fn group(&self) -> Option<&str> {
+        self.group.as_deref()
+    }
+
+    pub fn set_group(&mut self, group: Option<String>) {
+        self.group = group;
+    }
+}
+
+///Holds the [`Instrument`] of every tradable symbol. An empty registry means that no reference
+/// data has been loaded and every symbol is accepted
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct InstrumentRegistry {
+    instruments: BTreeMap<String, Instrument>,
+}
+
+impl InstrumentRegistry {
+    pub fn add(&mut self, instrument: Instrument) {
+        self.instruments.insert(instrument.symbol().to_string(), instrument);
+    }
+
+    pub fn get(&self, symbol: &str) -> Option<&Instrument> {
+        self.instruments.get(symbol)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.instruments.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.instruments.len()
+    }
+
+    ///Validates the order against the instrument of its symbol. Orders for unknown symbols are
+    /// rejected unless the registry is empty
+    pub fn validate(&self, order: &OrderSingle) -> Result<(), String> {
+        if self.is_empty() {
+            return Ok(());
+        }
+        match self.get(order.symbol()) {
+            Some(instrument) => instrument.validate(order),
+            None => Err(format!("unknown symbol {}", order.symbol())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::common::utils::{create_instrument_registry, create_order_from_string, read_input};
+    use crate::model::instrument::PriceBand;
+    use crate::model::price::Price;
+
+    #[test]
+    fn test_validate_orders_against_instruments() {
+        let registry = create_instrument_registry(read_input("test_data/instruments.txt")).unwrap();
+        let validate = |line: &str| registry.validate(&create_order_from_string(line.to_string()));
+
+        assert!(validate("id1 IBM 100 602.5 Buy").is_ok());
+        assert!(validate("id2 IBM 0 602.5 Buy").is_err());
+        assert!(validate("id3 IBM 100 602.55 Buy").is_err());
+        assert!(validate("id4 IBM 100 602.123 Buy").is_err());
+        assert!(validate("id5 TATA 15 602.5 Buy").is_err());
+        assert!(validate("id6 TATA 20 602.5 Buy").is_ok());
+        assert_eq!(validate("id7 INFY 100 602.5 Buy"), Err("unknown symbol INFY".to_string()));
+        assert!(validate("id8 IBM 100 MKT Sell stop=601.5").is_ok());
+        assert!(validate("id9 IBM 100 MKT Sell stop=601.55").is_err());
+    }
+
+    #[test]
+    fn test_price_band_limits() {
+        let reference = Price::new(600, 0);
+        let percent: PriceBand = "5%".parse().unwrap();
+        let absolute: PriceBand = "2.5".parse().unwrap();
+
+        assert_eq!(percent.limits(reference), (Price::new(570, 0), Price::new(630, 0)));
+        assert_eq!(absolute.limits(reference), (Price::new(5975, 1), Price::new(6025, 1)));
+        assert!(absolute.contains(reference, Price::new(6025, 1)));
+        assert!(!absolute.contains(reference, Price::new(6026, 1)));
+        assert_eq!(percent.to_string(), "5%");
+        assert!("0%".parse::<PriceBand>().is_err());
+        assert!("-1".parse::<PriceBand>().is_err());
+    }
+}
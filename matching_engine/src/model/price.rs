@@ -0,0 +1,363 @@
+use std::cmp::Ordering;
+use std::fmt::{Display, Formatter};
+use std::hash::{Hash, Hasher};
+use std::ops::{Add, Sub};
+use std::str::FromStr;
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde::de::{Error, Visitor};
+
+///A fixed point price. The price is held as an integer number of ticks where one tick is
+/// 10<sup>-scale</sup>, so `Price::new(6021, 1)` is 602.1. Prices with a different scale but the
+/// same value are equal, hash alike and fall on the same [`crate::model::domain::OrderBookKey`]
+///
+/// # Example
+///```rust
+/// use matching_engine::model::price::Price;
+/// let price: Price = "602.10".parse().unwrap();
+/// assert_eq!(price, Price::new(6021, 1));
+/// assert_eq!(price.to_string(), "602.10");
+///```
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Price {
+    ticks: i64,
+    scale: u32,
+}
+
+impl Price {
+    ///The largest number of decimal places a price may have
+    pub const MAX_SCALE: u32 = 9;
+
+    pub const ZERO: Price = Price { ticks: 0, scale: 0 };
+
+    ///Creates a price of `ticks` ticks of size 10<sup>-scale</sup>
+    pub fn new(ticks: i64, scale: u32) -> Self {
+        assert!(scale <= Self::MAX_SCALE, "price scale {} exceeds {}", scale, Self::MAX_SCALE);
+        Self { ticks, scale }
+    }
+
+    pub fn ticks(&self) -> i64 {
+        self.ticks
+    }
+
+    pub fn scale(&self) -> u32 {
+        self.scale
+    }
+
+    ///Returns the number of ticks of this price at the given scale or `None` if the price has
+    /// more decimal places than the scale allows
+    pub fn ticks_at(&self, scale: u32) -> Option<i64> {
+        if scale >= self.scale {
+            self.ticks.checked_mul(10_i64.pow(scale - self.scale))
+        } else {
+            let divisor = 10_i64.pow(self.scale - scale);
+            if self.ticks % divisor == 0 { Some(self.ticks / divisor) } else { None }
+        }
+    }
+
+    ///Returns the same price expressed at the given scale, or `None` if that would lose precision
+    pub fn with_scale(&self, scale: u32) -> Option<Price> {
+        self.ticks_at(scale).map(|ticks| Price::new(ticks, scale))
+    }
+
+    ///Returns the number of decimal places actually needed to represent the price
+    pub fn precision(&self) -> u32 {
+        let mut scale = self.scale;
+        let mut ticks = self.ticks;
+        while scale > 0 && ticks % 10 == 0 {
+            ticks /= 10;
+            scale -= 1;
+        }
+        scale
+    }
+
+    ///Returns `true` if the price is a whole number of `tick`s. Every price is a multiple of a zero tick
+    pub fn is_multiple_of(&self, tick: Price) -> bool {
+        if tick.is_zero() {
+            return true;
+        }
+        let scale = self.scale.max(tick.scale);
+        self.scaled(scale) % tick.scaled(scale) == 0
+    }
+
+    ///Returns the price halfway between the two prices, with one more decimal place if needed. Decimals
+    /// beyond the largest scale are truncated. A midpoint too large to be held is an error
+    pub fn midpoint(a: Price, b: Price) -> Result<Price, String> {
+        let scale = a.scale.max(b.scale);
+        let sum = a.scaled(scale) + b.scaled(scale);
+        let (ticks, scale) = if sum % 2 == 0 || scale == Self::MAX_SCALE {
+            (sum / 2, scale)
+        } else {
+            (sum * 5, scale + 1)
+        };
+        let ticks = i64::try_from(ticks).map_err(|_| format!("midpoint of {} and {} is out of range", a, b))?;
+        Ok(Price::new(ticks, scale))
+    }
+
+    ///Returns the highest multiple of `tick` at or below the price. A zero tick leaves the price as it is
+    pub fn floor_to(&self, tick: Price) -> Price {
+        if tick.is_zero() {
+            return *self;
+        }
+        let scale = self.scale.max(tick.scale);
+        let tick_ticks = tick.scaled(scale);
+        Price::new((self.scaled(scale).div_euclid(tick_ticks) * tick_ticks) as i64, scale)
+    }
+
+    ///Returns the lowest multiple of `tick` at or above the price. A zero tick leaves the price as it is
+    pub fn ceil_to(&self, tick: Price) -> Price {
+        let floor = self.floor_to(tick);
+        if floor == *self { floor } else { floor + tick }
+    }
+
+    ///Returns `percent` percent of the price. Decimals beyond the largest scale are truncated
+    pub fn percent(&self, percent: Price) -> Price {
+        let scale = self.scale + percent.scale + 2;
+        let ticks = self.ticks as i128 * percent.ticks as i128;
+        if scale > Self::MAX_SCALE {
+            Price::new((ticks / 10_i128.pow(scale - Self::MAX_SCALE)) as i64, Self::MAX_SCALE)
+        } else {
+            Price::new(ticks as i64, scale)
+        }
+    }
+
+    pub fn is_positive(&self) -> bool {
+        self.ticks > 0
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.ticks == 0
+    }
+
+    ///Returns the price as a float. Only meant for display and ratios, never for comparisons
+    pub fn to_f64(&self) -> f64 {
+        self.ticks as f64 / 10_f64.powi(self.scale as i32)
+    }
+
+    fn scaled(&self, scale: u32) -> i128 {
+        self.ticks as i128 * 10_i128.pow(scale - self.scale)
+    }
+}
+
+impl PartialEq for Price {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Price {}
+
+impl PartialOrd for Price {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Price {
+    fn cmp(&self, other: &Self) -> Ordering {
+        let scale = self.scale.max(other.scale);
+        self.scaled(scale).cmp(&other.scaled(scale))
+    }
+}
+
+///Hashes the price at its smallest precision so that equal prices hash alike
+impl Hash for Price {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        let precision = self.precision();
+        self.ticks_at(precision).hash(state);
+        precision.hash(state);
+    }
+}
+
+impl Add for Price {
+    type Output = Price;
+
+    fn add(self, rhs: Self) -> Self::Output {
+        let scale = self.scale.max(rhs.scale);
+        Price::new((self.scaled(scale) + rhs.scaled(scale)) as i64, scale)
+    }
+}
+
+impl Sub for Price {
+    type Output = Price;
+
+    fn sub(self, rhs: Self) -> Self::Output {
+        let scale = self.scale.max(rhs.scale);
+        Price::new((self.scaled(scale) - rhs.scaled(scale)) as i64, scale)
+    }
+}
+
+impl Display for Price {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if self.scale == 0 {
+            return write!(f, "{}", self.ticks);
+        }
+        let divisor = 10_u64.pow(self.scale);
+        let abs = self.ticks.unsigned_abs();
+        let sign = if self.ticks < 0 { "-" } else { "" };
+        write!(f, "{}{}.{:0width$}", sign, abs / divisor, abs % divisor, width = self.scale as usize)
+    }
+}
+
+///Parses a decimal price such as `602`, `602.1` or `-0.25` without going through a float.
+/// The scale of the price is the number of decimal places in the text
+impl FromStr for Price {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let text = s.trim();
+        let (negative, digits) = match text.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, text),
+        };
+        let (int_part, frac_part) = match digits.split_once('.') {
+            Some((int_part, frac_part)) => (int_part, frac_part),
+            None => (digits, ""),
+        };
+        let all_digits = |part: &str| part.chars().all(|c| c.is_ascii_digit());
+        if (int_part.is_empty() && frac_part.is_empty()) || !all_digits(int_part) || !all_digits(frac_part) {
+            return Err(format!("invalid price {}", s));
+        }
+        if frac_part.len() > Self::MAX_SCALE as usize {
+            return Err(format!("price {} has more than {} decimal places", s, Self::MAX_SCALE));
+        }
+        let scale = frac_part.len() as u32;
+        let ticks = format!("{}{}", int_part, frac_part).parse::<i64>()
+            .map_err(|e| format!("invalid price {} {}", s, e))?;
+        Ok(Price::new(if negative { -ticks } else { ticks }, scale))
+    }
+}
+
+impl Serialize for Price {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+///Prices are written as strings so that they round trip exactly. Numbers are accepted as well
+/// and are read from their shortest decimal representation
+impl<'de> Deserialize<'de> for Price {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(PriceVisitor)
+    }
+}
+
+struct PriceVisitor;
+
+impl<'de> Visitor<'de> for PriceVisitor {
+    type Value = Price;
+
+    fn expecting(&self, f: &mut Formatter) -> std::fmt::Result {
+        write!(f, "a decimal price as a string or a number")
+    }
+
+    fn visit_i64<E: Error>(self, v: i64) -> Result<Self::Value, E> {
+        Ok(Price::new(v, 0))
+    }
+
+    fn visit_u64<E: Error>(self, v: u64) -> Result<Self::Value, E> {
+        i64::try_from(v).map(|ticks| Price::new(ticks, 0)).map_err(E::custom)
+    }
+
+    fn visit_f64<E: Error>(self, v: f64) -> Result<Self::Value, E> {
+        if !v.is_finite() {
+            return Err(E::custom(format!("invalid price {}", v)));
+        }
+        v.to_string().parse().map_err(E::custom)
+    }
+
+    fn visit_str<E: Error>(self, v: &str) -> Result<Self::Value, E> {
+        v.parse().map_err(E::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::hash_map::DefaultHasher;
+    use std::hash::{Hash, Hasher};
+
+    use crate::model::price::Price;
+
+    fn hash_of(price: &Price) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        price.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    #[test]
+    fn test_parse_and_display() {
+        let price: Price = "602.1".parse().unwrap();
+        assert_eq!(price.ticks(), 6021);
+        assert_eq!(price.scale(), 1);
+        assert_eq!(price.to_string(), "602.1");
+        assert_eq!("-0.05".parse::<Price>().unwrap().to_string(), "-0.05");
+        assert_eq!("602".parse::<Price>().unwrap().to_string(), "602");
+        assert!("abc".parse::<Price>().is_err());
+        assert!("1.2.3".parse::<Price>().is_err());
+        assert!("0.0000000001".parse::<Price>().is_err());
+    }
+
+    #[test]
+    fn test_equal_values_with_different_scales() {
+        let p1: Price = "602.1".parse().unwrap();
+        let p2: Price = "602.10".parse().unwrap();
+        let p3: Price = "602.10000001".parse().unwrap();
+
+        assert_eq!(p1, p2);
+        assert_eq!(hash_of(&p1), hash_of(&p2));
+        assert_ne!(p1, p3);
+        assert!(p3 > p1);
+        assert_eq!(Price::new(0, 3), Price::ZERO);
+    }
+
+    #[test]
+    fn test_arithmetic_and_rescaling() {
+        let price: Price = "602.15".parse().unwrap();
+        let tick: Price = "0.05".parse().unwrap();
+
+        assert_eq!((price + tick).to_string(), "602.20");
+        assert_eq!((price - tick).to_string(), "602.10");
+        assert_eq!(price.ticks_at(3), Some(602150));
+        assert_eq!(price.ticks_at(1), None);
+        assert_eq!("602.10".parse::<Price>().unwrap().precision(), 1);
+        assert!(price.is_multiple_of(tick));
+        assert!(!price.is_multiple_of("0.1".parse().unwrap()));
+        assert_eq!(Price::new(600, 0).percent("2.5".parse().unwrap()), Price::new(15, 0));
+        assert_eq!(price.percent(Price::new(10, 0)).to_string(), "60.2150");
+    }
+
+    #[test]
+    fn test_midpoint_and_tick_rounding() {
+        let bid: Price = "602.1".parse().unwrap();
+        let ask: Price = "602.4".parse().unwrap();
+        let tick: Price = "0.1".parse().unwrap();
+
+        let mid = Price::midpoint(bid, ask).unwrap();
+        assert_eq!(mid.to_string(), "602.25");
+        assert_eq!(Price::midpoint(bid, "602.3".parse().unwrap()).unwrap().to_string(), "602.2");
+        assert_eq!(mid.floor_to(tick), "602.2".parse().unwrap());
+        assert_eq!(mid.ceil_to(tick), "602.3".parse().unwrap());
+        assert_eq!(bid.ceil_to(tick), bid);
+        assert_eq!(mid.floor_to(Price::ZERO), mid);
+    }
+
+    #[test]
+    fn test_midpoint_at_the_largest_scale() {
+        let bid: Price = "602.000000001".parse().unwrap();
+        let ask: Price = "602.000000004".parse().unwrap();
+
+        assert_eq!(Price::midpoint(bid, ask).unwrap(), "602.000000002".parse().unwrap());
+        assert_eq!(Price::midpoint(bid, "602.000000003".parse().unwrap()).unwrap(), "602.000000002".parse().unwrap());
+        assert!(Price::midpoint(Price::new(i64::MAX, 0), Price::new(i64::MAX - 2, 0)).is_ok());
+        assert!(Price::midpoint(Price::new(i64::MAX, 0), Price::new(i64::MAX - 1, 0)).is_err());
+    }
+
+    #[test]
+    fn test_json_round_trip() {
+        let price: Price = "602.123456789".parse().unwrap();
+        let json = serde_json::to_string(&price).unwrap();
+        assert_eq!(serde_json::from_str::<Price>(&json).unwrap(), price);
+        assert_eq!(serde_json::from_str::<Price>("602.1").unwrap(), Price::new(6021, 1));
+        assert_eq!(serde_json::from_str::<Price>("602").unwrap(), Price::new(602, 0));
+    }
+}
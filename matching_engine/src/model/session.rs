@@ -0,0 +1,210 @@
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+use chrono::NaiveTime;
+use serde::{Deserialize, Serialize};
+
+///Trading phase of a symbol. Orders are matched continuously only in the `Continuous` phase. In the
+/// pre-open, the auctions and while halted they are accepted and queued in the book without matching,
+/// and once the symbol is closed new orders are rejected
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum TradingPhase {
+    PreOpen,
+    OpeningAuction,
+    #[default]
+    Continuous,
+    Halted,
+    ClosingAuction,
+    Closed,
+}
+
+impl TradingPhase {
+    pub fn string_value(&self) -> String {
+        match self {
+            TradingPhase::PreOpen => "PreOpen",
+            TradingPhase::OpeningAuction => "OpeningAuction",
+            TradingPhase::Continuous => "Continuous",
+            TradingPhase::Halted => "Halted",
+            TradingPhase::ClosingAuction => "ClosingAuction",
+            TradingPhase::Closed => "Closed",
+        }.to_string()
+    }
+
+    ///Returns `true` if new orders are accepted, whether they are matched or queued
+    pub fn accepts_orders(&self) -> bool {
+        *self != TradingPhase::Closed
+    }
+
+    ///Returns `true` if the orders of the symbol are matched as they arrive
+    pub fn is_matching(&self) -> bool {
+        *self == TradingPhase::Continuous
+    }
+
+    ///Returns `true` if the orders queued in this phase are uncrossed in a call auction when the symbol
+    /// moves on to continuous trading or closes. A halted symbol resumes through a volatility auction
+    pub fn is_auction(&self) -> bool {
+        matches!(self, TradingPhase::OpeningAuction | TradingPhase::Halted | TradingPhase::ClosingAuction)
+    }
+
+    ///Returns `true` if a symbol in this phase can move to `next`. Any open symbol can be halted or
+    /// closed, the opening auction follows the pre-open, the closing auction follows continuous trading
+    /// and a closed symbol can only go back to the pre-open of the next session
+    pub fn can_move_to(&self, next: TradingPhase) -> bool {
+        use TradingPhase::*;
+        match (self, next) {
+            (Closed, PreOpen) => true,
+            (Closed, _) => false,
+            (current, next) if *current == next => false,
+            (_, Halted) | (_, Closed) => true,
+            (PreOpen, OpeningAuction) => true,
+            (OpeningAuction, Continuous) => true,
+            (Continuous, ClosingAuction) => true,
+            (Halted, OpeningAuction) | (Halted, Continuous) | (Halted, ClosingAuction) => true,
+            _ => false,
+        }
+    }
+}
+
+impl Display for TradingPhase {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.string_value())
+    }
+}
+
+impl FromStr for TradingPhase {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_uppercase().as_str() {
+            "PREOPEN" => Ok(TradingPhase::PreOpen),
+            "OPENINGAUCTION" | "OPEN" => Ok(TradingPhase::OpeningAuction),
+            "CONTINUOUS" => Ok(TradingPhase::Continuous),
+            "HALTED" | "HALT" => Ok(TradingPhase::Halted),
+            "CLOSINGAUCTION" => Ok(TradingPhase::ClosingAuction),
+            "CLOSED" | "CLOSE" => Ok(TradingPhase::Closed),
+            _ => Err(format!("invalid trading phase {}", s)),
+        }
+    }
+}
+
+///Status event published when a symbol moves from one trading phase to another
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PhaseChange {
+    symbol: String,
+    from: TradingPhase,
+    to: TradingPhase,
+}
+
+impl PhaseChange {
+    pub fn new(symbol: String, from: TradingPhase, to: TradingPhase) -> Self {
+        Self { symbol, from, to }
+    }
+
+    pub fn symbol(&self) -> &str {
+        &self.symbol
+    }
+
+    pub fn from(&self) -> TradingPhase {
+        self.from
+    }
+
+    pub fn to(&self) -> TradingPhase {
+        self.to
+    }
+}
+
+impl Display for PhaseChange {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {} -> {}", self.symbol, self.from, self.to)
+    }
+}
+
+///A phase transition of a symbol due at a time of day
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScheduleEntry {
+    time: NaiveTime,
+    symbol: String,
+    phase: TradingPhase,
+}
+
+impl ScheduleEntry {
+    pub fn new(time: NaiveTime, symbol: String, phase: TradingPhase) -> Self {
+        Self { time, symbol, phase }
+    }
+
+    pub fn time(&self) -> NaiveTime {
+        self.time
+    }
+
+    pub fn symbol(&self) -> &str {
+        &self.symbol
+    }
+
+    pub fn phase(&self) -> TradingPhase {
+        self.phase
+    }
+}
+
+///The phase transitions of a trading day in time order. Each entry is handed out once, the first time
+/// the schedule is polled at or after its time
+#[derive(Debug, Clone, Default)]
+pub struct SessionSchedule {
+    entries: Vec<ScheduleEntry>,
+    next: usize,
+}
+
+impl SessionSchedule {
+    pub fn new(mut entries: Vec<ScheduleEntry>) -> Self {
+        entries.sort_by_key(|entry| entry.time);
+        Self { entries, next: 0 }
+    }
+
+    pub fn entries(&self) -> &[ScheduleEntry] {
+        &self.entries
+    }
+
+    ///Returns the entries that became due since the last poll, up to and including `now`
+    pub fn due(&mut self, now: NaiveTime) -> Vec<ScheduleEntry> {
+        let due = self.entries[self.next..].iter().take_while(|entry| entry.time <= now).count();
+        let entries = self.entries[self.next..self.next + due].to_vec();
+        self.next += due;
+        entries
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveTime;
+
+    use crate::model::session::{ScheduleEntry, SessionSchedule};
+    use crate::model::session::TradingPhase::{ClosingAuction, Closed, Continuous, Halted, OpeningAuction, PreOpen};
+
+    #[test]
+    fn test_phase_transitions() {
+        assert!(PreOpen.can_move_to(OpeningAuction));
+        assert!(OpeningAuction.can_move_to(Continuous));
+        assert!(Continuous.can_move_to(Halted));
+        assert!(Halted.can_move_to(Continuous));
+        assert!(Continuous.can_move_to(ClosingAuction));
+        assert!(ClosingAuction.can_move_to(Closed));
+        assert!(Closed.can_move_to(PreOpen));
+        assert!(!PreOpen.can_move_to(Continuous));
+        assert!(!Closed.can_move_to(Continuous));
+        assert!(!Continuous.can_move_to(Continuous));
+        assert!(!Closed.accepts_orders());
+        assert!(!Halted.is_matching());
+    }
+
+    #[test]
+    fn test_schedule_hands_out_due_entries_once() {
+        let time = |h, m| NaiveTime::from_hms_opt(h, m, 0).unwrap();
+        let mut schedule = SessionSchedule::new(vec![
+            ScheduleEntry::new(time(9, 30), "IBM".to_string(), Continuous),
+            ScheduleEntry::new(time(9, 0), "IBM".to_string(), OpeningAuction),
+        ]);
+        assert!(schedule.due(time(8, 0)).is_empty());
+        let due = schedule.due(time(9, 30));
+        assert_eq!(due.iter().map(|entry| entry.phase()).collect::<Vec<_>>(), vec![OpeningAuction, Continuous]);
+        assert!(schedule.due(time(10, 0)).is_empty());
+    }
+}
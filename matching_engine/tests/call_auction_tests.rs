@@ -0,0 +1,126 @@
+use matching_engine::common::utils::create_order_book;
+use matching_engine::matchers::auction::CallAuction;
+use matching_engine::matchers::fifo_matcher::FIFOMatcher;
+use matching_engine::matchers::matcher::Matcher;
+use matching_engine::model::domain::Fill;
+use matching_engine::model::domain::Side::{Buy, Sell};
+use matching_engine::model::domain::Status::Cancelled;
+use matching_engine::model::price::Price;
+use matching_engine::model::session::TradingPhase::{Continuous, Halted};
+
+#[test]
+fn test_orders_accumulate_during_the_auction_and_uncross_at_a_single_price() {
+    let input = vec![
+        "id1 IBM 100 602 Buy".to_string(),
+        "id2 IBM 100 602 Buy".to_string(),
+        "id3 IBM 150 601 Sell".to_string(),
+        "id4 IBM 50 MKT Buy".to_string(),
+        "id5 TATA 10 600 Buy".to_string(),
+        "id6 TATA 10 600 Sell".to_string(),
+    ];
+    let mut order_book = create_order_book(input);
+    order_book.set_phase("IBM", Halted).unwrap();
+
+    let fills = FIFOMatcher.match_order_book(&mut order_book);
+    assert_eq!(fills.len(), 2);
+    assert!(fills.iter().all(|fill| fill.symbol() == "TATA"));
+    assert_eq!(order_book.immediate_orders().len(), 1);
+    assert_eq!(order_book.best_price("IBM", Buy), Some(Price::new(602, 0)));
+
+    let uncrossing = CallAuction::uncrossing(&order_book, "IBM").unwrap();
+    assert_eq!(uncrossing.price(), Price::new(601, 0));
+    assert_eq!(uncrossing.volume(), 150);
+
+    let fills = CallAuction::default().change_phase(&mut order_book, "IBM", Continuous, &mut FIFOMatcher).unwrap();
+    assert!(fills.iter().all(|fill| fill.price() == Price::new(601, 0)));
+    let buy_fills: Vec<(&str, u32)> = fills.iter().filter(|f| f.side() == Buy).map(|f| (f.cl_ord_id(), f.qty())).collect();
+    assert_eq!(buy_fills, vec![("id4", 50), ("id1", 100)]);
+    let sell_fills: Vec<Fill> = fills.into_iter().filter(|f| f.side() == Sell).collect();
+    assert_eq!(sell_fills.last().unwrap().cum_qty(), 150);
+    assert_eq!(sell_fills.last().unwrap().leaves_qty(), 0);
+
+    assert!(order_book.is_matching("IBM"));
+    assert_eq!(order_book.find_order("id2", None, None).unwrap().qty(), 100);
+    assert!(order_book.levels("IBM", Sell).is_none());
+    assert!(order_book.immediate_orders().is_empty());
+}
+
+#[test]
+fn test_market_orders_left_over_by_the_auction_are_cancelled() {
+    let input = vec![
+        "id1 IBM 100 MKT Buy".to_string(),
+        "id2 IBM 40 601 Sell".to_string(),
+    ];
+    let mut order_book = create_order_book(input);
+    order_book.set_phase("IBM", Halted).unwrap();
+
+    let fills = CallAuction::default().uncross(&mut order_book, "IBM");
+    assert_eq!(fills.len(), 3);
+    assert_eq!(fills[0].qty(), 40);
+    assert_eq!(fills[2].cl_ord_id(), "id1");
+    assert_eq!(fills[2].cum_qty(), 40);
+    assert_eq!(fills[2].leaves_qty(), 0);
+    assert!(order_book.is_empty());
+}
+
+#[test]
+fn test_orders_the_auction_cannot_fill_as_required_are_left_out_of_it() {
+    let input = vec![
+        "id1 IBM 100 602 Buy tif=FOK".to_string(),
+        "id2 IBM 100 601 Buy aon=Y".to_string(),
+        "id3 IBM 60 600 Sell".to_string(),
+        "id4 IBM 50 600 Buy minqty=40".to_string(),
+    ];
+    let mut order_book = create_order_book(input);
+    order_book.set_phase("IBM", Halted).unwrap();
+
+    let fills = CallAuction::default().change_phase(&mut order_book, "IBM", Continuous, &mut FIFOMatcher).unwrap();
+    let trades: Vec<(&str, u32, Price)> = fills.iter().filter(|f| f.qty() > 0).map(|f| (f.cl_ord_id(), f.qty(), f.price())).collect();
+    assert_eq!(trades, vec![("id4", 50, Price::new(600, 0)), ("id3", 50, Price::new(600, 0))]);
+    assert_eq!(fills.last().unwrap().cl_ord_id(), "id1");
+    assert_eq!(*fills.last().unwrap().status(), Cancelled);
+    assert_eq!(order_book.find_order("id2", None, None).unwrap().qty(), 100);
+    assert_eq!(order_book.find_order("id3", None, None).unwrap().qty(), 10);
+    assert!(order_book.find_order("id1", None, None).is_none());
+}
+
+#[test]
+fn test_self_trade_prevention_applies_in_the_auction() {
+    let input = vec![
+        "id1 IBM 100 601 Buy participant=P1".to_string(),
+        "id2 IBM 100 600 Sell participant=P1".to_string(),
+        "id3 IBM 100 600 Sell participant=P2".to_string(),
+    ];
+    let mut order_book = create_order_book(input);
+    order_book.set_phase("IBM", Halted).unwrap();
+
+    let fills = CallAuction::default().change_phase(&mut order_book, "IBM", Continuous, &mut FIFOMatcher).unwrap();
+    assert_eq!(fills[0].cl_ord_id(), "id2");
+    assert_eq!(*fills[0].status(), Cancelled);
+    let trades: Vec<(&str, String, u32)> = fills.iter().filter(|f| f.qty() > 0).map(|f| (f.cl_ord_id(), f.secondary_cl_ord_id(), f.qty())).collect();
+    assert_eq!(trades, vec![("id1", "id3".to_string(), 100), ("id3", "id1".to_string(), 100)]);
+    assert!(order_book.is_empty());
+}
+
+#[test]
+fn test_stops_triggered_by_the_uncrossing_trade_once_the_symbol_is_continuous() {
+    let input = vec![
+        "id1 IBM 100 601 Buy".to_string(),
+        "id2 IBM 100 601 Sell".to_string(),
+        "id3 IBM 50 MKT Buy stop=601".to_string(),
+        "id4 IBM 50 605 Sell".to_string(),
+    ];
+    let mut order_book = create_order_book(input);
+    order_book.set_phase("IBM", Halted).unwrap();
+
+    let fills = CallAuction::default().change_phase(&mut order_book, "IBM", Continuous, &mut FIFOMatcher).unwrap();
+    let trades: Vec<(&str, u32, Price)> = fills.iter().filter(|f| f.qty() > 0).map(|f| (f.cl_ord_id(), f.qty(), f.price())).collect();
+    assert_eq!(trades, vec![
+        ("id1", 100, Price::new(601, 0)),
+        ("id2", 100, Price::new(601, 0)),
+        ("id3", 50, Price::new(605, 0)),
+        ("id4", 50, Price::new(605, 0)),
+    ]);
+    assert!(order_book.is_empty());
+    assert!(order_book.immediate_orders().is_empty());
+}
@@ -0,0 +1,146 @@
+use std::str::FromStr;
+
+use serde_json::Value;
+
+use matching_engine::common::id_generator::SequentialIdGenerator;
+use matching_engine::common::utils::{create_order_from_string, read_input};
+use matching_engine::matchers::fifo_matcher::FIFOMatcher;
+use matching_engine::matchers::matcher::Matcher;
+use matching_engine::matchers::matcher_registry::MatcherRegistry;
+use matching_engine::matchers::pipeline_matcher::{AllocationStep, PipelineMatcher};
+use matching_engine::matchers::prorata_matcher::ProrataMatcher;
+use matching_engine::model::domain::OrderBook;
+
+const RUNS: usize = 100;
+
+fn mixed_input() -> Vec<String> {
+    let mut input = read_input("test_data/orders.txt");
+    input.extend([
+        "id11 TATA 100 50 Sell",
+        "id12 TATA 200 49.5 Sell",
+        "id13 TATA 250 51 Buy",
+        "id14 INFY 100 1500 Buy",
+        "id15 INFY 50 MKT Sell",
+        "id16 INFY 80 1499 Sell tif=IOC",
+        "id17 IBM 100 MKT Buy",
+    ].map(String::from));
+    input
+}
+
+/// Returns an order book holding the orders of the input, whose ids are drawn from a generator with the
+/// prefix SIM and seeded with `seed`
+fn create_seeded_order_book(input: &[String], seed: u64) -> OrderBook {
+    let mut order_book = OrderBook::default();
+    order_book.set_id_generator(Box::new(SequentialIdGenerator::new(Some("SIM".to_string()), None).seeded(seed)));
+    for line in input {
+        order_book.add_order_to_order_book(create_order_from_string(line.clone()));
+    }
+    order_book
+}
+
+/// Returns the outcome of matching the input with a freshly seeded id generator as the serialized fills,
+/// ids included, and the order book that is left
+fn replay(matcher: &mut dyn Matcher, input: &[String]) -> (Vec<Value>, String) {
+    let mut order_book = create_seeded_order_book(input, 1000);
+    let fills = matcher.match_order_book(&mut order_book);
+    let fills = fills.iter().map(|fill| serde_json::to_value(fill).unwrap()).collect();
+    (fills, format!("{:?}", order_book))
+}
+
+fn assert_deterministic(create: impl Fn() -> Box<dyn Matcher>, input: &[String]) {
+    let expected = replay(create().as_mut(), input);
+    assert!(!expected.0.is_empty());
+    for run in 1..RUNS {
+        assert_eq!(replay(create().as_mut(), input), expected, "run {} differs from the first run", run);
+    }
+}
+
+#[test]
+fn test_fifo_matching_is_deterministic() {
+    assert_deterministic(|| Box::new(FIFOMatcher), &read_input("test_data/orders.txt"));
+    assert_deterministic(|| Box::new(FIFOMatcher), &mixed_input());
+}
+
+#[test]
+fn test_pro_rata_matching_is_deterministic() {
+    assert_deterministic(|| Box::new(ProrataMatcher::default()), &read_input("test_data/orders.txt"));
+    assert_deterministic(|| Box::new(ProrataMatcher::default()), &mixed_input());
+}
+
+#[test]
+fn test_pipeline_and_registry_matching_is_deterministic() {
+    let pipeline = || {
+        let steps = vec![AllocationStep::TopOrder { max_qty: 50 }, AllocationStep::Fifo { percent: 40 }, AllocationStep::ProRata];
+        Box::new(PipelineMatcher::new(steps, ProrataMatcher::default())) as Box<dyn Matcher>
+    };
+    assert_deterministic(pipeline, &mixed_input());
+    assert_deterministic(|| Box::new(MatcherRegistry::from_str("FIFO,TATA=PRO").unwrap()), &mixed_input());
+}
+
+#[test]
+fn test_both_fills_of_a_trade_share_the_trade_id() {
+    let mut order_book = create_seeded_order_book(&["id1 IBM 100 10 Sell".to_string(), "id2 IBM 100 10 Buy".to_string()], 0);
+    let fills = FIFOMatcher.match_order_book(&mut order_book);
+    assert_eq!(fills.len(), 2);
+    assert_eq!(fills[0].trade_id(), Some("SIM-T1"));
+    assert_eq!(fills[1].trade_id(), Some("SIM-T1"));
+    assert_ne!(fills[0].execution_id(), fills[1].execution_id());
+    assert!(fills.iter().all(|fill| fill.execution_id().starts_with("SIM-E")));
+}
+
+#[test]
+fn test_every_report_of_an_order_carries_the_order_id_it_was_accepted_with() {
+    let mut order_book = create_seeded_order_book(&[
+        "id1 IBM 100 10 Sell".to_string(),
+        "id2 IBM 40 10 Buy".to_string(),
+        "id3 IBM 60 10 Buy".to_string(),
+    ], 0);
+    let order_id = order_book.find_order("id1", None, None).unwrap().order_id().to_string();
+    let fills = FIFOMatcher.match_order_book(&mut order_book);
+    let id1_fills: Vec<_> = fills.iter().filter(|fill| fill.cl_ord_id() == "id1").collect();
+    assert_eq!(id1_fills.len(), 2);
+    assert_eq!(order_id, "SIM-O1");
+    assert!(id1_fills.iter().all(|fill| fill.order_id() == order_id));
+    assert_ne!(id1_fills[0].execution_id(), id1_fills[1].execution_id());
+    assert_ne!(fills[0].order_id(), fills[1].order_id());
+}
+
+#[test]
+fn test_every_fill_of_an_order_sweeping_the_book_has_its_own_execution_id() {
+    let mut order_book = create_seeded_order_book(&[
+        "id1 IBM 40 10 Sell".to_string(),
+        "id2 IBM 60 10 Sell".to_string(),
+        "id3 IBM 100 10 Buy".to_string(),
+    ], 0);
+    let fills = FIFOMatcher.match_order_book(&mut order_book);
+    assert_eq!(fills.iter().filter(|fill| fill.cl_ord_id() == "id3").count(), 2);
+    let mut execution_ids: Vec<&str> = fills.iter().map(|fill| fill.execution_id()).collect();
+    execution_ids.sort();
+    execution_ids.dedup();
+    assert_eq!(execution_ids.len(), fills.len());
+}
+
+#[test]
+fn test_execution_ids_are_consecutive_in_report_order() {
+    let input: Vec<String> = [
+        "id1 IBM 40 10 Sell",
+        "id2 IBM 60 10.5 Sell",
+        "id3 IBM 50 11 Sell",
+        "id4 IBM 120 11 Buy",
+        "id5 TATA 100 50 Buy",
+        "id6 TATA 100 50 Buy",
+        "id7 TATA 150 MKT Sell",
+        "id8 TATA 100 50 Sell tif=FOK",
+        "id9 TATA 50 50 Sell",
+    ].map(String::from).to_vec();
+    let pipeline = PipelineMatcher::new(vec![AllocationStep::Fifo { percent: 50 }, AllocationStep::ProRata], ProrataMatcher::default());
+    let matchers: Vec<Box<dyn Matcher>> = vec![Box::new(FIFOMatcher), Box::new(ProrataMatcher::default()), Box::new(pipeline)];
+    for mut matcher in matchers {
+        let mut order_book = create_seeded_order_book(&input, 0);
+        let fills = matcher.match_order_book(&mut order_book);
+        let execution_ids: Vec<&str> = fills.iter().map(|fill| fill.execution_id()).collect();
+        let expected: Vec<String> = (1..=fills.len()).map(|n| format!("SIM-E{}", n)).collect();
+        assert!(fills.len() > 8);
+        assert_eq!(execution_ids, expected);
+    }
+}
@@ -1,8 +1,13 @@
 use matching_engine::common::utils::{create_order_book, create_order_from_string, read_input};
 use matching_engine::matchers::fifo_matcher::FIFOMatcher;
 use matching_engine::matchers::matcher::Matcher;
-use matching_engine::model::domain::{Fill, OrderBook, OrderBookKey};
+use matching_engine::model::cancel::CancelRejectReason::{InvalidRequest, TooLateToCancel, UnknownOrder};
+use matching_engine::model::cancel::ReplaceRequest;
+use matching_engine::model::domain::{Fill, Liquidity, OrderBook, OrderBookKey};
 use matching_engine::model::domain::Side::{Buy, Sell};
+use matching_engine::model::domain::Status;
+use matching_engine::model::domain::Status::{Cancelled, PartialFill, Rejected, Replaced, Restated};
+use matching_engine::model::price::Price;
 
 
 #[test]
@@ -21,21 +26,393 @@ fn test_fifo_match_order_book(){
 
     assert_eq!(buy.len(),3);
     assert_eq!(sell.len(),1);
+    // id2 trades with id1 and id4 with id3 as they arrive, nothing that arrives later crosses
     assert_eq!(fills.len(),4);
 }
 
+#[test]
+fn test_buy_order_sweeps_sell_levels_at_or_below_limit() {
+    let input = vec![
+        "id1 IBM 100 602 Sell".to_string(),
+        "id2 IBM 100 602.5 Sell".to_string(),
+        "id3 IBM 100 603 Sell".to_string(),
+        "id4 IBM 150 602.5 Buy".to_string(),
+    ];
+    let mut order_book = create_order_book(input);
+    let mut fifo = FIFOMatcher;
+    let fills = fifo.match_order_book(&mut order_book);
+    let client_fills: Vec<Fill> = fills.clone().into_iter().filter(|f| f.side() == Buy).collect();
+
+    assert_eq!(client_fills.len(), 2);
+    assert_eq!(client_fills[0].secondary_cl_ord_id(), "id1");
+    assert_eq!(client_fills[0].price(), Price::new(602, 0));
+    assert_eq!(client_fills[0].qty(), 100);
+    assert_eq!(client_fills[1].secondary_cl_ord_id(), "id2");
+    assert_eq!(client_fills[1].price(), Price::new(6025, 1));
+    assert_eq!(client_fills[1].qty(), 50);
+    assert_eq!(client_fills[1].cum_qty(), 150);
+
+    let sell = order_book.get_orders_for(Sell);
+    assert_eq!(sell.len(), 2);
+    assert_eq!(sell.get(&OrderBookKey::new(Price::new(6025, 1), "IBM".to_string())).unwrap()[0].qty(), 50);
+    assert!(order_book.get_orders_for(Buy).is_empty());
+}
+
+#[test]
+fn test_market_order_sweeps_contra_side_and_cancels_remainder() {
+    let input = vec![
+        "id1 IBM 100 601 Buy".to_string(),
+        "id2 IBM 100 602 Buy".to_string(),
+        "id3 IBM 250 MKT Sell".to_string(),
+    ];
+    let mut order_book = create_order_book(input);
+    let mut fifo = FIFOMatcher;
+    let fills = fifo.match_order_book(&mut order_book);
+    let market_fills: Vec<Fill> = fills.clone().into_iter().filter(|f| f.cl_ord_id() == "id3").collect();
+
+    assert_eq!(market_fills.len(), 3);
+    assert_eq!(market_fills[0].secondary_cl_ord_id(), "id2");
+    assert_eq!(market_fills[0].price(), Price::new(602, 0));
+    assert_eq!(market_fills[1].secondary_cl_ord_id(), "id1");
+    assert_eq!(market_fills[1].price(), Price::new(601, 0));
+    assert_eq!(market_fills[1].cum_qty(), 200);
+    assert_eq!(*market_fills[2].status(), Cancelled);
+    assert_eq!(market_fills[2].cum_qty(), 200);
+    assert_eq!(market_fills[2].leaves_qty(), 0);
+    assert!(order_book.is_empty());
+}
+
+#[test]
+fn test_market_order_against_empty_book_is_cancelled() {
+    let input = vec![
+        "id1 IBM 100 601 Buy".to_string(),
+        "id2 IBM 100 MKT Buy".to_string(),
+    ];
+    let mut order_book = create_order_book(input);
+    let mut fifo = FIFOMatcher;
+    let fills = fifo.match_order_book(&mut order_book);
+
+    assert_eq!(fills.len(), 1);
+    assert_eq!(*fills[0].status(), Cancelled);
+    assert_eq!(fills[0].cum_qty(), 0);
+    assert_eq!(order_book.get_orders_for(Buy).len(), 1);
+}
+
+#[test]
+fn test_ioc_order_remainder_is_cancelled() {
+    let input = vec![
+        "id1 IBM 100 601 Sell".to_string(),
+        "id2 IBM 100 603 Sell".to_string(),
+        "id3 IBM 150 602 Buy tif=IOC".to_string(),
+    ];
+    let mut order_book = create_order_book(input);
+    let mut fifo = FIFOMatcher;
+    let fills = fifo.match_order_book(&mut order_book);
+    let ioc_fills: Vec<Fill> = fills.into_iter().filter(|f| f.cl_ord_id() == "id3").collect();
+
+    assert_eq!(ioc_fills.len(), 2);
+    assert_eq!(ioc_fills[0].qty(), 100);
+    assert_eq!(ioc_fills[0].price(), Price::new(601, 0));
+    assert_eq!(*ioc_fills[1].status(), Cancelled);
+    assert_eq!(ioc_fills[1].cum_qty(), 100);
+    assert!(order_book.get_orders_for(Buy).is_empty());
+    assert_eq!(order_book.get_orders_for(Sell).len(), 1);
+}
+
+#[test]
+fn test_fok_order_is_filled_completely_or_not_at_all() {
+    let input = vec![
+        "id1 IBM 100 601 Sell".to_string(),
+        "id2 IBM 100 603 Sell".to_string(),
+        "id3 IBM 150 602 Buy tif=FOK".to_string(),
+        "id4 IBM 150 603 Buy tif=FOK".to_string(),
+    ];
+    let mut order_book = create_order_book(input);
+    let mut fifo = FIFOMatcher;
+    let fills = fifo.match_order_book(&mut order_book);
+    let killed: Vec<Fill> = fills.clone().into_iter().filter(|f| f.cl_ord_id() == "id3").collect();
+    let filled: Vec<Fill> = fills.into_iter().filter(|f| f.cl_ord_id() == "id4").collect();
+
+    assert_eq!(killed.len(), 1);
+    assert_eq!(*killed[0].status(), Cancelled);
+    assert_eq!(killed[0].cum_qty(), 0);
+    assert_eq!(filled.len(), 2);
+    assert_eq!(filled[1].cum_qty(), 150);
+    assert_eq!(filled[1].leaves_qty(), 0);
+    assert_eq!(order_book.get_orders_for(Sell).values().next().unwrap()[0].qty(), 50);
+    assert!(order_book.get_orders_for(Buy).is_empty());
+}
+
+fn first_buy_at_601(order_book: &OrderBook) -> String {
+    let key = OrderBookKey::new(Price::new(601, 0), "IBM".to_string());
+    order_book.get_orders_for(Buy).get(&key).unwrap()[0].cl_ord_id().clone()
+}
+
+#[test]
+fn test_replace_keeps_priority_only_for_quantity_decrease() {
+    let input = vec![
+        "id1 IBM 100 601 Buy".to_string(),
+        "id2 IBM 100 601 Buy".to_string(),
+        "id3 IBM 100 603 Sell".to_string(),
+    ];
+    let mut order_book = create_order_book(input);
+    let mut fifo = FIFOMatcher;
+
+    let reports = fifo.replace_order(&mut order_book, &ReplaceRequest::new("id1".to_string(), Some(50), None)).unwrap();
+    assert_eq!(reports.len(), 1);
+    assert_eq!(*reports[0].exec_type(), Replaced);
+    assert_eq!(reports[0].leaves_qty(), 50);
+    assert_eq!(first_buy_at_601(&order_book), "id1");
+
+    fifo.replace_order(&mut order_book, &ReplaceRequest::new("id1".to_string(), Some(150), None)).unwrap();
+    assert_eq!(first_buy_at_601(&order_book), "id2");
+
+    let reports = fifo.replace_order(&mut order_book, &ReplaceRequest::new("id2".to_string(), None, Some(Price::new(603, 0)))).unwrap();
+    assert_eq!(reports.len(), 3);
+    assert_eq!(reports[1].cl_ord_id(), "id2");
+    assert_eq!(reports[1].qty(), 100);
+    assert_eq!(reports[1].price(), Price::new(603, 0));
+    assert_eq!(first_buy_at_601(&order_book), "id1");
+}
+
+#[test]
+fn test_replace_is_rejected_for_filled_or_invalid_orders() {
+    let input = vec![
+        "id1 IBM 100 601 Buy".to_string(),
+        "id2 IBM 60 601 Sell".to_string(),
+        "id3 IBM 60 600 Buy".to_string(),
+    ];
+    let mut order_book = create_order_book(input);
+    let mut fifo = FIFOMatcher;
+    fifo.match_order_book(&mut order_book);
+
+    let below_filled = ReplaceRequest::new("id1".to_string(), Some(60), None);
+    assert_eq!(fifo.replace_order(&mut order_book, &below_filled).unwrap_err().reason(), InvalidRequest);
+    let filled = ReplaceRequest::new("id2".to_string(), Some(100), None);
+    assert_eq!(fifo.replace_order(&mut order_book, &filled).unwrap_err().reason(), TooLateToCancel);
+    let unknown = ReplaceRequest::new("id9".to_string(), Some(100), None);
+    assert_eq!(fifo.replace_order(&mut order_book, &unknown).unwrap_err().reason(), UnknownOrder);
+
+    let report = order_book.replace_order(&ReplaceRequest::new("id1".to_string(), Some(80), None)).unwrap();
+    assert_eq!(report.cum_qty(), 60);
+    assert_eq!(report.leaves_qty(), 20);
+    assert_eq!(*report.status(), PartialFill);
+}
+
+#[test]
+fn test_iceberg_peak_is_refreshed_at_the_back_of_the_queue() {
+    let input = vec![
+        "id1 IBM 300 601 Sell display=100".to_string(),
+        "id2 IBM 100 601 Sell".to_string(),
+        "id3 IBM 150 601 Buy".to_string(),
+    ];
+    let mut order_book = create_order_book(input);
+    let depth = order_book.print_market_depth_for("IBM");
+    assert!(depth.contains("| IBM    | 200      | 601   | Sell |"));
+
+    let mut fifo = FIFOMatcher;
+    let fills = fifo.match_order_book(&mut order_book);
+    let client_fills: Vec<Fill> = fills.into_iter().filter(|f| f.side() == Buy).collect();
+
+    assert_eq!(client_fills.len(), 2);
+    assert_eq!(client_fills[0].secondary_cl_ord_id(), "id1");
+    assert_eq!(client_fills[0].qty(), 100);
+    assert_eq!(client_fills[1].secondary_cl_ord_id(), "id2");
+    assert_eq!(client_fills[1].qty(), 50);
+
+    let sell = order_book.get_orders_for(Sell);
+    let queue = sell.get(&OrderBookKey::new(Price::new(601, 0), "IBM".to_string())).unwrap();
+    assert_eq!(queue[0].cl_ord_id(), "id2");
+    assert_eq!(queue[1].cl_ord_id(), "id1");
+    assert_eq!(queue[1].qty(), 200);
+    assert_eq!(queue[1].visible_qty(), 100);
+}
+
+#[test]
+fn test_stop_orders_cascade_in_arrival_order() {
+    let input = vec![
+        "id1 IBM 100 600 Buy".to_string(),
+        "id2 IBM 100 599 Buy".to_string(),
+        "id3 IBM 100 598 Buy".to_string(),
+        "id4 IBM 100 MKT Sell stop=599".to_string(),
+        "id5 IBM 100 MKT Sell stop=600".to_string(),
+        "id6 IBM 100 598 Sell stop=597".to_string(),
+    ];
+    let mut order_book = create_order_book(input);
+    assert_eq!(order_book.stop_orders().len(), 3);
+    assert!(order_book.get_orders_for(Sell).is_empty());
+
+    order_book.add_order_to_order_book(create_order_from_string("id7 IBM 100 600 Sell".to_string()));
+    let mut fifo = FIFOMatcher;
+    let fills = fifo.match_order_book(&mut order_book);
+    let trades: Vec<(String, String, Price)> = fills.iter()
+        .filter(|f| f.side() == Sell && f.qty() > 0)
+        .map(|f| (f.cl_ord_id().to_string(), f.secondary_cl_ord_id().to_string(), f.price()))
+        .collect();
+
+    assert_eq!(trades, vec![
+        ("id7".to_string(), "id1".to_string(), Price::new(600, 0)),
+        ("id5".to_string(), "id2".to_string(), Price::new(599, 0)),
+        ("id4".to_string(), "id3".to_string(), Price::new(598, 0)),
+    ]);
+    assert_eq!(order_book.stop_orders().len(), 1);
+    assert_eq!(order_book.stop_orders()[0].cl_ord_id(), "id6");
+    assert!(order_book.get_orders_for(Buy).is_empty());
+}
+
+#[test]
+fn test_pegged_orders_are_repriced_and_matched_when_the_bbo_moves() {
+    let input = vec![
+        "id1 IBM 100 601 Buy".to_string(),
+        "id2 IBM 100 603 Sell".to_string(),
+        "id3 IBM 50 PEG Buy peg=MID".to_string(),
+    ];
+    let mut order_book = create_order_book(input);
+    let mut fifo = FIFOMatcher;
+    assert!(fifo.match_order_book(&mut order_book).is_empty());
+    assert_eq!(order_book.best_price("IBM", Buy), Some(Price::new(602, 0)));
+
+    order_book.add_order_to_order_book(create_order_from_string("id4 IBM 30 PEG Sell peg=MID".to_string()));
+    let fills: Vec<Fill> = fifo.match_order_book(&mut order_book).into_iter().filter(|f| f.side() == Buy).collect();
+    assert_eq!(fills.len(), 1);
+    assert_eq!(fills[0].cl_ord_id(), "id3");
+    assert_eq!(fills[0].secondary_cl_ord_id(), "id4");
+    assert_eq!(fills[0].price(), Price::new(602, 0));
+
+    order_book.add_order_to_order_book(create_order_from_string("id5 IBM 130 PEG Sell peg=MARKET".to_string()));
+    let fills: Vec<Fill> = fifo.match_order_book(&mut order_book).into_iter().filter(|f| f.side() == Buy).collect();
+    let filled: Vec<(&str, u32)> = fills.iter().map(|fill| (fill.cl_ord_id(), fill.qty())).collect();
+    assert_eq!(filled, vec![("id3", 20), ("id1", 100)]);
+    let prices: Vec<Price> = fills.iter().map(|fill| fill.price()).collect();
+    assert_eq!(prices, vec![Price::new(602, 0), Price::new(601, 0)]);
+    let id5 = order_book.find_order("id5", None, None).unwrap();
+    assert_eq!(id5.qty(), 10);
+    assert!(id5.price().is_zero());
+    assert_eq!(order_book.pegged_orders().len(), 1);
+
+    order_book.add_order_to_order_book(create_order_from_string("id6 IBM 10 600 Buy".to_string()));
+    let fills: Vec<Fill> = fifo.match_order_book(&mut order_book).into_iter().filter(|f| f.side() == Buy).collect();
+    assert_eq!(fills.len(), 1);
+    assert_eq!(fills[0].cl_ord_id(), "id6");
+    assert_eq!(fills[0].secondary_cl_ord_id(), "id5");
+    assert_eq!(fills[0].price(), Price::new(600, 0));
+}
+
+#[test]
+fn test_post_only_orders_never_cross_on_arrival() {
+    let input = vec![
+        "id1 IBM 100 602 Sell".to_string(),
+        "id2 IBM 100 601.5 Buy".to_string(),
+        "id3 IBM 100 602 Buy postonly=reject".to_string(),
+        "id4 IBM 100 602.5 Buy postonly=reprice".to_string(),
+        "id5 IBM 100 601.5 Sell postonly=reprice".to_string(),
+        "id6 IBM 100 601.8 Buy postonly=reject".to_string(),
+    ];
+    let mut order_book = create_order_book(input);
+    let mut fifo = FIFOMatcher;
+    let reports = fifo.match_order_book(&mut order_book);
+
+    let summary: Vec<(&str, Status, Price)> = reports.iter().map(|r| (r.cl_ord_id(), *r.exec_type(), r.price())).collect();
+    assert_eq!(summary, vec![
+        ("id3", Rejected, Price::new(602, 0)),
+        ("id4", Restated, Price::new(601, 0)),
+        ("id5", Restated, Price::new(6016, 1)),
+        ("id6", Rejected, Price::new(6018, 1)),
+    ]);
+    assert!(reports.iter().all(|r| r.qty() == 0));
+    assert_eq!(reports[0].leaves_qty(), 0);
+    assert_eq!(reports[1].leaves_qty(), 100);
+    assert_eq!(order_book.best_price("IBM", Buy), Some(Price::new(6015, 1)));
+    assert_eq!(order_book.best_price("IBM", Sell), Some(Price::new(6016, 1)));
+    assert!(order_book.take_reports().is_empty());
+}
+
+#[test]
+fn test_self_trade_prevention_cancels_instead_of_trading() {
+    let input = vec![
+        "id1 IBM 100 601 Sell participant=P1".to_string(),
+        "id2 IBM 100 601.5 Sell participant=P2".to_string(),
+        "id3 IBM 150 602 Buy participant=P1 stp=CO".to_string(),
+        "id4 TATA 100 603 Sell participant=P3".to_string(),
+        "id5 TATA 100 603 Buy participant=P3 stp=CN".to_string(),
+    ];
+    let mut order_book = create_order_book(input);
+    let reports = FIFOMatcher.match_order_book(&mut order_book);
+
+    let summary: Vec<(&str, Status, u32)> = reports.iter().map(|r| (r.cl_ord_id(), *r.exec_type(), r.qty())).collect();
+    assert_eq!(summary, vec![
+        ("id1", Cancelled, 0),
+        ("id3", Status::New, 100),
+        ("id2", Status::New, 100),
+        ("id5", Cancelled, 0),
+    ]);
+    assert_eq!(reports[1].leaves_qty(), 50);
+    assert_eq!(order_book.find_order("id3", None, None).unwrap().qty(), 50);
+    assert_eq!(order_book.find_order("id4", None, None).unwrap().qty(), 100);
+    assert!(order_book.find_order("id1", None, None).is_none());
+}
+
+#[test]
+fn test_min_qty_and_all_or_none_orders_are_skipped_without_losing_priority() {
+    let input = vec![
+        "id1 IBM 200 601 Sell aon=Y".to_string(),
+        "id2 IBM 100 601 Sell".to_string(),
+        "id3 IBM 150 601 Buy minqty=150".to_string(),
+        "id4 IBM 50 601 Buy".to_string(),
+    ];
+    let mut order_book = create_order_book(input);
+    let mut fifo = FIFOMatcher;
+    let fills: Vec<Fill> = fifo.match_order_book(&mut order_book).into_iter().filter(|f| f.side() == Buy).collect();
+
+    assert_eq!(fills.len(), 1);
+    assert_eq!(fills[0].cl_ord_id(), "id4");
+    assert_eq!(fills[0].secondary_cl_ord_id(), "id2");
+    let buy_queue: Vec<String> = order_book.get_orders_for(Buy).values().flatten().map(|o| o.cl_ord_id().clone()).collect();
+    assert_eq!(buy_queue, vec!["id3"]);
+
+    order_book.add_order_to_order_book(create_order_from_string("id5 IBM 50 601 Buy".to_string()));
+    order_book.add_order_to_order_book(create_order_from_string("id6 IBM 250 601 Buy".to_string()));
+    let fills: Vec<Fill> = fifo.match_order_book(&mut order_book).into_iter().filter(|f| f.side() == Buy).collect();
+    let filled: Vec<(&str, String, u32)> = fills.iter().map(|f| (f.cl_ord_id(), f.secondary_cl_ord_id(), f.qty())).collect();
+    assert_eq!(filled, vec![("id5", "id2".to_string(), 50), ("id6", "id1".to_string(), 200)]);
+    let buy_queue: Vec<(String, u32)> = order_book.get_orders_for(Buy).values().flatten().map(|o| (o.cl_ord_id().clone(), o.qty())).collect();
+    assert_eq!(buy_queue, vec![("id3".to_string(), 150), ("id6".to_string(), 50)]);
+}
+
+#[test]
+fn test_best_priced_buy_order_is_matched_first() {
+    let input = vec![
+        "id1 IBM 100 601 Buy".to_string(),
+        "id2 IBM 100 602 Buy".to_string(),
+        "id3 IBM 100 600 Sell".to_string(),
+    ];
+    let mut order_book = create_order_book(input);
+    let mut fifo = FIFOMatcher;
+    let fills = fifo.match_order_book(&mut order_book);
+
+    assert_eq!(fills.len(), 2);
+    assert_eq!(fills[0].cl_ord_id(), "id3");
+    assert_eq!(fills[1].cl_ord_id(), "id2");
+    // the sell order arrived last, so it takes the price of the resting buy order
+    assert!(fills.iter().all(|f| f.price() == Price::new(602, 0)));
+    assert_eq!(fills[0].liquidity(), Some(Liquidity::Removed));
+    assert_eq!(fills[1].liquidity(), Some(Liquidity::Added));
+    assert!(order_book.get_orders_for(Sell).is_empty());
+    assert!(order_book.get_orders_for(Buy).contains_key(&OrderBookKey::new(Price::new(601, 0), "IBM".to_string())));
+}
+
 #[test]
 fn test_match_order_multiple_buy_orders_against_a_single_sell_order() {
     let input = read_input("test_data/test1.txt");
     let mut order_book = create_order_book(input);
-    let key2 = OrderBookKey::new(602.5, "TATA".to_string());
+    let key2 = OrderBookKey::new(Price::new(6025, 1), "TATA".to_string());
     let buy = order_book.get_orders_for(Buy);
     let sell = order_book.get_orders_for(Sell);
 
     assert_eq!(buy.len(), 1);
     assert_eq!(sell.len(), 1);
-    assert_eq!(buy.contains_key(&key2), true);
-    assert_eq!(sell.contains_key(&key2), true);
+    assert!(buy.contains_key(&key2));
+    assert!(sell.contains_key(&key2));
 
     let buy_orders = buy.get(&key2).unwrap();
     assert_eq!(buy_orders.len(),2);
@@ -47,4 +424,28 @@ fn test_match_order_multiple_buy_orders_against_a_single_sell_order() {
     println!("{}",s);
     let client_fills: Vec<Fill> = fills.clone().into_iter().filter(|f| f.side() == Buy).collect();
     let ex_fills: Vec<Fill> = fills.clone().into_iter().filter(|f| f.side() == Sell).collect();
-}
\ No newline at end of file
+    assert_eq!(client_fills.len(), 2);
+    assert_eq!(ex_fills.len(), 2);
+    assert_eq!(ex_fills[1].cum_qty(), 50);
+    assert_eq!(ex_fills[1].leaves_qty(), 50);
+}
+#[test]
+fn test_the_order_that_arrived_later_is_the_aggressor() {
+    let input = vec![
+        "id1 IBM 100 601 Sell".to_string(),
+        "id2 IBM 100 603 Buy".to_string(),
+        "id3 IBM 50 600 Sell".to_string(),
+    ];
+    let mut order_book = create_order_book(input);
+    let fills = FIFOMatcher.match_order_book(&mut order_book);
+
+    let trades: Vec<(&str, String, Price, Option<Liquidity>)> = fills.iter()
+        .map(|f| (f.cl_ord_id(), f.secondary_cl_ord_id(), f.price(), f.liquidity()))
+        .collect();
+    // id2 arrived after id1 and takes its price, which fills id2 before id3 arrives, so id3 rests
+    assert_eq!(trades, vec![
+        ("id2", "id1".to_string(), Price::new(601, 0), Some(Liquidity::Removed)),
+        ("id1", "id2".to_string(), Price::new(601, 0), Some(Liquidity::Added)),
+    ]);
+    assert!(order_book.get_orders_for(Sell).contains_key(&OrderBookKey::new(Price::new(600, 0), "IBM".to_string())));
+}
@@ -0,0 +1,59 @@
+use std::str::FromStr;
+
+use matching_engine::common::utils::{create_instrument_registry, create_order_book_with_instruments};
+use matching_engine::matchers::matcher::Matcher;
+use matching_engine::matchers::matcher_registry::{create_matcher, MatcherRegistry};
+use matching_engine::model::domain::{Fill, OrderBook, Side};
+use matching_engine::model::domain::Side::{Buy, Sell};
+
+fn order_book(input: &[&str]) -> OrderBook {
+    let instruments = create_instrument_registry(vec![
+        "ES 0.25 1 1 2 group=FUT".to_string(),
+        "IBM 0.01 1 1 2".to_string(),
+    ]).unwrap();
+    create_order_book_with_instruments(input.iter().map(|line| line.to_string()).collect(), instruments)
+}
+
+fn fills_of<'a>(fills: &'a [Fill], symbol: &str, side: Side) -> Vec<(&'a str, u32)> {
+    fills.iter()
+        .filter(|f| f.side() == side && f.symbol() == symbol)
+        .map(|f| (f.cl_ord_id(), f.qty()))
+        .collect()
+}
+
+#[test]
+fn test_futures_are_matched_pro_rata_and_equities_fifo_in_the_same_book() {
+    let mut order_book = order_book(&[
+        "id1 ES 100 4000 Buy",
+        "id2 ES 300 4000 Buy",
+        "id3 IBM 100 601 Buy",
+        "id4 IBM 100 601 Buy",
+        "id5 ES 200 4000 Sell",
+        "id6 IBM 150 601 Sell",
+    ]);
+    let mut registry = MatcherRegistry::from_str("FIFO,FUT=PRO").unwrap();
+    let fills = registry.match_order_book(&mut order_book);
+
+    // the sell orders are allocated across the buy orders of a level, pro-rata for the futures
+    assert_eq!(fills_of(&fills, "ES", Buy), vec![("id1", 50), ("id2", 150)]);
+    assert_eq!(fills_of(&fills, "IBM", Buy), vec![("id3", 100), ("id4", 50)]);
+}
+
+#[test]
+fn test_market_orders_are_matched_by_the_matcher_of_their_symbol() {
+    let mut order_book = order_book(&[
+        "id1 ES 100 4000 Sell",
+        "id2 ES 300 4000 Sell",
+        "id3 IBM 100 601 Sell",
+        "id4 IBM 100 601 Sell",
+        "id5 ES 200 MKT Buy",
+        "id6 IBM 150 MKT Buy",
+    ]);
+    let mut registry = MatcherRegistry::new(create_matcher("FIFO").unwrap());
+    registry.register("ES", create_matcher("PRO").unwrap());
+    let fills = registry.match_order_book(&mut order_book);
+
+    assert_eq!(fills_of(&fills, "ES", Sell), vec![("id1", 50), ("id2", 150)]);
+    assert_eq!(fills_of(&fills, "IBM", Sell), vec![("id3", 100), ("id4", 50)]);
+    assert!(order_book.immediate_orders().is_empty());
+}
@@ -0,0 +1,71 @@
+use matching_engine::common::utils::create_order_book;
+use matching_engine::matchers::matcher::Matcher;
+use matching_engine::matchers::pipeline_matcher::{AllocationStep, PipelineMatcher};
+use matching_engine::matchers::prorata_matcher::{ProrataMatcher, RemainderPolicy};
+use matching_engine::model::domain::Fill;
+use matching_engine::model::domain::Side::{Buy, Sell};
+use matching_engine::model::price::Price;
+
+fn matcher() -> PipelineMatcher {
+    let steps = vec![AllocationStep::TopOrder { max_qty: 50 }, AllocationStep::Fifo { percent: 40 }, AllocationStep::ProRata];
+    PipelineMatcher::new(steps, ProrataMatcher::new(RemainderPolicy::TimePriority, 0))
+}
+
+fn sell_fills(fills: &[Fill]) -> Vec<(&str, u32)> {
+    fills.iter().filter(|f| f.side() == Sell).map(|f| (f.cl_ord_id(), f.qty())).collect()
+}
+
+#[test]
+fn test_order_that_improved_the_price_gets_the_top_order_allocation() {
+    let input = vec![
+        "id1 IBM 100 601 Sell".to_string(),
+        "id2 IBM 200 601 Sell".to_string(),
+        "id3 IBM 100 601 Sell".to_string(),
+        "id4 IBM 300 601 Buy".to_string(),
+    ];
+    let mut order_book = create_order_book(input);
+    let fills = matcher().match_order_book(&mut order_book);
+
+    assert_eq!(sell_fills(&fills), vec![("id1", 100), ("id2", 140), ("id3", 60)]);
+    assert!(fills.iter().all(|f| f.price() == Price::new(601, 0)));
+    assert_eq!(fills.iter().rfind(|f| f.side() == Buy).unwrap().cum_qty(), 300);
+    assert_eq!(order_book.find_order("id2", None, None).unwrap().qty(), 60);
+    assert_eq!(order_book.find_order("id3", None, None).unwrap().qty(), 40);
+}
+
+#[test]
+fn test_market_order_is_allocated_through_the_pipeline_level_by_level() {
+    let input = vec![
+        "id1 IBM 100 601 Sell".to_string(),
+        "id2 IBM 100 602 Sell".to_string(),
+        "id3 IBM 100 602 Sell".to_string(),
+        "id4 IBM 250 MKT Buy".to_string(),
+    ];
+    let mut order_book = create_order_book(input);
+    let fills = matcher().match_order_book(&mut order_book);
+
+    // id2 did not improve the best price of 601 when it arrived, so 602 has no top order: 40% of 150 goes
+    // to id2 in time priority and the 90 left pro-rata on 40 and 100
+    assert_eq!(sell_fills(&fills), vec![("id1", 100), ("id2", 86), ("id3", 64)]);
+    let market_fills: Vec<&Fill> = fills.iter().filter(|f| f.cl_ord_id() == "id4").collect();
+    assert_eq!(market_fills.last().unwrap().cum_qty(), 250);
+    assert_eq!(market_fills.last().unwrap().leaves_qty(), 0);
+    assert_eq!(order_book.best_price("IBM", Sell), Some(Price::new(602, 0)));
+}
+
+#[test]
+fn test_lead_market_maker_gets_its_guaranteed_share_before_pro_rata() {
+    let input = vec![
+        "id1 IBM 200 601 Sell".to_string(),
+        "id2 IBM 100 601 Sell lmm=Y".to_string(),
+        "id3 IBM 100 601 Sell".to_string(),
+        "id4 IBM 200 601 Buy".to_string(),
+    ];
+    let mut order_book = create_order_book(input);
+    let steps = vec![AllocationStep::LeadMarketMaker { percent: 50 }, AllocationStep::ProRata];
+    let fills = PipelineMatcher::new(steps, ProrataMatcher::default()).match_order_book(&mut order_book);
+
+    // half of the 200 goes to the lead market maker and the other half pro-rata on 200 and 100
+    assert_eq!(sell_fills(&fills), vec![("id1", 67), ("id2", 100), ("id3", 33)]);
+    assert!(order_book.find_order("id2", None, None).is_none());
+}
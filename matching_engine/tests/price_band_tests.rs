@@ -0,0 +1,108 @@
+use matching_engine::common::utils::{create_instrument_registry, create_order_from_string};
+use matching_engine::matchers::auction::CallAuction;
+use matching_engine::matchers::fifo_matcher::FIFOMatcher;
+use matching_engine::matchers::matcher::Matcher;
+use matching_engine::matchers::matcher_registry::{create_matcher, MatcherRegistry};
+use matching_engine::matchers::pipeline_matcher::{AllocationStep, PipelineMatcher};
+use matching_engine::matchers::prorata_matcher::ProrataMatcher;
+use matching_engine::model::domain::OrderBook;
+use matching_engine::model::domain::Side::{Buy, Sell};
+use matching_engine::model::price::Price;
+use matching_engine::model::session::TradingPhase::{Continuous, Halted};
+
+fn banded_order_book(instrument: &str, reference_price: Price, input: &[&str]) -> OrderBook {
+    let instruments = create_instrument_registry(vec![instrument.to_string()]).unwrap();
+    let mut order_book = OrderBook::with_instruments(instruments);
+    order_book.set_reference_price("IBM", reference_price);
+    for line in input {
+        add_order(&mut order_book, line).unwrap();
+    }
+    order_book
+}
+
+fn add_order(order_book: &mut OrderBook, line: &str) -> Result<(), String> {
+    let order = create_order_from_string(line.to_string());
+    order_book.validate_order(&order)?;
+    order_book.add_order_to_order_book(order);
+    Ok(())
+}
+
+#[test]
+fn test_orders_priced_outside_the_band_are_rejected() {
+    let mut order_book = banded_order_book("IBM 0.1 1 1 2 band=5%", Price::new(600, 0), &[]);
+
+    assert_eq!(order_book.price_band_limits("IBM"), Some((Price::new(570, 0), Price::new(630, 0))));
+    assert!(add_order(&mut order_book, "id1 IBM 100 630 Buy").is_ok());
+    assert!(add_order(&mut order_book, "id2 IBM 100 569.9 Sell").is_err());
+    assert!(add_order(&mut order_book, "id3 IBM 100 630.1 Buy").is_err());
+    assert!(add_order(&mut order_book, "id4 IBM 100 MKT Sell").is_ok());
+
+    let instruments = create_instrument_registry(vec!["IBM 0.1 1 1 2 band=5%".to_string()]).unwrap();
+    let mut unreferenced = OrderBook::with_instruments(instruments);
+    assert!(unreferenced.price_band_limits("IBM").is_none());
+    assert!(add_order(&mut unreferenced, "id5 IBM 100 700 Buy").is_ok());
+}
+
+#[test]
+fn test_sweep_through_the_dynamic_band_halts_the_symbol_into_a_volatility_auction() {
+    let mut order_book = banded_order_book("IBM 0.1 1 1 2 dynband=1%", Price::new(600, 0), &[
+        "id1 IBM 100 601 Sell",
+        "id2 IBM 100 605 Sell",
+        "id3 IBM 100 610 Sell",
+        "id4 IBM 250 MKT Buy",
+    ]);
+
+    let fills = FIFOMatcher.match_order_book(&mut order_book);
+    let buy_fills: Vec<(Price, u32)> = fills.iter().filter(|f| f.side() == Buy).map(|f| (f.price(), f.qty())).collect();
+    assert_eq!(buy_fills, vec![(Price::new(601, 0), 100), (Price::new(605, 0), 100)]);
+    assert_eq!(order_book.phase("IBM"), Halted);
+    assert_eq!(order_book.take_status_events().len(), 1);
+    assert_eq!(order_book.immediate_orders()[0].qty(), 50);
+
+    add_order(&mut order_book, "id5 IBM 100 612 Buy").unwrap();
+    assert!(FIFOMatcher.match_order_book(&mut order_book).is_empty());
+
+    let fills = CallAuction::default().change_phase(&mut order_book, "IBM", Continuous, &mut FIFOMatcher).unwrap();
+    assert!(fills.iter().filter(|f| f.qty() > 0).all(|f| f.price() == Price::new(610, 0)));
+    assert_eq!(fills.iter().filter(|f| f.side() == Sell).map(|f| f.qty()).sum::<u32>(), 100);
+    assert_eq!(order_book.reference_price("IBM"), Some(Price::new(610, 0)));
+    assert_eq!(order_book.find_order("id5", None, None).unwrap().qty(), 50);
+}
+
+#[test]
+fn test_resting_buy_crossing_past_the_dynamic_band_halts_after_the_trades_inside_it() {
+    let mut order_book = banded_order_book("IBM 0.1 1 1 2 dynband=2", Price::new(600, 0), &[
+        "id1 IBM 100 601 Sell",
+        "id2 IBM 100 603 Sell",
+        "id3 IBM 150 603 Buy",
+    ]);
+
+    let fills = FIFOMatcher.match_order_book(&mut order_book);
+    assert_eq!(fills.len(), 2);
+    assert_eq!(fills[0].price(), Price::new(601, 0));
+    assert_eq!(order_book.phase("IBM"), Halted);
+    assert_eq!(order_book.find_order("id3", None, None).unwrap().qty(), 50);
+    assert_eq!(order_book.best_price("IBM", Sell), Some(Price::new(603, 0)));
+}
+
+#[test]
+fn test_pro_rata_and_pipeline_symbols_of_a_registry_halt_at_the_dynamic_band() {
+    let steps = vec![AllocationStep::TopOrder { max_qty: 50 }, AllocationStep::ProRata];
+    for matcher in [create_matcher("PRO").unwrap(), Box::new(PipelineMatcher::new(steps, ProrataMatcher::default()))] {
+        let mut registry = MatcherRegistry::new(Box::new(FIFOMatcher));
+        registry.register("IBM", matcher);
+        let mut order_book = banded_order_book("IBM 0.1 1 1 2 dynband=1%", Price::new(600, 0), &[
+            "id1 IBM 100 601 Sell",
+            "id2 IBM 100 605 Sell",
+            "id3 IBM 100 610 Sell",
+            "id4 IBM 250 MKT Buy",
+        ]);
+
+        let fills = registry.match_order_book(&mut order_book);
+        let buy_fills: Vec<(Price, u32)> = fills.iter().filter(|f| f.side() == Buy).map(|f| (f.price(), f.qty())).collect();
+        assert_eq!(buy_fills, vec![(Price::new(601, 0), 100), (Price::new(605, 0), 100)]);
+        assert_eq!(order_book.phase("IBM"), Halted);
+        assert_eq!(order_book.immediate_orders()[0].qty(), 50);
+        assert_eq!(order_book.best_price("IBM", Sell), Some(Price::new(610, 0)));
+    }
+}
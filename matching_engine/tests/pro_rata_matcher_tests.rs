@@ -1,24 +1,71 @@
 use matching_engine::matchers::matcher::Matcher;
 use matching_engine::matchers::prorata_matcher::ProrataMatcher;
-use matching_engine::model::domain::{Fill, OrderBook};
+use matching_engine::model::domain::Fill;
+use matching_engine::model::price::Price;
 use matching_engine::model::domain::Side::{Buy, Sell};
-use matching_engine::common::utils::{create_order_book,read_input};
+use matching_engine::common::utils::{create_order_book, create_order_from_string, read_input};
 
 #[test]
 fn test_match_order_book() {
     let mut order_book = create_order_book(read_input("test_data/orders.txt"));
-    let mut pro = ProrataMatcher;
+    let mut pro = ProrataMatcher::default();
     let fills = pro.match_order_book(&mut order_book);
 
     let client_fills: Vec<Fill> = fills.clone().into_iter().filter(|f| f.side() == Buy).collect();
     let exchange_fills: Vec<Fill> = fills.clone().into_iter().filter(|f| f.side() == Sell).collect();
 
-    assert_eq!(client_fills.len(), 4);
-    assert_eq!(exchange_fills.len(), 4);
+    assert_eq!(client_fills.len(), 2);
+    assert_eq!(exchange_fills.len(), 2);
     assert_eq!(order_book.get_orders_for(Buy).len(), 3);
     assert_eq!(order_book.get_orders_for(Sell).len(), 1);
     let symbols = order_book.get_excl_keys();
     for symbol in symbols {
         order_book.print_market_depth_for(symbol);
     }
-}
\ No newline at end of file
+}
+#[test]
+fn test_a_crossing_buy_that_arrives_last_is_allocated_across_the_sells() {
+    let input = vec![
+        "s1 IBM 300 600 Sell".to_string(),
+        "s2 IBM 100 600 Sell".to_string(),
+        "s3 IBM 100 602 Sell".to_string(),
+        "b1 IBM 200 603 Buy".to_string(),
+    ];
+    let mut order_book = create_order_book(input.clone());
+    let fills = ProrataMatcher::default().match_order_book(&mut order_book);
+    let trades: Vec<(&str, u32, Price)> = fills.iter().filter(|f| f.side() == Sell).map(|f| (f.cl_ord_id(), f.qty(), f.price())).collect();
+
+    assert_eq!(trades, vec![("s1", 150, Price::new(600, 0)), ("s2", 50, Price::new(600, 0))]);
+    assert_eq!(order_book.find_order("s1", None, None).unwrap().qty(), 150);
+    assert_eq!(order_book.find_order("s2", None, None).unwrap().qty(), 50);
+    assert_eq!(order_book.best_price("IBM", Sell), Some(Price::new(600, 0)));
+    assert!(order_book.get_orders_for(Buy).is_empty());
+
+    let mut submitted = create_order_book(vec![]);
+    let mut pro = ProrataMatcher::default();
+    let submit_fills: Vec<Fill> = input.into_iter()
+        .flat_map(|line| pro.submit(&mut submitted, create_order_from_string(line)))
+        .collect();
+    let submit_trades: Vec<(&str, u32, Price)> = submit_fills.iter().filter(|f| f.side() == Sell).map(|f| (f.cl_ord_id(), f.qty(), f.price())).collect();
+    assert_eq!(submit_trades, trades);
+}
+
+#[test]
+fn test_the_minimum_quantity_of_an_incoming_order_is_met_across_the_levels_it_sweeps() {
+    let input = vec![
+        "id1 IBM 100 600 Sell".to_string(),
+        "id2 IBM 100 601 Sell".to_string(),
+        "id3 IBM 250 601 Buy minqty=250".to_string(),
+        "id4 IBM 150 601 Buy minqty=150".to_string(),
+    ];
+    let mut order_book = create_order_book(input);
+    let fills = ProrataMatcher::default().match_order_book(&mut order_book);
+    let filled: Vec<(&str, String, u32, Price)> = fills.iter().filter(|f| f.side() == Buy)
+        .map(|f| (f.cl_ord_id(), f.secondary_cl_ord_id(), f.qty(), f.price()))
+        .collect();
+
+    // id3 cannot fill 250 in one go and rests, id4 fills its 150 across both levels
+    assert_eq!(filled, vec![("id4", "id1".to_string(), 100, Price::new(600, 0)), ("id4", "id2".to_string(), 50, Price::new(601, 0))]);
+    assert_eq!(order_book.find_order("id3", None, None).unwrap().qty(), 250);
+    assert_eq!(order_book.find_order("id2", None, None).unwrap().qty(), 50);
+}
@@ -0,0 +1,186 @@
+use matching_engine::common::utils::{create_order_book, create_order_from_string};
+use matching_engine::matchers::fifo_matcher::FIFOMatcher;
+use matching_engine::matchers::matcher::Matcher;
+use matching_engine::matchers::pipeline_matcher::{AllocationStep, PipelineMatcher};
+use matching_engine::matchers::prorata_matcher::ProrataMatcher;
+use matching_engine::model::domain::{Fill, Liquidity, OrderBook, Status};
+use matching_engine::model::price::Price;
+use matching_engine::model::domain::Liquidity::{Added, Removed};
+use matching_engine::model::domain::Side::{Buy, Sell};
+use matching_engine::model::domain::Status::{Cancelled, Filled, PartialFill, Rejected};
+use matching_engine::model::session::TradingPhase::Closed;
+
+fn submit(matcher: &mut impl Matcher, order_book: &mut OrderBook, line: &str) -> Vec<Fill> {
+    matcher.submit(order_book, create_order_from_string(line.to_string()))
+}
+
+fn reports(fills: &[Fill]) -> Vec<(&str, u32, Status)> {
+    fills.iter().map(|f| (f.cl_ord_id(), f.qty(), *f.status())).collect()
+}
+
+type Trade = (String, String, u32, Price, Option<Liquidity>, Status, u32, u32);
+
+fn trades(fills: &[Fill]) -> Vec<Trade> {
+    fills.iter()
+        .map(|f| (f.cl_ord_id().to_string(), f.secondary_cl_ord_id(), f.qty(), f.price(), f.liquidity(), *f.status(), f.cum_qty(), f.leaves_qty()))
+        .collect()
+}
+
+fn assert_batch_matches_submit(mut batch: impl Matcher, mut incremental: impl Matcher, input: &[&str]) {
+    let mut loaded = create_order_book(input.iter().map(|line| line.to_string()).collect());
+    let batch_fills = batch.match_order_book(&mut loaded);
+    let mut order_book = OrderBook::default();
+    let mut submitted_fills: Vec<Fill> = input.iter().flat_map(|line| submit(&mut incremental, &mut order_book, line)).collect();
+    // the loaded book is matched one symbol at a time
+    submitted_fills.sort_by(|a, b| a.symbol().cmp(b.symbol()));
+    assert_eq!(trades(&batch_fills), trades(&submitted_fills));
+}
+
+#[test]
+fn test_a_loaded_book_is_matched_in_arrival_order() {
+    let mut order_book = create_order_book(vec![
+        "B1 IBM 100 602 Buy".to_string(),
+        "S1 IBM 100 601 Sell".to_string(),
+        "B2 IBM 100 603 Buy".to_string(),
+    ]);
+    let fills = FIFOMatcher.match_order_book(&mut order_book);
+    let filled: Vec<(&str, String, Price)> = fills.iter().map(|f| (f.cl_ord_id(), f.secondary_cl_ord_id(), f.price())).collect();
+    assert_eq!(filled, vec![
+        ("S1", "B1".to_string(), Price::new(602, 0)),
+        ("B1", "S1".to_string(), Price::new(602, 0)),
+    ]);
+    assert_eq!(order_book.best_price("IBM", Buy), Some(Price::new(603, 0)));
+}
+
+#[test]
+fn test_matching_a_loaded_book_and_submitting_its_orders_produce_the_same_fills() {
+    let input = [
+        "B1 IBM 100 602 Buy",
+        "S1 IBM 100 601 Sell",
+        "B2 IBM 100 603 Buy",
+        "S2 IBM 50 600 Sell",
+        "B3 IBM 200 603 Buy stop=601",
+        "S3 IBM 120 602.5 Sell",
+        "B4 IBM 80 MKT Buy",
+        "S4 TATA 100 99 Sell",
+        "B5 TATA 60 100 Buy tif=IOC",
+        "B6 IBM 40 PEG Buy peg=PRIMARY",
+        "S5 IBM 70 604 Sell",
+        "S6 TATA 40 98 Sell",
+        "B7 TATA 100 99 Buy",
+    ];
+    assert_batch_matches_submit(FIFOMatcher, FIFOMatcher, &input);
+    let pipeline = || PipelineMatcher::new(vec![AllocationStep::TopOrder { max_qty: 50 }, AllocationStep::ProRata], ProrataMatcher::default());
+    assert_batch_matches_submit(pipeline(), pipeline(), &input);
+    assert_batch_matches_submit(ProrataMatcher::default(), ProrataMatcher::default(), &input);
+}
+
+#[test]
+fn test_submitted_order_matches_the_contra_side_and_rests_the_remainder() {
+    let mut order_book = OrderBook::default();
+    let mut matcher = FIFOMatcher;
+    assert!(submit(&mut matcher, &mut order_book, "id1 IBM 100 601 Sell").is_empty());
+    assert!(submit(&mut matcher, &mut order_book, "id2 IBM 100 602 Sell").is_empty());
+
+    let fills = submit(&mut matcher, &mut order_book, "id3 IBM 150 602 Buy");
+
+    assert_eq!(reports(&fills), vec![("id3", 100, PartialFill), ("id1", 100, Filled), ("id3", 50, Filled), ("id2", 50, PartialFill)]);
+    assert!(fills.iter().all(|f| f.liquidity() == Some(if f.cl_ord_id() == "id3" { Removed } else { Added })));
+    assert_eq!(order_book.best_price("IBM", Sell).unwrap().to_string(), "602");
+    assert!(order_book.levels("IBM", Buy).map_or(true, |levels| levels.is_empty()));
+
+    let fills = submit(&mut matcher, &mut order_book, "id4 IBM 100 602 Buy");
+    assert_eq!(reports(&fills), vec![("id4", 50, PartialFill), ("id2", 50, Filled)]);
+    assert_eq!(order_book.find_order("id4", None, None).unwrap().qty(), 50);
+}
+
+#[test]
+fn test_only_the_submitted_order_is_matched() {
+    let mut order_book = OrderBook::default();
+    order_book.add_order_to_order_book(create_order_from_string("id1 TATA 100 50 Buy".to_string()));
+    order_book.add_order_to_order_book(create_order_from_string("id2 TATA 100 50 Sell".to_string()));
+
+    let fills = submit(&mut FIFOMatcher, &mut order_book, "id3 IBM 100 601 Buy");
+
+    assert!(fills.is_empty());
+    assert!(order_book.find_order("id1", None, None).is_some());
+    assert!(order_book.find_order("id2", None, None).is_some());
+    assert!(order_book.find_order("id3", None, None).is_some());
+}
+
+#[test]
+fn test_immediate_remainders_are_cancelled_and_triggered_stops_are_matched() {
+    let mut order_book = OrderBook::default();
+    let mut matcher = FIFOMatcher;
+    submit(&mut matcher, &mut order_book, "id1 IBM 100 600 Sell");
+    submit(&mut matcher, &mut order_book, "id2 IBM 100 605 Sell");
+    submit(&mut matcher, &mut order_book, "id3 IBM 50 MKT Buy stop=600");
+
+    let fills = submit(&mut matcher, &mut order_book, "id4 IBM 150 600 Buy tif=IOC");
+
+    assert_eq!(reports(&fills), vec![
+        ("id4", 100, PartialFill), ("id1", 100, Filled), ("id4", 0, Cancelled),
+        ("id3", 50, Filled), ("id2", 50, PartialFill),
+    ]);
+    assert!(order_book.stop_orders().is_empty());
+}
+
+#[test]
+fn test_a_triggered_stop_is_matched_on_its_own_against_the_queue_as_it_stands() {
+    let mut order_book = OrderBook::default();
+    let mut matcher = FIFOMatcher;
+    submit(&mut matcher, &mut order_book, "id1 IBM 150 600 Sell display=50");
+    submit(&mut matcher, &mut order_book, "id2 IBM 100 600 Sell");
+    submit(&mut matcher, &mut order_book, "id3 IBM 100 600 Buy stop=600");
+
+    // the peak of id1 is consumed and its new peak queues behind id2, which the triggered stop trades first
+    let fills = submit(&mut matcher, &mut order_book, "id4 IBM 50 600 Buy");
+
+    assert_eq!(reports(&fills), vec![
+        ("id4", 50, Filled), ("id1", 50, PartialFill),
+        ("id3", 100, Filled), ("id2", 100, Filled),
+    ]);
+    assert_eq!(order_book.find_order("id1", None, None).unwrap().qty(), 100);
+}
+
+#[test]
+fn test_a_repriced_pegged_order_is_matched_on_its_own_against_the_queue_as_it_stands() {
+    let mut order_book = OrderBook::default();
+    let mut matcher = FIFOMatcher;
+    submit(&mut matcher, &mut order_book, "id1 IBM 150 601 Sell display=50");
+    submit(&mut matcher, &mut order_book, "id2 IBM 100 601 Sell");
+    submit(&mut matcher, &mut order_book, "id3 IBM 50 601 Buy");
+    submit(&mut matcher, &mut order_book, "id4 IBM 100 599 Buy");
+    submit(&mut matcher, &mut order_book, "id5 IBM 100 PEG Buy peg=PRIMARY offset=1");
+
+    // the bid moves to 600, so the pegged order follows it to 601 and trades id2 ahead of the new peak of id1
+    let fills = submit(&mut matcher, &mut order_book, "id6 IBM 10 600 Buy");
+
+    assert_eq!(reports(&fills), vec![("id5", 100, Filled), ("id2", 100, Filled)]);
+    assert_eq!(order_book.find_order("id1", None, None).unwrap().qty(), 100);
+}
+
+#[test]
+fn test_invalid_orders_are_rejected() {
+    let mut order_book = OrderBook::default();
+    order_book.set_phase("IBM", Closed).unwrap();
+
+    let fills = submit(&mut FIFOMatcher, &mut order_book, "id1 IBM 100 600 Sell");
+
+    assert_eq!(reports(&fills), vec![("id1", 0, Rejected)]);
+    assert!(order_book.is_empty());
+}
+
+#[test]
+fn test_submitted_order_is_allocated_pro_rata() {
+    let mut order_book = OrderBook::default();
+    let mut matcher = ProrataMatcher::default();
+    submit(&mut matcher, &mut order_book, "id1 IBM 100 600 Buy");
+    submit(&mut matcher, &mut order_book, "id2 IBM 300 600 Buy");
+
+    let fills = submit(&mut matcher, &mut order_book, "id3 IBM 200 600 Sell");
+
+    let buys: Vec<(&str, u32)> = fills.iter().filter(|f| f.side() == Buy).map(|f| (f.cl_ord_id(), f.qty())).collect();
+    assert_eq!(buys, vec![("id1", 50), ("id2", 150)]);
+    assert_eq!(order_book.find_order("id2", None, None).unwrap().qty(), 150);
+}
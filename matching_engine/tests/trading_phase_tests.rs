@@ -0,0 +1,89 @@
+use chrono::NaiveTime;
+
+use matching_engine::common::utils::{create_order_book, create_order_from_string, create_session_schedule};
+use matching_engine::matchers::auction::CallAuction;
+use matching_engine::matchers::fifo_matcher::FIFOMatcher;
+use matching_engine::matchers::matcher::Matcher;
+use matching_engine::model::domain::OrderBook;
+use matching_engine::model::domain::Side::Buy;
+use matching_engine::model::session::PhaseChange;
+use matching_engine::model::session::TradingPhase::{ClosingAuction, Closed, Continuous, Halted, OpeningAuction, PreOpen};
+
+fn add_order(order_book: &mut OrderBook, line: &str) -> Result<(), String> {
+    let order = create_order_from_string(line.to_string());
+    order_book.validate_order(&order)?;
+    order_book.add_order_to_order_book(order);
+    Ok(())
+}
+
+#[test]
+fn test_orders_are_queued_before_the_open_and_rejected_after_the_close() {
+    let mut order_book = create_order_book(vec![]);
+    order_book.set_phase("IBM", Closed).unwrap();
+    assert!(add_order(&mut order_book, "id1 IBM 100 601 Buy").is_err());
+
+    order_book.set_phase("IBM", PreOpen).unwrap();
+    add_order(&mut order_book, "id1 IBM 100 601 Buy").unwrap();
+    add_order(&mut order_book, "id2 IBM 100 600 Sell").unwrap();
+    assert!(FIFOMatcher.match_order_book(&mut order_book).is_empty());
+    assert!(CallAuction::default().change_phase(&mut order_book, "IBM", Continuous, &mut FIFOMatcher).is_err());
+
+    let mut auction = CallAuction::default();
+    assert!(auction.change_phase(&mut order_book, "IBM", OpeningAuction, &mut FIFOMatcher).unwrap().is_empty());
+    let fills = auction.change_phase(&mut order_book, "IBM", Continuous, &mut FIFOMatcher).unwrap();
+    assert_eq!(fills.iter().filter(|fill| fill.side() == Buy).map(|fill| fill.qty()).sum::<u32>(), 100);
+
+    add_order(&mut order_book, "id3 IBM 50 602 Buy").unwrap();
+    add_order(&mut order_book, "id4 IBM 50 602 Sell").unwrap();
+    assert_eq!(FIFOMatcher.match_order_book(&mut order_book).len(), 2);
+
+    auction.change_phase(&mut order_book, "IBM", ClosingAuction, &mut FIFOMatcher).unwrap();
+    auction.change_phase(&mut order_book, "IBM", Closed, &mut FIFOMatcher).unwrap();
+    assert!(add_order(&mut order_book, "id5 IBM 50 602 Buy").is_err());
+
+    let events: Vec<String> = order_book.take_status_events().iter().map(PhaseChange::to_string).collect();
+    assert_eq!(events, vec![
+        "IBM Continuous -> Closed",
+        "IBM Closed -> PreOpen",
+        "IBM PreOpen -> OpeningAuction",
+        "IBM OpeningAuction -> Continuous",
+        "IBM Continuous -> ClosingAuction",
+        "IBM ClosingAuction -> Closed",
+    ]);
+    assert!(order_book.take_status_events().is_empty());
+}
+
+#[test]
+fn test_schedule_drives_the_phases_and_a_halt_only_stops_its_symbol() {
+    let input = vec![
+        "id1 IBM 100 601 Buy".to_string(),
+        "id2 TATA 10 600 Buy".to_string(),
+    ];
+    let mut order_book = create_order_book(input);
+    let mut schedule = create_session_schedule(vec![
+        "10:00 IBM Halted".to_string(),
+        "10:05 IBM Continuous".to_string(),
+        // TATA cannot move from continuous trading back to the pre-open, so this change is skipped
+        "10:05 TATA PreOpen".to_string(),
+    ]).unwrap();
+    let time = |h, m| NaiveTime::from_hms_opt(h, m, 0).unwrap();
+    let mut auction = CallAuction::default();
+
+    assert!(auction.run_schedule(&mut order_book, &mut schedule, time(10, 0), &mut FIFOMatcher).is_empty());
+    assert_eq!(order_book.phase("IBM"), Halted);
+    add_order(&mut order_book, "id3 IBM 100 600 Sell").unwrap();
+    add_order(&mut order_book, "id4 TATA 10 600 Sell").unwrap();
+    let fills = FIFOMatcher.match_order_book(&mut order_book);
+    assert!(fills.iter().all(|fill| fill.symbol() == "TATA"));
+    assert_eq!(fills.len(), 2);
+
+    let fills = auction.run_schedule(&mut order_book, &mut schedule, time(10, 30), &mut FIFOMatcher);
+    assert_eq!(fills.len(), 2);
+    assert!(fills.iter().all(|fill| fill.symbol() == "IBM"));
+    assert_eq!(order_book.phase("IBM"), Continuous);
+    assert_eq!(order_book.phase("TATA"), Continuous);
+    let events = order_book.take_status_events();
+    assert_eq!(events.len(), 2);
+    assert!(events.iter().all(|event| event.symbol() == "IBM"));
+    assert!(schedule.due(time(23, 59)).is_empty());
+}